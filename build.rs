@@ -0,0 +1,24 @@
+use std::process::Command;
+
+/// Trimmed standard output of a git command on the sources, `None` if it could not be run or failed
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Revision of the sources the binary is built from, read by the metadata of the results
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=src");
+
+    if let Some(revision) = git(&["rev-parse", "HEAD"]).filter(|r| !r.is_empty()) {
+        println!("cargo:rustc-env=GIT_REVISION={}", revision);
+        if let Some(status) = git(&["status", "--porcelain", "--untracked-files=no"]) {
+            println!("cargo:rustc-env=GIT_DIRTY={}", !status.is_empty());
+        }
+    }
+}
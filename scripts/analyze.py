@@ -351,15 +351,22 @@ for path in paths:
     
     print("reading config...")
     js = None
+    header_rows = 0
     with open(path) as f:
-        js = f.readline()
-        js = json.loads(js[1:-2])
-        pprint(js)
+        first_line = f.readline()
+    if first_line.startswith('"{'):
+        # older results files start with the configuration as a quoted JSON line
+        js = json.loads(first_line[1:-2])
+        header_rows = 1
+    else:
+        with open(os.path.splitext(path)[0] + ".json") as f:
+            js = json.load(f)["configuration"]
+    pprint(js)
     
     print("reading chunks...")
     df = None
     l = []
-    reader = pd.read_table(path, sep=",", chunksize=500*1024, skiprows=1)
+    reader = pd.read_table(path, sep=",", chunksize=500*1024, skiprows=header_rows)
     
     i = 0
     for chunk in reader:
@@ -0,0 +1,45 @@
+"""Example evaluator for the external scorer: scores real-valued genomes with 1 / (1 + sum of squares).
+
+Reads one JSON array of genomes per line on stdin and writes one JSON array of scores per line on stdout.
+Used with the Rastrigin genomes, which serialize as {"value": [x1, x2, ...]}:
+
+    {"problem": "rastrigin", "scorer": {"name": "external", "parameters": {"command": "python3 scripts/sphere_evaluator.py"}}}
+
+A string command is split on whitespace: when a path holds spaces, give the command as a list instead,
+such as ["python3", "my scripts/sphere_evaluator.py"].
+
+The --crash-every, --garbage-every and --hang-every options misbehave on every n-th request,
+to check how the testbed handles failing evaluators.
+"""
+import argparse
+import json
+import sys
+import time
+
+
+def score(genome):
+    values = genome["value"] if isinstance(genome, dict) else genome
+    return 1.0 / (1.0 + sum(x * x for x in values))
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--crash-every", type=int, default=0)
+    parser.add_argument("--garbage-every", type=int, default=0)
+    parser.add_argument("--hang-every", type=int, default=0)
+    args = parser.parse_args()
+
+    for n, line in enumerate(sys.stdin, start=1):
+        if args.crash_every and n % args.crash_every == 0:
+            sys.exit(3)
+        if args.garbage_every and n % args.garbage_every == 0:
+            print("not a score", flush=True)
+            continue
+        if args.hang_every and n % args.hang_every == 0:
+            time.sleep(3600)
+        genomes = json.loads(line)
+        print(json.dumps([score(g) for g in genomes]), flush=True)
+
+
+if __name__ == "__main__":
+    main()
@@ -1,16 +1,19 @@
-use crate::organism::grid::Grid;
-use crate::organism::{Organism,OrganismGenerator};
-use crate::features::FeatureMapper;
+use crate::organism::Organism;
+use crate::organism::lineage::Lineage;
 
 use crate::common::*;
 use crate::algorithm::selection::Elitism;
 use std::rc::Rc;
-use crate::problems::Environment;
-use crate::scoring::Scorer;
 use crate::algorithm::config::ProblemConfig;
 
 
 pub trait ReplacementSelection<V,P,H>: Named + Parametrized {
+    /// Parameters of a run with the given population size and problem instance,
+    /// or an error message if the algorithm cannot be set up for them.
+    fn run_parameters(&self, _pop_size: usize, _problem: &P, _problem_config: &ProblemConfig<V,P,H>) -> Result<serde_json::Value, String> {
+        Ok(self.parameters())
+    }
+
     fn initialize_solver(
         &self,
         pop_size: usize,
@@ -21,8 +24,55 @@ pub trait ReplacementSelection<V,P,H>: Named + Parametrized {
 }
 
 
+/// What happened to the offspring produced by the last update
+#[derive(Clone, Debug, Default)]
+pub struct UpdateReport {
+    pub accepted: bool,
+    /// Whether the mutation changed the genotype of the offspring
+    pub mutation_changed: bool,
+    pub offspring_score: f64,
+    /// Score of the organism the offspring competed with, `None` if it went to an empty niche
+    pub competitor_score: Option<f64>,
+    pub offspring_hyperparameters: Option<Vec<f64>>,
+    pub offspring_lineage: Option<Lineage>
+}
+
+/// An organism kept by a solver, with its place in the solver's archive
+pub struct ArchiveEntry<'a, V> {
+    pub organism: &'a Organism<V>,
+    /// Features of the niche the organism occupies, for solvers using a feature mapper,
+    /// or why they could not be serialised
+    pub features: Option<Result<serde_json::Value, String>>,
+    /// Coordinates of the grid cell the organism lives in, for grid based solvers
+    pub cell: Option<Vec<usize>>
+}
+
+impl<'a, V> ArchiveEntry<'a, V> {
+    pub fn organism(organism: &'a Organism<V>) -> Self {
+        ArchiveEntry {
+            organism,
+            features: None,
+            cell: None
+        }
+    }
+}
+
 pub trait UpdatableSolver<V> {
     fn update(&mut self) -> Vec<Organism<V>>;
+    fn last_update(&self) -> &UpdateReport;
+    /// Reports of all the offspring of the last update, for solvers producing them in batches
+    fn last_updates(&self) -> &[UpdateReport] {
+        std::slice::from_ref(self.last_update())
+    }
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>>;
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>>;
+    /// Forgets the scores kept by the solver after a change of the fitness landscape, organisms are
+    /// scored again the next time they are compared
+    fn invalidate_scores(&mut self) {
+        for organism in self.organisms_mut() {
+            organism.forget_score();
+        }
+    }
 }
 
 
@@ -1,9 +1,9 @@
 use std::rc::Rc;
 use crate::organism::OrganismGenerator;
 use crate::problems::{ProblemInstanceGenerator, Environment};
-use crate::features::FeatureMapper;
 use crate::scoring::Scorer;
 use crate::algorithm::mutation::Mutator;
+use crate::algorithm::evaluation::ReEvaluation;
 
 pub struct ProblemConfig<V,P,H> {
     pub random_organism_generator: Rc<dyn OrganismGenerator<V,P>>,
@@ -11,7 +11,9 @@ pub struct ProblemConfig<V,P,H> {
     pub constant_hyperparameters: H,
     pub hyperparameter_mapper: Rc<dyn Environment<H>>,
     pub scorer: Rc<dyn Scorer<V,P>>,
-    pub mutator: Rc<dyn Mutator<V,H>>
+    pub mutator: Rc<dyn Mutator<V,H>>,
+    /// When organisms already scored are scored again, for noisy scorers
+    pub re_evaluation: ReEvaluation
 }
 
 impl<V,P,H: Clone> Clone for ProblemConfig<V,P,H> {
@@ -22,7 +24,8 @@ impl<V,P,H: Clone> Clone for ProblemConfig<V,P,H> {
             constant_hyperparameters: self.constant_hyperparameters.clone(),
             hyperparameter_mapper: self.hyperparameter_mapper.clone(),
             scorer: self.scorer.clone(),
-            mutator: self.mutator.clone()
+            mutator: self.mutator.clone(),
+            re_evaluation: self.re_evaluation
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,51 @@
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+
+/// When the solvers score again organisms already scored, for noisy scorers whose first score
+/// of an organism may be lucky. Offspring are always scored when created, the strategies differ
+/// in how many samples they average and in what happens to incumbents kept from earlier iterations.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ReEvaluation {
+    /// Organisms are scored once
+    #[default]
+    Never,
+    /// Incumbents get one more sample, averaged with their previous ones, every time they are compared
+    Incumbent,
+    /// Every score is the mean of `samples` evaluations
+    Average { samples: usize },
+    /// Incumbents scored `max_age` iterations ago or more are scored again when compared
+    Age { max_age: u64 }
+}
+
+impl ReEvaluation {
+    /// Evaluations averaged for a new score
+    pub fn samples(&self) -> usize {
+        match self {
+            ReEvaluation::Average { samples } => (*samples).max(1),
+            _ => 1
+        }
+    }
+}
+
+impl Named for ReEvaluation {
+    fn name(&self) -> String {
+        match self {
+            ReEvaluation::Never => "No re-evaluation",
+            ReEvaluation::Incumbent => "Incumbent re-sampling",
+            ReEvaluation::Average { .. } => "Average of samples",
+            ReEvaluation::Age { .. } => "Age-based re-evaluation"
+        }.to_string()
+    }
+}
+
+impl Parametrized for ReEvaluation {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        match self {
+            ReEvaluation::Average { samples } => { hm.insert("samples".to_string(), (*samples).into()); },
+            ReEvaluation::Age { max_age } => { hm.insert("max age".to_string(), (*max_age).into()); },
+            _ => {}
+        }
+        Value::Object(hm)
+    }
+}
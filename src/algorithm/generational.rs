@@ -0,0 +1,155 @@
+use std::rc::Rc;
+use rand::{thread_rng, Rng};
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver, UpdateReport, ArchiveEntry};
+use crate::algorithm::config::ProblemConfig;
+use crate::algorithm::selection::Elitism;
+use crate::organism::Organism;
+use crate::problems::Hyperparameter;
+
+/// Replaces the whole population at every update: the `elites` best organisms are kept and the
+/// others are offspring of parents chosen by tournaments, scored together as one batch.
+#[derive(Copy, Clone)]
+pub struct GenerationalGA {
+    pub tournament_size: usize,
+    pub elites: usize
+}
+
+impl Named for GenerationalGA {
+    fn name(&self) -> String {
+        String::from("Generational GA")
+    }
+}
+
+impl Parametrized for GenerationalGA {
+    fn parameters(&self) -> Value {
+        let mut config = Map::new();
+        config.insert("use spatial grid".to_string(), false.into());
+        config.insert("use spatial hyperparameters".to_string(), false.into());
+        config.insert("use features".to_string(), false.into());
+        config.insert("tournament size".to_string(), self.tournament_size.into());
+        config.insert("elites".to_string(), self.elites.into());
+
+        Value::Object(config)
+    }
+}
+
+impl<V: Clone + 'static,P: 'static,H: Hyperparameter + 'static> ReplacementSelection<V,P,H> for GenerationalGA {
+    fn run_parameters(&self, pop_size: usize, _problem: &P, _problem_config: &ProblemConfig<V,P,H>) -> Result<Value, String> {
+        if self.elites >= pop_size {
+            return Err(format!("{} elites leave no room for offspring in a population of {}", self.elites, pop_size));
+        }
+        if self.tournament_size == 0 {
+            return Err("the tournament size should be at least 1".to_string());
+        }
+        Ok(self.parameters())
+    }
+
+    fn initialize_solver(
+            &self, pop_size: usize,
+            problem: Rc<P>,
+            elitism: Rc<dyn Elitism>,
+            problem_config: Rc<ProblemConfig<V,P,H>>) -> Box<dyn UpdatableSolver<V>> {
+        let organisms = problem_config.random_organism_generator.generate_population(problem.as_ref(), pop_size);
+        Box::new(GenerationalGAExec {
+            algo_config: *self,
+            problem,
+            organisms,
+            problem_config,
+            elitism,
+            reports: vec![UpdateReport::default()],
+            iteration: 0
+        })
+    }
+}
+
+struct GenerationalGAExec<V,P,H> {
+    algo_config: GenerationalGA,
+    problem: Rc<P>,
+    organisms: Vec<Organism<V>>,
+    problem_config: Rc<ProblemConfig<V,P,H>>,
+    elitism: Rc<dyn Elitism>,
+    /// One report per offspring of the last generation
+    reports: Vec<UpdateReport>,
+    iteration: u64
+}
+
+impl<V: Clone,P,H: Hyperparameter> GenerationalGAExec<V,P,H> {
+    /// Index of the winner of a tournament, contenders challenge the current winner through the elitism
+    fn tournament(&self, scores: &[f64]) -> usize {
+        let mut rng = thread_rng();
+        let mut winner = rng.gen_range(0, scores.len());
+        for _i in 1..self.algo_config.tournament_size {
+            let contender = rng.gen_range(0, scores.len());
+            if self.elitism.choose(scores[contender], scores[winner]) {
+                winner = contender;
+            }
+        }
+        winner
+    }
+}
+
+impl<V: Clone,P,H: Hyperparameter> UpdatableSolver<V> for GenerationalGAExec<V,P,H> {
+    fn update(&mut self) -> Vec<Organism<V>> {
+        let scorer = self.problem_config.scorer.as_ref();
+        let problem = self.problem.as_ref();
+
+        let re_evaluation = &self.problem_config.re_evaluation;
+        let scores = Organism::incumbent_scores(self.organisms.iter_mut().collect(), scorer, problem, re_evaluation, self.iteration);
+
+        let mut ranking: Vec<usize> = (0..self.organisms.len()).collect();
+        ranking.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(std::cmp::Ordering::Equal));
+        let elites = self.algo_config.elites.min(self.organisms.len());
+
+        let mut parents = Vec::with_capacity(self.organisms.len() - elites);
+        let mut offspring = Vec::with_capacity(self.organisms.len() - elites);
+        let mut changes = Vec::with_capacity(self.organisms.len() - elites);
+        for _i in elites..self.organisms.len() {
+            let parent = self.tournament(&scores);
+            let mut child = self.organisms[parent].clone();
+            changes.push(child.mutate(self.problem_config.mutator.as_ref(), &self.problem_config.constant_hyperparameters));
+            child.record_birth(self.iteration, "mutation", || None);
+            parents.push(parent);
+            offspring.push(child);
+        }
+
+        let offspring_scores = Organism::offspring_scores(offspring.iter_mut().collect(), scorer, problem, re_evaluation, self.iteration);
+
+        // every offspring enters the next generation, it is compared to its parent in the reports
+        self.reports = offspring.iter().zip(parents).zip(changes).zip(offspring_scores)
+            .map(|(((child, parent), changed), score)| UpdateReport {
+                accepted: true,
+                mutation_changed: changed,
+                offspring_score: score,
+                competitor_score: Some(scores[parent]),
+                offspring_hyperparameters: child.get_hyperparameters().cloned(),
+                offspring_lineage: child.get_lineage().cloned()
+            })
+            .collect();
+
+        let mut next_generation: Vec<Organism<V>> = ranking[..elites].iter().map(|&i| self.organisms[i].clone()).collect();
+        next_generation.append(&mut offspring);
+        self.organisms = next_generation;
+
+        self.iteration += 1;
+
+        self.organisms.clone()
+    }
+
+    fn last_update(&self) -> &UpdateReport {
+        self.reports.last().unwrap()
+    }
+
+    fn last_updates(&self) -> &[UpdateReport] {
+        &self.reports
+    }
+
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>> {
+        self.organisms.iter_mut().collect()
+    }
+
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>> {
+        self.organisms.iter().map(ArchiveEntry::organism).collect()
+    }
+}
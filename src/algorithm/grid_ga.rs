@@ -1,25 +1,94 @@
-use crate::common::{Named, Parametrized};
+use crate::common::{Named, Parametrized, component_param};
 use crate::organism::grid::Grid;
 use std::rc::Rc;
 use crate::algorithm::config::ProblemConfig;
 use crate::algorithm::selection::Elitism;
 use serde_json::{Map, Value};
-use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver};
+use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver, UpdateReport, ArchiveEntry};
 use std::hash::Hash;
 use crate::organism::Organism;
 use crate::problems::Hyperparameter;
 use std::collections::HashMap;
 use rand::{thread_rng, Rng};
-use ndarray::{Array, ArrayView, ViewRepr, ArrayViewMut, IxDynImpl, Dim, ArrayD, ArrayViewD};
+use ndarray::{Array, ArrayViewMut, IxDynImpl, Dim, ArrayD, ArrayViewD, Dimension};
 use rand::seq::SliceRandom;
-use std::iter::Zip;
 use crate::features::FeatureMapper;
+use serde::Serialize;
 
 pub struct GeneralizedMAPElite<V,F,P> {
     pub feature_mapper: Option<Rc<dyn FeatureMapper<V,F,P>>>,
     pub use_hyperparameter_mapping: bool,
     pub number_of_spatial_dimensions: usize,
-    pub default_feature: F
+    pub default_feature: F,
+    /// Explicit size of each grid axis, derived from the population size when `None`
+    pub grid_shape: Option<Vec<usize>>
+}
+
+/// Shape of the spatial grid actually used for a run
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridLayout {
+    pub shape: Vec<usize>,
+    pub features_per_cell: usize,
+    /// Maximum number of organisms the grid can hold, the last cells hold fewer features when it is not
+    /// a multiple of `features_per_cell`
+    pub capacity: usize
+}
+
+impl GridLayout {
+    pub fn number_of_cells(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of features the cell at `index` can hold, cells being filled in row-major order
+    pub fn slots(&self, index: &[usize]) -> usize {
+        let position = index.iter().zip(self.shape.iter()).fold(0, |acc, (&i, &d)| acc * d + i);
+        self.capacity.saturating_sub(position * self.features_per_cell).min(self.features_per_cell)
+    }
+}
+
+impl Parametrized for GridLayout {
+    fn parameters(&self) -> Value {
+        let mut config = Map::new();
+        config.insert("grid shape".to_string(), self.shape.clone().into());
+        config.insert("number of cells".to_string(), self.number_of_cells().into());
+        config.insert("features per cell".to_string(), self.features_per_cell.into());
+        config.insert("grid capacity".to_string(), self.capacity().into());
+        Value::Object(config)
+    }
+}
+
+/// Splits `n` into `number_of_dims` factors whose product is exactly `n`,
+/// keeping the axes as close to each other in size as possible.
+pub fn balanced_shape(n: usize, number_of_dims: usize) -> Vec<usize> {
+    let mut shape = Vec::with_capacity(number_of_dims);
+    let mut remaining = n;
+
+    for d in (1..=number_of_dims).rev() {
+        if d == 1 {
+            shape.push(remaining);
+            break;
+        }
+
+        let ideal = (remaining as f64).powf(1.0/d as f64);
+        let best = (1..=remaining)
+            .filter(|&k| remaining.is_multiple_of(k))
+            .min_by(|&x, &y| {
+                let dx = (x as f64 - ideal).abs();
+                let dy = (y as f64 - ideal).abs();
+                dx.partial_cmp(&dy).unwrap()
+            })
+            .unwrap_or(1);
+
+        shape.push(best);
+        remaining /= best;
+    }
+
+    shape.sort_unstable_by(|a, b| b.cmp(a));
+    shape
 }
 
 impl<V,F: Clone,P> Clone for GeneralizedMAPElite<V,F,P> {
@@ -28,7 +97,8 @@ impl<V,F: Clone,P> Clone for GeneralizedMAPElite<V,F,P> {
             feature_mapper: self.feature_mapper.clone(),
             use_hyperparameter_mapping: self.use_hyperparameter_mapping,
             number_of_spatial_dimensions: self.number_of_spatial_dimensions,
-            default_feature: self.default_feature.clone()
+            default_feature: self.default_feature.clone(),
+            grid_shape: self.grid_shape.clone()
         }
     }
 }
@@ -40,6 +110,63 @@ impl<V,F: Clone + Eq + Hash,P> GeneralizedMAPElite<V,F,P> {
             None => self.default_feature.clone()
         }
     }
+
+    fn number_of_dimensions<H: Hyperparameter>(&self, problem_config: &ProblemConfig<V,P,H>) -> usize {
+        problem_config.hyperparameter_mapper.number_of_hyperparameters().max(self.number_of_spatial_dimensions)
+    }
+
+    /// Chooses the grid shape for `pop_size` organisms, see `grid_layout`
+    pub fn layout<H: Hyperparameter>(&self,
+                                     pop_size: usize,
+                                     problem: &P,
+                                     problem_config: &ProblemConfig<V,P,H>) -> Result<GridLayout, String> {
+        let possibles_features = match &self.feature_mapper {
+            Some(fm) => fm.number_of_possible_features(problem),
+            None => 1
+        };
+
+        grid_layout(pop_size, possibles_features, self.number_of_dimensions(problem_config), self.grid_shape.as_deref())
+    }
+}
+
+/// Grid of `number_of_dims` axes holding `pop_size` organisms, `features_per_cell` per cell. When `pop_size`
+/// is not a multiple of `features_per_cell`, the last cell holds fewer features so that the capacity is
+/// exactly `pop_size`. An explicit `grid_shape` must have that many cells, otherwise the shape is a
+/// balanced factorisation of the number of cells.
+pub fn grid_layout(pop_size: usize,
+                   features_per_cell: usize,
+                   number_of_dims: usize,
+                   grid_shape: Option<&[usize]>) -> Result<GridLayout, String> {
+    // required for fair comparison by maintaining same pop size between algos
+    if features_per_cell > pop_size {
+        return Err(format!("population size {} is smaller than the {} possible features, \
+                            not a single grid cell can be built", pop_size, features_per_cell));
+    }
+
+    let shape = match grid_shape {
+        Some(shape) => {
+            if shape.len() != number_of_dims {
+                return Err(format!("grid shape {:?} has {} axes but {} are required",
+                                   shape, shape.len(), number_of_dims));
+            }
+            if shape.contains(&0) {
+                return Err(format!("grid shape {:?} has an empty axis", shape));
+            }
+            let number_of_cells = shape.iter().product::<usize>();
+            if number_of_cells != pop_size.div_ceil(features_per_cell) {
+                return Err(format!("grid shape {:?} has {} cells but the population size {} with {} features per cell needs {}",
+                                   shape, number_of_cells, pop_size, features_per_cell, pop_size.div_ceil(features_per_cell)));
+            }
+            shape.to_vec()
+        },
+        None => balanced_shape(pop_size.div_ceil(features_per_cell), number_of_dims)
+    };
+
+    Ok(GridLayout {
+        shape,
+        features_per_cell,
+        capacity: pop_size
+    })
 }
 
 impl<V,F,P> Named for GeneralizedMAPElite<V,F,P> {
@@ -54,8 +181,9 @@ impl<V,F,P> Parametrized for GeneralizedMAPElite<V,F,P> {
         config.insert("use spatial grid".to_string(), self.number_of_spatial_dimensions.into());
         config.insert("use spatial hyperparameters".to_string(), self.use_hyperparameter_mapping.into());
         config.insert("use features".to_string(), self.feature_mapper.is_some().into());
+        config.insert("feature mapper".to_string(), self.feature_mapper.as_ref().map_or(Value::Null, |m| component_param(m.as_ref())));
 
-        return Value::Object(config);
+        Value::Object(config)
     }
 }
 
@@ -64,56 +192,65 @@ pub struct GeneralizedMAPEliteExec<V,P,F,H> {
     problem: Rc<P>,
     organisms: Grid<V,F>,
     problem_config: Rc<ProblemConfig<V,P,H>>,
-    elitism: Rc<dyn Elitism>
+    elitism: Rc<dyn Elitism>,
+    layout: GridLayout,
+    iteration: u64,
+    report: UpdateReport
 }
 
 impl<V: Clone + 'static + PartialEq,
     P: 'static,
-    F: Hash + Clone + Eq + 'static,
+    F: Hash + Clone + Eq + Serialize + 'static,
     H: Hyperparameter + 'static + Clone> ReplacementSelection<V,P,H> for GeneralizedMAPElite<V,F,P> {
-    fn initialize_solver(&self, pop_size: usize, problem: Rc<P>, elitism: Rc<dyn Elitism>, problem_config: Rc<ProblemConfig<V, P, H>>) -> Box<dyn UpdatableSolver<V>> {
+    fn run_parameters(&self, pop_size: usize, problem: &P, problem_config: &ProblemConfig<V, P, H>) -> Result<Value, String> {
+        let layout = self.layout(pop_size, problem, problem_config)?;
 
-        let possibles_features = match &self.feature_mapper {
-            Some(fm) => fm.number_of_possible_features(problem.as_ref()),
-            None => 1
+        let mut config = match self.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
         };
+        if let Value::Object(layout_params) = layout.parameters() {
+            config.extend(layout_params);
+        }
+        config.insert("population size".to_string(), layout.capacity().into());
 
-        //println!("number of possibles features: {}", possibles_features);
-
-        assert!(pop_size > possibles_features); // required for fair comparison by maintaining same pop size between algos
-
-        let pop_per_cell = pop_size/possibles_features;
-
-
-        let num_dims = problem_config.hyperparameter_mapper.number_of_hyperparameters().max(self.number_of_spatial_dimensions);
+        Ok(Value::Object(config))
+    }
 
+    fn initialize_solver(&self, pop_size: usize, problem: Rc<P>, elitism: Rc<dyn Elitism>, problem_config: Rc<ProblemConfig<V, P, H>>) -> Box<dyn UpdatableSolver<V>> {
 
-        let dim_size = (pop_per_cell as f64).powf(1.0/num_dims as f64) as usize;
-        //let dim_size = (pop_size as f64).powf(1.0/num_dims as f64) as usize;
+        let layout = self.layout(pop_size, problem.as_ref(), problem_config.as_ref())
+            .unwrap_or_else(|e| panic!("infeasible grid configuration: {}", e));
 
+        let mut population = problem_config.random_organism_generator
+            .generate_population(problem.as_ref(), layout.number_of_cells())
+            .into_iter();
 
-        let organisms: ArrayD<HashMap<F,Organism<V>>> = Array::from_shape_fn(vec![dim_size; num_dims], |_|{
-            let org = problem_config.random_organism_generator.generate_organism(problem.as_ref());
+        let organisms: ArrayD<HashMap<F,Organism<V>>> = Array::from_shape_fn(layout.shape.clone(), |_|{
+            let org = population.next().unwrap();
             let mut hm = HashMap::new();
 
             let features = self.project(&org.genotype);
 
             hm.insert(features, org);
-            return hm;
+            hm
         });
 
-        return Box::new(GeneralizedMAPEliteExec {
+        Box::new(GeneralizedMAPEliteExec {
             algo_config: self.clone(),
             problem: problem.clone(),
             organisms: Grid {cells : organisms},
             problem_config: problem_config.clone(),
-            elitism
-        });
+            elitism,
+            layout,
+            iteration: 0,
+            report: UpdateReport::default()
+        })
     }
 }
 
 
-impl<V: Clone + PartialEq,P,F: Clone + Hash + Eq,H: Hyperparameter + Clone> UpdatableSolver<V> for GeneralizedMAPEliteExec<V,P,F,H> {
+impl<V: Clone + PartialEq,P,F: Clone + Hash + Eq + Serialize,H: Hyperparameter + Clone> UpdatableSolver<V> for GeneralizedMAPEliteExec<V,P,F,H> {
     fn update(&mut self) -> Vec<Organism<V>> {
         let mut rng = thread_rng();
 
@@ -155,52 +292,136 @@ impl<V: Clone + PartialEq,P,F: Clone + Hash + Eq,H: Hyperparameter + Clone> Upda
             org.clone()
         };
 
-        let old = org_a.clone();
-
         let old_feature = self.algo_config.project(&org_a.genotype); // project if using mapper else default
 
         let hyper = if self.algo_config.use_hyperparameter_mapping {
             let coord: Vec<(usize,usize)> = id_a.iter().zip(shp.iter()).map(|(&x,&y)| (x,y)).collect();
-            self.problem_config.hyperparameter_mapper.map_hyperparameters(&coord)
+            self.problem_config.hyperparameter_mapper.map_hyperparameters_at(&coord, self.iteration)
         }
         else {
             self.problem_config.constant_hyperparameters.clone()
         };
 
-        org_a.mutate(self.problem_config.mutator.as_ref(), &hyper);
+        let changed = org_a.mutate(self.problem_config.mutator.as_ref(), &hyper);
+        org_a.record_birth(self.iteration, "mutation", || Some(id_b.clone()));
 
         let feature_a = self.algo_config.project(&org_a.genotype);
 
-        let score_a = org_a.score_with_cache(self.problem_config.scorer.as_ref(), self.problem.as_ref());
+        let score_a = org_a.offspring_score(self.problem_config.scorer.as_ref(), self.problem.as_ref(), &self.problem_config.re_evaluation, self.iteration);
 
-        let mut score_b = score_a;
+        let mut competitor_score = None;
         let mut replace = {
             // ArrayViewMut<ViewRepr<&mut ?>, IxDyn<IxDynImpl>>
             let mut v: ArrayViewMut<HashMap<F, Organism<V>>, Dim<IxDynImpl>> = self.organisms.cells.view_mut();
             let hm_b: &mut HashMap<F,Organism<V>> = v.get_mut(id_b.as_slice()).unwrap();
+            let cell_full = hm_b.len() >= self.layout.slots(&id_b);
             let op_org_b = hm_b.get_mut(&feature_a);
 
             match op_org_b {
                 Some(org_b) => {
-                    score_b = org_b.score_with_cache(self.problem_config.scorer.as_ref(), self.problem.as_ref());
+                    let score_b = org_b.incumbent_score(self.problem_config.scorer.as_ref(), self.problem.as_ref(), &self.problem_config.re_evaluation, self.iteration);
                     assert!(score_b.is_finite());
+                    competitor_score = Some(score_b);
                     self.elitism.choose(score_a, score_b)
                 },
-                None => true
+                // a new feature needs a free slot in the cell
+                None => !cell_full
             }
         };
 
         replace = replace && (old_feature != feature_a || id_a != id_b );
 
+        self.report = UpdateReport {
+            accepted: replace,
+            mutation_changed: changed,
+            offspring_score: score_a,
+            competitor_score,
+            offspring_hyperparameters: org_a.get_hyperparameters().cloned(),
+            offspring_lineage: org_a.get_lineage().cloned()
+        };
+
         if replace {
             let mut v: ArrayViewMut<HashMap<F,Organism<V>>, Dim<IxDynImpl>> = self.organisms.cells.view_mut();
             let feat_map: &mut HashMap<F, Organism<V>> = v.get_mut(id_b.as_slice()).unwrap();
             feat_map.insert(feature_a, org_a);
         }
 
+        self.iteration += 1;
 
         self.organisms.cells.view().iter().flat_map(|hm: &HashMap<F, Organism<V>>| {
             hm.values().cloned().collect::<Vec<Organism<V>>>()
         }).collect()
     }
+
+    fn last_update(&self) -> &UpdateReport {
+        &self.report
+    }
+
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>> {
+        self.organisms.cells.iter_mut().flat_map(|hm| hm.values_mut()).collect()
+    }
+
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>> {
+        let use_features = self.algo_config.feature_mapper.is_some();
+        self.organisms.cells.indexed_iter().flat_map(|(index, hm)| {
+            let cell = index.slice().to_vec();
+            hm.iter().map(move |(features, organism)| ArchiveEntry {
+                organism,
+                features: if use_features { Some(serde_json::to_value(features).map_err(|e| e.to_string())) } else { None },
+                cell: Some(cell.clone())
+            })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balanced_shape_is_an_exact_factorisation() {
+        assert_eq!(balanced_shape(2500, 2), vec![50, 50]);
+        assert_eq!(balanced_shape(2500, 1), vec![2500]);
+        assert_eq!(balanced_shape(51, 2), vec![17, 3]);
+        assert_eq!(balanced_shape(7, 2), vec![7, 1]);
+        for n in 1..200 {
+            for dims in 1..4 {
+                let shape = balanced_shape(n, dims);
+                assert_eq!(shape.len(), dims);
+                assert_eq!(shape.iter().product::<usize>(), n);
+            }
+        }
+    }
+
+    #[test]
+    fn derived_layout_holds_the_population() {
+        let layout = grid_layout(2500, 50, 2, None).unwrap();
+        assert_eq!(layout.shape, vec![10, 5]);
+        assert_eq!(layout.capacity(), 2500);
+
+        // 2500 organisms with 49 features per cell need 52 cells, the last one holding a single feature
+        let layout = grid_layout(2500, 49, 1, None).unwrap();
+        assert_eq!(layout.shape, vec![52]);
+        assert_eq!(layout.capacity(), 2500);
+        assert_eq!((0..52).map(|c| layout.slots(&[c])).sum::<usize>(), 2500);
+        assert_eq!(layout.slots(&[50]), 49);
+        assert_eq!(layout.slots(&[51]), 1);
+
+        assert!(grid_layout(100, 256, 1, None).is_err());
+    }
+
+    #[test]
+    fn explicit_layout_must_match_the_population() {
+        assert_eq!(grid_layout(2500, 1, 2, Some(&[25, 100])).unwrap().shape, vec![25, 100]);
+        assert_eq!(grid_layout(2500, 10, 2, Some(&[25, 10])).unwrap().capacity(), 2500);
+        assert!(grid_layout(2500, 1, 2, Some(&[50, 49])).is_err());
+        assert!(grid_layout(2500, 1, 2, Some(&[2500])).is_err());
+        assert!(grid_layout(2500, 1, 2, Some(&[2500, 0])).is_err());
+
+        let layout = grid_layout(2500, 49, 2, Some(&[13, 4])).unwrap();
+        assert_eq!(layout.capacity(), 2500);
+        assert_eq!(layout.slots(&[12, 2]), 49);
+        assert_eq!(layout.slots(&[12, 3]), 1);
+        assert!(grid_layout(2500, 49, 2, Some(&[13, 5])).is_err());
+    }
 }
@@ -0,0 +1,230 @@
+use std::rc::Rc;
+use rand::{thread_rng, Rng};
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver, UpdateReport, ArchiveEntry};
+use crate::algorithm::config::ProblemConfig;
+use crate::algorithm::selection::Elitism;
+use crate::organism::Organism;
+use crate::problems::Hyperparameter;
+
+/// Splits the population in islands evolving side by side. At every update each island produces
+/// one offspring replacing one of its organisms as in the simple replacement, the offspring of all
+/// islands are scored together as one batch. Every `migration_interval` updates the `migrants` best
+/// organisms of every island replace the worst ones of the next island, the islands forming a ring.
+#[derive(Copy, Clone)]
+pub struct IslandModel {
+    pub number_of_islands: usize,
+    pub migration_interval: u64,
+    pub migrants: usize
+}
+
+impl IslandModel {
+    /// Sizes of the islands holding `pop_size` organisms, the first islands hold one more organism
+    /// when the population does not divide evenly
+    fn island_sizes(&self, pop_size: usize) -> Vec<usize> {
+        let n = self.number_of_islands.max(1);
+        (0..n).map(|k| pop_size / n + usize::from(k < pop_size % n)).collect()
+    }
+}
+
+impl Named for IslandModel {
+    fn name(&self) -> String {
+        String::from("Island model")
+    }
+}
+
+impl Parametrized for IslandModel {
+    fn parameters(&self) -> Value {
+        let mut config = Map::new();
+        config.insert("use spatial grid".to_string(), false.into());
+        config.insert("use spatial hyperparameters".to_string(), false.into());
+        config.insert("use features".to_string(), false.into());
+        config.insert("number of islands".to_string(), self.number_of_islands.into());
+        config.insert("migration interval".to_string(), self.migration_interval.into());
+        config.insert("migrants".to_string(), self.migrants.into());
+
+        Value::Object(config)
+    }
+}
+
+impl<V: Clone + 'static,P: 'static,H: Hyperparameter + 'static> ReplacementSelection<V,P,H> for IslandModel {
+    fn run_parameters(&self, pop_size: usize, _problem: &P, _problem_config: &ProblemConfig<V,P,H>) -> Result<Value, String> {
+        if self.number_of_islands == 0 {
+            return Err("there should be at least one island".to_string());
+        }
+        let island_sizes = self.island_sizes(pop_size);
+        let island_size = island_sizes.iter().copied().min().unwrap_or(0);
+        if island_size < 2 {
+            return Err(format!("population size {} is too small for {} islands of at least 2 organisms",
+                               pop_size, self.number_of_islands));
+        }
+        if self.migrants >= island_size {
+            return Err(format!("{} migrants would replace whole islands of {} organisms", self.migrants, island_size));
+        }
+        if self.migration_interval == 0 {
+            return Err("the migration interval should be at least 1".to_string());
+        }
+
+        let mut config = match self.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
+        };
+        config.insert("island sizes".to_string(), island_sizes.into());
+        config.insert("population size".to_string(), pop_size.into());
+        Ok(Value::Object(config))
+    }
+
+    fn initialize_solver(
+            &self, pop_size: usize,
+            problem: Rc<P>,
+            elitism: Rc<dyn Elitism>,
+            problem_config: Rc<ProblemConfig<V,P,H>>) -> Box<dyn UpdatableSolver<V>> {
+        let mut population = problem_config.random_organism_generator
+            .generate_population(problem.as_ref(), pop_size)
+            .into_iter();
+        let islands = self.island_sizes(pop_size).into_iter()
+            .map(|size| population.by_ref().take(size).collect())
+            .collect();
+
+        Box::new(IslandModelExec {
+            algo_config: *self,
+            problem,
+            islands,
+            problem_config,
+            elitism,
+            reports: vec![UpdateReport::default()],
+            iteration: 0
+        })
+    }
+}
+
+struct IslandModelExec<V,P,H> {
+    algo_config: IslandModel,
+    problem: Rc<P>,
+    islands: Vec<Vec<Organism<V>>>,
+    problem_config: Rc<ProblemConfig<V,P,H>>,
+    elitism: Rc<dyn Elitism>,
+    /// One report per island for the last update
+    reports: Vec<UpdateReport>,
+    iteration: u64
+}
+
+impl<V: Clone,P,H: Hyperparameter> IslandModelExec<V,P,H> {
+    /// Copies the best organisms of every island over the worst ones of the next island
+    fn migrate(&mut self) {
+        let scorer = self.problem_config.scorer.as_ref();
+        let problem = self.problem.as_ref();
+        Organism::offspring_scores(self.islands.iter_mut().flatten().collect(), scorer, problem, &self.problem_config.re_evaluation, self.iteration);
+
+        let by_score = |island: &Vec<Organism<V>>| {
+            let mut ranking: Vec<usize> = (0..island.len()).collect();
+            ranking.sort_by(|&a, &b| island[b].get_score().partial_cmp(&island[a].get_score()).unwrap_or(std::cmp::Ordering::Equal));
+            ranking
+        };
+
+        let emigrants: Vec<Vec<Organism<V>>> = self.islands.iter()
+            .map(|island| by_score(island)[..self.algo_config.migrants].iter().map(|&i| island[i].clone()).collect())
+            .collect();
+
+        let n = self.islands.len();
+        for (k, migrants) in emigrants.into_iter().enumerate() {
+            let destination = &mut self.islands[(k + 1) % n];
+            let worst: Vec<usize> = by_score(destination).into_iter().rev().take(migrants.len()).collect();
+            for (i, migrant) in worst.into_iter().zip(migrants) {
+                destination[i] = migrant;
+            }
+        }
+    }
+}
+
+impl<V: Clone,P,H: Hyperparameter> UpdatableSolver<V> for IslandModelExec<V,P,H> {
+    fn update(&mut self) -> Vec<Organism<V>> {
+        let scorer = self.problem_config.scorer.as_ref();
+        let problem = self.problem.as_ref();
+        let mut rng = thread_rng();
+
+        let mut offspring = Vec::with_capacity(self.islands.len());
+        let mut changes = Vec::with_capacity(self.islands.len());
+        let mut replaced = Vec::with_capacity(self.islands.len());
+        for island in &self.islands {
+            let size = island.len();
+            let index_a = rng.gen_range(0, size);
+            let mut index_replace = rng.gen_range(0, size);
+            while index_replace == index_a {
+                index_replace = rng.gen_range(0, size);
+            }
+
+            let mut child = island[index_a].clone();
+            changes.push(child.mutate(self.problem_config.mutator.as_ref(), &self.problem_config.constant_hyperparameters));
+            child.record_birth(self.iteration, "mutation", || None);
+            offspring.push(child);
+            replaced.push(index_replace);
+        }
+
+        let re_evaluation = &self.problem_config.re_evaluation;
+        let scores = Organism::offspring_scores(offspring.iter_mut().collect(), scorer, problem, re_evaluation, self.iteration);
+        let competitor_scores = Organism::incumbent_scores(
+            self.islands.iter_mut().zip(&replaced).map(|(island, &i)| &mut island[i]).collect(),
+            scorer, problem, re_evaluation, self.iteration);
+
+        self.reports.clear();
+        for (k, child) in offspring.into_iter().enumerate() {
+            let keep_child = self.elitism.choose(scores[k], competitor_scores[k]);
+
+            self.reports.push(UpdateReport {
+                accepted: keep_child,
+                mutation_changed: changes[k],
+                offspring_score: scores[k],
+                competitor_score: Some(competitor_scores[k]),
+                offspring_hyperparameters: child.get_hyperparameters().cloned(),
+                offspring_lineage: child.get_lineage().cloned()
+            });
+
+            if keep_child {
+                self.islands[k][replaced[k]] = child;
+            }
+        }
+
+        self.iteration += 1;
+        if self.iteration.is_multiple_of(self.algo_config.migration_interval) && self.islands.len() > 1 {
+            self.migrate();
+        }
+
+        self.islands.iter().flatten().cloned().collect()
+    }
+
+    fn last_update(&self) -> &UpdateReport {
+        self.reports.last().unwrap()
+    }
+
+    fn last_updates(&self) -> &[UpdateReport] {
+        &self.reports
+    }
+
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>> {
+        self.islands.iter_mut().flatten().collect()
+    }
+
+    /// The cell of an organism is the index of its island
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>> {
+        self.islands.iter().enumerate().flat_map(|(k, island)| island.iter().map(move |organism| ArchiveEntry {
+            organism,
+            features: None,
+            cell: Some(vec![k])
+        })).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn islands_hold_the_whole_population() {
+        let model = IslandModel { number_of_islands: 3, migration_interval: 10, migrants: 1 };
+        assert_eq!(model.island_sizes(2500), vec![834, 833, 833]);
+        assert_eq!(model.island_sizes(9), vec![3, 3, 3]);
+        assert_eq!(model.island_sizes(2500).iter().sum::<usize>(), 2500);
+    }
+}
@@ -3,24 +3,28 @@ use crate::organism::Organism;
 use std::rc::Rc;
 use crate::algorithm::config::ProblemConfig;
 use crate::algorithm::selection::Elitism;
-use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver};
-use crate::common::{Named, Parametrized};
+use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver, UpdateReport, ArchiveEntry};
+use crate::problems::Hyperparameter;
+use crate::common::{Named, Parametrized, component_param};
 use rand::thread_rng;
-use rand::seq::IteratorRandom;
 use std::hash::Hash;
 use std::collections::hash_map::Entry;
 use rand::seq::SliceRandom;
 use serde_json::{Value, Map};
 use crate::features::FeatureMapper;
+use serde::Serialize;
 
 pub struct MAPElite<V,F,P> {
-    pub feature_mapper: Rc<dyn FeatureMapper<V,F,P>>
+    pub feature_mapper: Rc<dyn FeatureMapper<V,F,P>>,
+    /// Offspring produced and scored together at every update
+    pub batch_size: usize
 }
 
 impl<V,F,P> Clone for MAPElite<V,F,P> {
     fn clone(&self) -> Self {
         MAPElite {
-            feature_mapper: self.feature_mapper.clone()
+            feature_mapper: self.feature_mapper.clone(),
+            batch_size: self.batch_size
         }
     }
 }
@@ -37,32 +41,55 @@ impl<V,F,P> Parametrized for MAPElite<V,F,P> {
         config.insert("use spatial grid".to_string(), false.into());
         config.insert("use spatial hyperparameters".to_string(), false.into());
         config.insert("use features".to_string(), true.into());
+        config.insert("feature mapper".to_string(), component_param(self.feature_mapper.as_ref()));
+        config.insert("batch size".to_string(), self.batch_size.into());
 
-        return Value::Object(config);
+        Value::Object(config)
     }
 }
 
-impl<V: 'static + Clone ,P: 'static,F: 'static + Clone + Eq + Hash,H: 'static> ReplacementSelection<V,P,H> for MAPElite<V,F,P> {
+impl<V: 'static + Clone ,P: 'static,F: 'static + Clone + Eq + Hash + Serialize,H: 'static + Hyperparameter> ReplacementSelection<V,P,H> for MAPElite<V,F,P> {
+    fn run_parameters(&self, pop_size: usize, _problem: &P, _problem_config: &ProblemConfig<V,P,H>) -> Result<Value, String> {
+        let mut config = match self.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
+        };
+        // the archive used to start from a single organism
+        config.insert("initial population".to_string(), pop_size.max(1).into());
+        config.insert("initial elites".to_string(), "best scored organism of the initial population in every niche".into());
+        Ok(Value::Object(config))
+    }
+
     fn initialize_solver(&self,
                          pop_size: usize,
                          problem: Rc<P>,
                          elitism: Rc<dyn Elitism>,
                          problem_config: Rc<ProblemConfig<V, P, H>>) -> Box<dyn UpdatableSolver<V>> {
-        let mut hm = HashMap::new();
-
-        let org = problem_config.random_organism_generator.generate_organism(
-            problem.as_ref());
-
-        let feat = self.feature_mapper.project(&org.genotype);
-
-        hm.insert(feat, org);
+        let mut population = problem_config.random_organism_generator.generate_population(problem.as_ref(), pop_size.max(1));
+        let scores = Organism::offspring_scores(population.iter_mut().collect(), problem_config.scorer.as_ref(),
+                                                problem.as_ref(), &problem_config.re_evaluation, 0);
+
+        // every niche starts with the best organism of the initial population it holds, the first one on ties
+        let mut best: HashMap<F, (f64, Organism<V>)> = HashMap::new();
+        for (org, score) in population.into_iter().zip(scores) {
+            match best.entry(self.feature_mapper.project(&org.genotype)) {
+                Entry::Occupied(mut entry) => if score > entry.get().0 {
+                    entry.insert((score, org));
+                },
+                Entry::Vacant(entry) => {
+                    entry.insert((score, org));
+                }
+            }
+        }
 
         Box::new(MAPEliteExec {
-            niches: hm,
+            niches: best.into_iter().map(|(feat, (_, org))| (feat, org)).collect(),
             problem: problem.clone(),
             problem_config: problem_config.clone(),
             elitism,
-            algo_config: self.clone()
+            algo_config: self.clone(),
+            reports: vec![UpdateReport::default()],
+            iteration: 0
         })
     }
 }
@@ -73,11 +100,14 @@ pub struct MAPEliteExec<V,P,F,H> {
     problem: Rc<P>,
     problem_config: Rc<ProblemConfig<V,P,H>>,
     elitism: Rc<dyn Elitism>,
-    algo_config: MAPElite<V,F,P>
+    algo_config: MAPElite<V,F,P>,
+    /// One report per offspring of the last update
+    reports: Vec<UpdateReport>,
+    iteration: u64
 }
 
 
-impl<V: Clone,P,F: Clone + Eq + Hash,H> UpdatableSolver<V> for MAPEliteExec<V,P,F,H> {
+impl<V: Clone,P,F: Clone + Eq + Hash + Serialize,H: Hyperparameter> UpdatableSolver<V> for MAPEliteExec<V,P,F,H> {
     fn update(&mut self) -> Vec<Organism<V>> {
 
         let scorer = &self.problem_config.scorer;
@@ -86,30 +116,78 @@ impl<V: Clone,P,F: Clone + Eq + Hash,H> UpdatableSolver<V> for MAPEliteExec<V,P,
 
         let mut rng = thread_rng();
 
-        let v: Vec<(&F,&Organism<V>)> = self.niches.iter().collect();
-
-        let &(_,x) = v.choose(&mut rng).unwrap();
-
-        let mut new_org: Organism<V> = x.clone();
+        let mut offspring: Vec<Organism<V>> = {
+            let v: Vec<&Organism<V>> = self.niches.values().collect();
+            (0..self.algo_config.batch_size.max(1)).map(|_| (*v.choose(&mut rng).unwrap()).clone()).collect()
+        };
+
+        let changes: Vec<bool> = offspring.iter_mut().map(|new_org| {
+            let changed = new_org.mutate(self.problem_config.mutator.as_ref(), &self.problem_config.constant_hyperparameters);
+            new_org.record_birth(self.iteration, "mutation", || None);
+            changed
+        }).collect();
+
+        let scores = Organism::offspring_scores(offspring.iter_mut().collect(), scorer.as_ref(), problem, &self.problem_config.re_evaluation, self.iteration);
+
+        // offspring compete for their niche in turn, later offspring can replace earlier ones
+        self.reports.clear();
+        for ((new_org, changed), offspring_score) in offspring.into_iter().zip(changes).zip(scores) {
+            let new_feat = self.algo_config.feature_mapper.project(&new_org.genotype);
+
+            let mut accepted = true;
+            let mut competitor_score = None;
+            let offspring_hyperparameters = new_org.get_hyperparameters().cloned();
+            let offspring_lineage = new_org.get_lineage().cloned();
+
+            match self.niches.entry(new_feat) {
+                Entry::Occupied(mut ent) => {
+                    let retrieved = ent.get_mut();
+                    let score_retrieved = retrieved.incumbent_score(scorer.as_ref(), problem, &self.problem_config.re_evaluation, self.iteration);
+                    competitor_score = Some(score_retrieved);
+
+                    accepted = elitism.choose(offspring_score, score_retrieved);
+                    if accepted {
+                        *retrieved = new_org;
+                    }
+                },
+                Entry::Vacant(ent) => {
+                    ent.insert(new_org);
+                }
+            }
 
-        new_org.mutate(self.problem_config.mutator.as_ref(), &self.problem_config.constant_hyperparameters);
+            self.reports.push(UpdateReport {
+                accepted,
+                mutation_changed: changed,
+                offspring_score,
+                competitor_score,
+                offspring_hyperparameters,
+                offspring_lineage
+            });
+        }
 
-        let new_feat = self.algo_config.feature_mapper.project(&new_org.genotype);
+        self.iteration += 1;
 
-        let copied = new_org.clone();
+        self.niches.values().cloned().collect()
+    }
 
-        let ent = self.niches.entry(new_feat);
+    fn last_update(&self) -> &UpdateReport {
+        self.reports.last().unwrap()
+    }
 
-        ent.and_modify(|retrieved| {
-            let score_new = new_org.score_with_cache(scorer.as_ref(), problem);
-            let score_retrieved = retrieved.score_with_cache(scorer.as_ref(), problem);
+    fn last_updates(&self) -> &[UpdateReport] {
+        &self.reports
+    }
 
-            if elitism.choose(score_new, score_retrieved) {
-                *retrieved = new_org;
-            }
-        }).or_insert(copied);
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>> {
+        self.niches.values_mut().collect()
+    }
 
-        return self.niches.values().cloned().collect();
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>> {
+        self.niches.iter().map(|(features, organism)| ArchiveEntry {
+            organism,
+            features: Some(serde_json::to_value(features).map_err(|e| e.to_string())),
+            cell: None
+        }).collect()
     }
 }
 
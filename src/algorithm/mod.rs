@@ -1,9 +1,13 @@
+#[allow(clippy::module_inception)]
 pub mod algorithm;
 pub mod selection;
 pub mod simple;
 pub mod grid_ga;
 pub mod map_elite;
 pub mod simple_adaptive;
+pub mod generational;
+pub mod evaluation;
+pub mod island;
 pub mod mutation;
 pub mod config;
 pub mod util;
\ No newline at end of file
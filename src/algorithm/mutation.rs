@@ -1,7 +1,6 @@
+use crate::common::{Named, Parametrized};
 
-
-
-pub trait Mutator<V,H> {
+pub trait Mutator<V,H>: Named + Parametrized {
     fn mutate(&self, genome: &mut V, hyperparameters: &H) -> bool;
 }
 
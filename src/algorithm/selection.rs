@@ -1,11 +1,6 @@
 use self::super::super::common::Named;
-use super::super::organism::organism::{Organism};
-use super::super::organism::grid::Grid;
 
 use rand::{thread_rng, Rng};
-use crate::organism::{OrganismGenerator};
-use crate::features::FeatureMapper;
-use std::collections::HashMap;
 
 
 
@@ -26,7 +21,7 @@ impl Elitism for MetropolisHastings {
     fn choose(&self, score_a: f64, score_b: f64) -> bool {
         let mut trng = thread_rng();
         assert!(score_a >= 0.0 && score_b >= 0.0);
-        return trng.gen::<f64>() < score_a/score_b;
+        trng.gen::<f64>() < score_a/score_b
     }
 }
 
@@ -41,6 +36,6 @@ impl Named for GreedySelection {
 
 impl Elitism for GreedySelection {
     fn choose(&self, score_a: f64, score_b: f64) -> bool {
-        return score_a >= score_b;
+        score_a >= score_b
     }
 }
\ No newline at end of file
@@ -1,14 +1,10 @@
-use std::collections::HashMap;
-use crate::common::{Named, Parametrized, str_param};
-use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver};
-use crate::organism::{OrganismGenerator, Organism};
-use crate::features::FeatureMapper;
-use crate::organism::grid::Grid;
+use crate::common::{Named, Parametrized};
+use crate::algorithm::algorithm::{ReplacementSelection, UpdatableSolver, UpdateReport, ArchiveEntry};
+use crate::organism::Organism;
 use rand::{thread_rng, Rng};
 use std::rc::Rc;
 use crate::algorithm::selection::Elitism;
-use crate::problems::Environment;
-use crate::scoring::Scorer;
+use crate::problems::Hyperparameter;
 use crate::algorithm::config::ProblemConfig;
 use serde_json::{Map, Value};
 
@@ -19,7 +15,9 @@ struct SimpleReplacementExec<V,P,H> {
     problem: Rc<P>,
     organisms: Vec<Organism<V>>,
     problem_config: Rc<ProblemConfig<V,P,H>>,
-    elitism: Rc<dyn Elitism>
+    elitism: Rc<dyn Elitism>,
+    report: UpdateReport,
+    iteration: u64
 }
 
 impl Named for SimpleReplacement {
@@ -35,11 +33,11 @@ impl Parametrized for SimpleReplacement {
         config.insert("use spatial hyperparameters".to_string(), false.into());
         config.insert("use features".to_string(), false.into());
 
-        return Value::Object(config);
+        Value::Object(config)
     }
 }
 
-impl<V: Clone + 'static,P: 'static,H: Clone + 'static> ReplacementSelection<V,P,H> for SimpleReplacement {
+impl<V: Clone + 'static,P: 'static,H: Hyperparameter + Clone + 'static> ReplacementSelection<V,P,H> for SimpleReplacement {
     fn initialize_solver(
             &self, pop_size: usize,
             problem: Rc<P>,
@@ -47,20 +45,19 @@ impl<V: Clone + 'static,P: 'static,H: Clone + 'static> ReplacementSelection<V,P,
             problem_config: Rc<ProblemConfig<V,P,H>>) -> Box<dyn UpdatableSolver<V>> {
         let generator = &problem_config.random_organism_generator;
 
-        let mut gr = Vec::with_capacity(pop_size);
-        for _i in 0..pop_size {
-            gr.push(generator.generate_organism(problem.as_ref()));
-        }
-        return Box::new(SimpleReplacementExec {
+        let gr = generator.generate_population(problem.as_ref(), pop_size);
+        Box::new(SimpleReplacementExec {
             problem: problem.clone(),
             organisms: gr,
             problem_config: problem_config.clone(),
-            elitism
-        });
+            elitism,
+            report: UpdateReport::default(),
+            iteration: 0
+        })
     }
 }
 
-impl<V: Clone,P,H> UpdatableSolver<V> for SimpleReplacementExec<V,P,H> {
+impl<V: Clone,P,H: Hyperparameter> UpdatableSolver<V> for SimpleReplacementExec<V,P,H> {
     fn update(&mut self) -> Vec<Organism<V>> {
         let scorer = &self.problem_config.scorer;
 
@@ -75,29 +72,54 @@ impl<V: Clone,P,H> UpdatableSolver<V> for SimpleReplacementExec<V,P,H> {
 
         let score;
         let score_replace;
+        let changed;
 
         let org = {
             let org_a= self.organisms.get_mut(index_a).unwrap();
 
             let mut org_b = org_a.clone();
 
-            org_b.mutate(self.problem_config.mutator.as_ref(), &self.problem_config.constant_hyperparameters);
+            changed = org_b.mutate(self.problem_config.mutator.as_ref(), &self.problem_config.constant_hyperparameters);
+            org_b.record_birth(self.iteration, "mutation", || None);
 
-            score = org_b.score_with_cache(scorer.as_ref(), self.problem.as_ref());
+            score = org_b.offspring_score(scorer.as_ref(), self.problem.as_ref(), &self.problem_config.re_evaluation, self.iteration);
             org_b
         };
 
         {
             let org_c = self.organisms.get_mut(index_replace).unwrap();
-            score_replace = org_c.score_with_cache(scorer.as_ref(), self.problem.as_ref());
+            score_replace = org_c.incumbent_score(scorer.as_ref(), self.problem.as_ref(), &self.problem_config.re_evaluation, self.iteration);
         }
 
         let keep_first = self.elitism.choose(score, score_replace);
 
+        self.report = UpdateReport {
+            accepted: keep_first,
+            mutation_changed: changed,
+            offspring_score: score,
+            competitor_score: Some(score_replace),
+            offspring_hyperparameters: org.get_hyperparameters().cloned(),
+            offspring_lineage: org.get_lineage().cloned()
+        };
+
         if keep_first {
             self.organisms[index_replace] = org;
         }
 
-        return self.organisms.clone();
+        self.iteration += 1;
+
+        self.organisms.clone()
+    }
+
+    fn last_update(&self) -> &UpdateReport {
+        &self.report
+    }
+
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>> {
+        self.organisms.iter_mut().collect()
+    }
+
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>> {
+        self.organisms.iter().map(ArchiveEntry::organism).collect()
     }
 }
@@ -2,19 +2,28 @@ use std::rc::Rc;
 use crate::organism::Organism;
 use crate::algorithm::config::ProblemConfig;
 use crate::algorithm::selection::Elitism;
-use crate::problems::DiscreteHyperparameters;
+use crate::problems::{SelfAdaptive, AdaptationSettings};
 use crate::algorithm::mutation::Mutator;
 use rand::{thread_rng, Rng};
-use crate::scoring::Scorer;
 use crate::common::{Named, Parametrized};
 use serde_json::{Value, Map};
-use crate::{MyConfigIt};
-use crate::algorithm::algorithm::{UpdatableSolver, ReplacementSelection};
+use crate::algorithm::algorithm::{UpdatableSolver, ReplacementSelection, UpdateReport, ArchiveEntry};
 
 #[derive(Copy, Clone)]
 pub struct SimpleAdaptive {
     pub prior_a: i64,
-    pub prior_b: i64
+    pub prior_b: i64,
+    pub learning_rate: f64
+}
+
+impl SimpleAdaptive {
+    fn settings(&self) -> AdaptationSettings {
+        AdaptationSettings {
+            prior_a: self.prior_a,
+            prior_b: self.prior_b,
+            learning_rate: self.learning_rate
+        }
+    }
 }
 
 impl Named for SimpleAdaptive {
@@ -28,116 +37,84 @@ impl Parametrized for SimpleAdaptive {
         let mut hm = Map::new();
         hm.insert("prior a".to_string(), self.prior_a.into());
         hm.insert("prior b".to_string(), self.prior_b.into());
+        hm.insert("learning rate".to_string(), self.learning_rate.into());
         hm.insert("use spatial grid".to_string(), false.into());
         hm.insert("use spatial hyperparameters".to_string(), false.into());
         hm.insert("use features".to_string(), false.into());
-        return Value::Object(hm);
+        Value::Object(hm)
     }
 }
 
-impl<V: 'static + Clone, P: 'static> ReplacementSelection<V,P,DiscreteHyperparameters> for SimpleAdaptive {
+impl<V: 'static + Clone, P: 'static, H: 'static + SelfAdaptive> ReplacementSelection<V,P,H> for SimpleAdaptive {
     fn initialize_solver(&self,
                          pop_size: usize,
                          problem: Rc<P>,
                          elitism: Rc<dyn Elitism>,
-                         problem_config: Rc<ProblemConfig<V, P, DiscreteHyperparameters>>) -> Box<dyn UpdatableSolver<V>> {
-        let mut pop = Vec::with_capacity(pop_size);
-
-        for _i in 0..pop_size {
-            let org = problem_config.random_organism_generator.generate_organism(&problem);
-            pop.push(AdaptiveOrg::new(self.prior_a, self.prior_b, org));
-        }
+                         problem_config: Rc<ProblemConfig<V, P, H>>) -> Box<dyn UpdatableSolver<V>> {
+        let settings = self.settings();
+
+        let pop = problem_config.random_organism_generator.generate_population(&problem, pop_size)
+            .into_iter()
+            .map(|org| {
+                let encoding = H::initial_encoding(&problem_config.constant_hyperparameters, &settings);
+                AdaptiveOrg::new(encoding, org)
+            })
+            .collect();
 
-        return Box::new(SimpleAdaptiveExec {
+        Box::new(SimpleAdaptiveExec {
             problem: problem.clone(),
             organisms: pop,
             problem_config: problem_config.clone(),
-            elitism
-        });
+            elitism,
+            settings,
+            report: UpdateReport::default(),
+            iteration: 0
+        })
     }
 }
 
-#[derive(Copy, Clone)]
-struct AdaptiveOrg<V> {
-    a: i64,
-    b: i64,
+#[derive(Clone)]
+struct AdaptiveOrg<V,E> {
+    encoding: E,
     org: Organism<V>
 }
 
-impl<V> AdaptiveOrg<V> {
-    fn new(prior_a: i64, prior_b: i64, organism: Organism<V>) -> Self {
-        return AdaptiveOrg {
-            a: prior_a,
-            b: prior_b,
+impl<V,E> AdaptiveOrg<V,E> {
+    fn new(encoding: E, organism: Organism<V>) -> Self {
+        AdaptiveOrg {
+            encoding,
             org: organism
         }
     }
 
-    fn into_organism(self) -> Organism<V> {
-        return self.org;
-    }
+    fn mutate<H: SelfAdaptive<Encoding=E>>(&mut self, mutator: &dyn Mutator<V,H>, settings: &AdaptationSettings) -> bool {
+        H::mutate_encoding(&mut self.encoding, settings);
 
-    fn get_mut_prob(&self) -> f64 {
-        let af = self.a as f64;
-        return af/(af + self.b as f64);
-    }
-
-    fn mut_prob(&mut self) {
-        let mut rng = thread_rng();
+        let hyper = H::decode(&self.encoding);
 
-        while rng.gen::<f64>() < self.get_mut_prob() {
-            let p_ref = if rng.gen_bool(0.5) {
-                &mut self.a
-            }
-            else {
-                &mut self.b
-            };
-
-            if rng.gen_bool(0.5) {
-                *p_ref += 1;
-            }
-            else {
-                *p_ref -= 1;
-            }
-
-            *p_ref = (*p_ref).max(1);
-        }
-    }
-
-    fn mutate(&mut self, mutator: &dyn Mutator<V,DiscreteHyperparameters>) {
-
-        let mut rng = thread_rng();
-
-        self.mut_prob();
-
-        let hyper = DiscreteHyperparameters {
-            mutation_chance: self.get_mut_prob()
-        };
-
-        self.org.mutate(mutator, &hyper);
-    }
-
-    fn score_with_cache<P>(&mut self, scorer: &dyn Scorer<V,P>, problem: &P) -> f64 {
-        self.org.score_with_cache(scorer, problem)
+        self.org.mutate(mutator, &hyper)
     }
 
     fn organism_ref(&self) -> &Organism<V> {
-        return &self.org;
+        &self.org
     }
 
     fn organism_ref_mut(&mut self) -> &mut Organism<V> {
-        return &mut self.org;
+        &mut self.org
     }
 }
 
-pub struct SimpleAdaptiveExec<V,P> {
+pub struct SimpleAdaptiveExec<V,P,H: SelfAdaptive> {
     problem: Rc<P>,
-    organisms: Vec<AdaptiveOrg<V>>,
-    problem_config: Rc<ProblemConfig<V,P,DiscreteHyperparameters>>,
-    elitism: Rc<dyn Elitism>
+    organisms: Vec<AdaptiveOrg<V,H::Encoding>>,
+    problem_config: Rc<ProblemConfig<V,P,H>>,
+    elitism: Rc<dyn Elitism>,
+    settings: AdaptationSettings,
+    report: UpdateReport,
+    iteration: u64
 }
 
-impl<V: Clone,P> UpdatableSolver<V> for SimpleAdaptiveExec<V,P> {
+impl<V: Clone,P,H: SelfAdaptive> UpdatableSolver<V> for SimpleAdaptiveExec<V,P,H> {
     fn update(&mut self) -> Vec<Organism<V>> {
 
         let scorer = &self.problem_config.scorer;
@@ -153,31 +130,56 @@ impl<V: Clone,P> UpdatableSolver<V> for SimpleAdaptiveExec<V,P> {
 
         let score;
         let score_replace;
+        let changed;
 
         let org = {
             let org_a= self.organisms.get_mut(index).unwrap();
 
             let mut org_b = org_a.clone();
 
-            org_b.mutate(self.problem_config.mutator.as_ref());
+            changed = org_b.mutate(self.problem_config.mutator.as_ref(), &self.settings);
+            org_b.organism_ref_mut().record_birth(self.iteration, "self-adaptive mutation", || None);
 
-            score = org_b.score_with_cache(scorer.as_ref(), self.problem.as_ref());
+            score = org_b.organism_ref_mut().offspring_score(scorer.as_ref(), self.problem.as_ref(), &self.problem_config.re_evaluation, self.iteration);
             org_b
         };
 
         {
-            let org_c: &mut AdaptiveOrg<V> = self.organisms.get_mut(index_replace).unwrap();
-            score_replace = org_c.score_with_cache(scorer.as_ref(), self.problem.as_ref());
+            let org_c: &mut AdaptiveOrg<V,H::Encoding> = self.organisms.get_mut(index_replace).unwrap();
+            score_replace = org_c.organism_ref_mut().incumbent_score(scorer.as_ref(), self.problem.as_ref(), &self.problem_config.re_evaluation, self.iteration);
         }
 
         let keep_first = self.elitism.choose(score, score_replace);
 
+        self.report = UpdateReport {
+            accepted: keep_first,
+            mutation_changed: changed,
+            offspring_score: score,
+            competitor_score: Some(score_replace),
+            offspring_hyperparameters: org.organism_ref().get_hyperparameters().cloned(),
+            offspring_lineage: org.organism_ref().get_lineage().cloned()
+        };
+
         if keep_first {
             self.organisms[index_replace] = org;
         }
 
-        return self.organisms.iter()
+        self.iteration += 1;
+
+        self.organisms.iter()
             .map(|ao| ao.organism_ref().clone())
-            .collect();
+            .collect()
+    }
+
+    fn last_update(&self) -> &UpdateReport {
+        &self.report
+    }
+
+    fn organisms_mut(&mut self) -> Vec<&mut Organism<V>> {
+        self.organisms.iter_mut().map(|ao| ao.organism_ref_mut()).collect()
+    }
+
+    fn archive(&self) -> Vec<ArchiveEntry<'_, V>> {
+        self.organisms.iter().map(|ao| ArchiveEntry::organism(ao.organism_ref())).collect()
     }
 }
\ No newline at end of file
@@ -9,6 +9,7 @@ struct Test {
     genotype: Vec<usize>
 }
 
+#[allow(unused_variables, clippy::needless_return)]
 fn main() {
 
 
@@ -1,5 +1,6 @@
 use ndarray::Array;
 
+#[allow(unused_variables)]
 fn main() {
 
     let arr = Array::from_shape_fn((3,3,3), |_| 3);
@@ -1,9 +1,7 @@
-use std::collections::HashMap;
-use ordered_float::OrderedFloat;
-use serde_json::{Value, Number, Map};
+use serde_json::{Value, Map};
 
 pub fn decimal_param(val: f64) -> Value {
-    return Value::Number(serde_json::Number::from_f64(val).unwrap());
+    Value::Number(serde_json::Number::from_f64(val).unwrap())
 }
 
 pub fn str_param(str: &str) -> Value {
@@ -11,7 +9,7 @@ pub fn str_param(str: &str) -> Value {
 }
 
 pub fn int_param(int: i64) -> Value {
-    return Value::Number(int.into())
+    Value::Number(int.into())
 }
 
 pub trait Named {
@@ -20,6 +18,14 @@ pub trait Named {
 
 pub trait Parametrized {
     fn parameters(&self) -> Value {
-        return Value::Object(Map::new());
+        Value::Object(Map::new())
     }
-}
\ No newline at end of file
+}
+
+/// Name and parameters of a configurable component
+pub fn component_param<C: Named + Parametrized + ?Sized>(component: &C) -> Value {
+    let mut hm = Map::new();
+    hm.insert("name".to_string(), component.name().into());
+    hm.insert("parameters".to_string(), component.parameters());
+    Value::Object(hm)
+}
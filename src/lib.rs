@@ -0,0 +1,24 @@
+
+extern crate rand;
+extern crate csv;
+extern crate serde;
+extern crate serde_json;
+extern crate statistical;
+extern crate chrono;
+extern crate itertools;
+
+pub mod common;
+pub mod problems;
+pub mod organism;
+pub mod algorithm;
+pub mod features;
+pub mod scoring;
+pub mod statistics;
+pub mod quality_diversity;
+pub mod snapshot;
+pub mod plot;
+pub mod metadata;
+pub mod sinks;
+pub mod progress;
+pub mod registry;
+pub mod runner;
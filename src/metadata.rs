@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use chrono::{DateTime, Local};
+use serde_json::{Value, Map};
+
+//
+// Metadata sidecar of a results file: configuration, machine and timing of the experiment
+//
+
+/// Path of the metadata file describing the results file `results`
+pub fn sidecar_path(results: &Path) -> PathBuf {
+    results.with_extension("json")
+}
+
+/// Trimmed standard output of a command, `None` if it could not be run or failed
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+fn hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname").ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| command_output("hostname", &[]))
+}
+
+/// Machine, code revision and command line of the current process
+pub fn environment_info() -> Value {
+    // revision of the sources the binary was built from, captured by the build script
+    let revision = option_env!("GIT_REVISION");
+    let dirty = option_env!("GIT_DIRTY").map(|d| d == "true");
+
+    let mut hm = Map::new();
+    hm.insert("hostname".to_string(), hostname().into());
+    hm.insert("git revision".to_string(), revision.into());
+    hm.insert("uncommitted changes".to_string(), dirty.into());
+    hm.insert("version".to_string(), env!("CARGO_PKG_VERSION").into());
+    hm.insert("os".to_string(), std::env::consts::OS.into());
+    hm.insert("architecture".to_string(), std::env::consts::ARCH.into());
+    hm.insert("command line".to_string(), std::env::args().collect::<Vec<String>>().into());
+    // every random draw goes through `thread_rng`, which cannot be seeded: there is no seed to record
+    // and runs cannot be replayed
+    hm.insert("random number generator".to_string(), "thread_rng, seeded from system entropy, not reproducible".into());
+    Value::Object(hm)
+}
+
+/// Sidecar of one results file, rewritten when the experiment progresses
+pub struct ResultsMetadata {
+    pub path: PathBuf,
+    pub results_file: String,
+    pub configuration: Value,
+    pub environment: Value,
+    pub start: DateTime<Local>,
+    pub end: Option<DateTime<Local>>,
+    pub completed_runs: u64,
+    pub total_runs: u64
+}
+
+impl ResultsMetadata {
+    pub fn new(results: &Path, configuration: Value, environment: Value, total_runs: u64) -> Self {
+        ResultsMetadata {
+            path: sidecar_path(results),
+            results_file: results.file_name().map_or(String::new(), |f| f.to_string_lossy().to_string()),
+            configuration,
+            environment,
+            start: Local::now(),
+            end: None,
+            completed_runs: 0,
+            total_runs
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut timing = Map::new();
+        timing.insert("start".to_string(), self.start.to_rfc3339().into());
+        timing.insert("end".to_string(), self.end.map(|e| e.to_rfc3339()).into());
+        timing.insert("duration (s)".to_string(),
+                      self.end.map(|e| e.signed_duration_since(self.start).num_milliseconds() as f64 / 1000.0).into());
+        timing.insert("completed runs".to_string(), self.completed_runs.into());
+        timing.insert("total runs".to_string(), self.total_runs.into());
+
+        let mut hm = Map::new();
+        hm.insert("results file".to_string(), self.results_file.clone().into());
+        hm.insert("configuration".to_string(), self.configuration.clone());
+        hm.insert("environment".to_string(), self.environment.clone());
+        hm.insert("timing".to_string(), Value::Object(timing));
+        Value::Object(hm)
+    }
+
+    pub fn write(&self) -> Result<(), String> {
+        let file = File::create(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &self.to_json())
+            .map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    /// Records the end of a run and saves the progress
+    pub fn run_completed(&mut self) -> Result<(), String> {
+        self.completed_runs += 1;
+        self.write()
+    }
+
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.end = Some(Local::now());
+        self.write()
+    }
+}
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 use super::organism::Organism;
-use crate::organism::OrganismGenerator;
-use ndarray::{Array, ArrayD};
+use ndarray::ArrayD;
 
 #[derive(Clone)]
 pub struct Grid<V,F> {
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn new_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Where an organism comes from, only tracked when lineage tracking is enabled
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lineage {
+    pub id: u64,
+    pub parents: Vec<u64>,
+    pub birth_iteration: u64,
+    pub operator: &'static str,
+    pub birth_cell: Option<Vec<usize>>
+}
+
+impl Lineage {
+    /// Lineage of an organism of the initial population
+    pub fn root() -> Self {
+        Lineage {
+            id: new_id(),
+            parents: Vec::new(),
+            birth_iteration: 0,
+            operator: "initialization",
+            birth_cell: None
+        }
+    }
+
+    pub fn child(parents: Vec<u64>, birth_iteration: u64, operator: &'static str, birth_cell: Option<Vec<usize>>) -> Self {
+        Lineage {
+            id: new_id(),
+            parents,
+            birth_iteration,
+            operator,
+            birth_cell
+        }
+    }
+
+    pub fn header() -> Vec<&'static str> {
+        vec!["event", "id", "parents", "birth iteration", "operator", "birth cell"]
+    }
+
+    /// Genealogy log record, `event` tells why the organism is logged
+    pub fn row(&self, event: &str) -> Vec<String> {
+        let join = |v: &[u64]| v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(";");
+        vec![
+            event.to_string(),
+            self.id.to_string(),
+            join(&self.parents),
+            self.birth_iteration.to_string(),
+            self.operator.to_string(),
+            self.birth_cell.as_ref().map_or(String::new(),
+                |c| c.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(";"))
+        ]
+    }
+}
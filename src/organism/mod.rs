@@ -1,3 +1,6 @@
+#[allow(clippy::module_inception)]
 pub mod organism;
 pub mod grid;
+pub mod lineage;
+pub mod warm_start;
 pub use organism::*;
\ No newline at end of file
@@ -1,27 +1,78 @@
 use super::super::common::Named;
 use crate::common::Parametrized;
-use std::rc::Rc;
 use crate::algorithm::mutation::Mutator;
 use crate::scoring::Scorer;
+use crate::problems::Hyperparameter;
+use crate::organism::lineage::Lineage;
+use crate::algorithm::evaluation::ReEvaluation;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Organism<T> {
     pub genotype: T,
-    score: Option<f64>
+    score: Option<f64>,
+    /// Evaluations averaged in the score
+    samples: usize,
+    /// Iteration the score was last sampled at
+    scored_at: u64,
+    hyperparameters: Option<Vec<f64>>,
+    lineage: Option<Box<Lineage>>
 }
 
 impl<T> Organism<T> {
+    /// Organism of the initial population, not scored yet
+    pub fn new(genotype: T) -> Self {
+        Organism {
+            genotype,
+            score: None,
+            samples: 0,
+            scored_at: 0,
+            hyperparameters: None,
+            lineage: None
+        }
+    }
+
 
-    pub fn mutate<H>(&mut self, mutator: &dyn Mutator<T,H>, hyperparameters: &H) -> bool {
+    pub fn mutate<H: Hyperparameter>(&mut self, mutator: &dyn Mutator<T,H>, hyperparameters: &H) -> bool {
         let changed = mutator.mutate(&mut self.genotype, hyperparameters);
         self.score = None;
-        return changed;
+        self.hyperparameters = Some(hyperparameters.to_values());
+        changed
     }
 
     pub fn get_score(&self) -> Option<f64> {
         self.score
     }
 
+    /// Drops the score and its samples, the organism is scored again when it is next evaluated
+    pub fn forget_score(&mut self) {
+        self.score = None;
+        self.samples = 0;
+    }
+
+    /// Values of the hyperparameters this organism was produced with, `None` for generated organisms
+    pub fn get_hyperparameters(&self) -> Option<&Vec<f64>> {
+        self.hyperparameters.as_ref()
+    }
+
+    pub fn get_lineage(&self) -> Option<&Lineage> {
+        self.lineage.as_deref()
+    }
+
+    /// Starts tracking the lineage of this organism and of its future offspring
+    pub fn enable_lineage(&mut self) {
+        if self.lineage.is_none() {
+            self.lineage = Some(Box::new(Lineage::root()));
+        }
+    }
+
+    /// Gives a new identity to an organism cloned from its parent, does nothing if lineage is not tracked.
+    /// The birth cell is only computed when lineage is tracked.
+    pub fn record_birth<C: FnOnce() -> Option<Vec<usize>>>(&mut self, birth_iteration: u64, operator: &'static str, birth_cell: C) {
+        if let Some(parent) = &self.lineage {
+            self.lineage = Some(Box::new(Lineage::child(vec![parent.id], birth_iteration, operator, birth_cell())));
+        }
+    }
+
     pub fn only_score<P>(&self, scorer: &dyn Scorer<T,P>, problem: &P) -> f64 {
         scorer.score(&self.genotype, problem)
     }
@@ -36,11 +87,70 @@ impl<T> Organism<T> {
             Some(s) => s
         }
     }
+
+    /// Score of an offspring created at `iteration`, see `offspring_scores`
+    pub fn offspring_score<P>(&mut self, scorer: &dyn Scorer<T,P>, problem: &P, re_evaluation: &ReEvaluation, iteration: u64) -> f64 {
+        Organism::offspring_scores(vec![self], scorer, problem, re_evaluation, iteration)[0]
+    }
+
+    /// Score of an organism compared with an offspring at `iteration`, see `incumbent_scores`
+    pub fn incumbent_score<P>(&mut self, scorer: &dyn Scorer<T,P>, problem: &P, re_evaluation: &ReEvaluation, iteration: u64) -> f64 {
+        Organism::incumbent_scores(vec![self], scorer, problem, re_evaluation, iteration)[0]
+    }
+
+    /// Scores of organisms, those not scored yet are scored together with one batch
+    pub fn offspring_scores<P>(organisms: Vec<&mut Organism<T>>, scorer: &dyn Scorer<T,P>, problem: &P,
+                               re_evaluation: &ReEvaluation, iteration: u64) -> Vec<f64> {
+        Organism::evaluate(organisms, scorer, problem, re_evaluation, iteration, false)
+    }
+
+    /// Scores of organisms kept from earlier iterations, sampled again as the re-evaluation strategy asks
+    pub fn incumbent_scores<P>(organisms: Vec<&mut Organism<T>>, scorer: &dyn Scorer<T,P>, problem: &P,
+                               re_evaluation: &ReEvaluation, iteration: u64) -> Vec<f64> {
+        Organism::evaluate(organisms, scorer, problem, re_evaluation, iteration, true)
+    }
+
+    fn evaluate<P>(mut organisms: Vec<&mut Organism<T>>, scorer: &dyn Scorer<T,P>, problem: &P,
+                   re_evaluation: &ReEvaluation, iteration: u64, incumbents: bool) -> Vec<f64> {
+        // new samples of every organism, and whether they replace its score or are averaged with it
+        let plan: Vec<(usize, bool)> = organisms.iter().map(|o| match (o.score, re_evaluation) {
+            (None, _) => (re_evaluation.samples(), true),
+            (Some(_), ReEvaluation::Incumbent) if incumbents => (1, false),
+            (Some(_), ReEvaluation::Age { max_age }) if incumbents && iteration >= o.scored_at + max_age => (1, true),
+            _ => (0, false)
+        }).collect();
+
+        let samples = {
+            let genomes: Vec<&T> = organisms.iter().zip(&plan)
+                .flat_map(|(o, &(n, _))| std::iter::repeat_n(&o.genotype, n))
+                .collect();
+            if genomes.is_empty() { Vec::new() } else { scorer.score_batch(&genomes, problem) }
+        };
+
+        let mut samples = samples.into_iter();
+        for (org, &(n, replace)) in organisms.iter_mut().zip(&plan) {
+            if n == 0 {
+                continue;
+            }
+            let sum: f64 = samples.by_ref().take(n).sum();
+            if replace {
+                org.score = Some(sum / n as f64);
+                org.samples = n;
+            }
+            else {
+                let total = org.score.unwrap() * org.samples as f64 + sum;
+                org.samples += n;
+                org.score = Some(total / org.samples as f64);
+            }
+            org.scored_at = iteration;
+        }
+        organisms.iter().map(|o| o.score.unwrap()).collect()
+    }
 }
 
 impl<V: PartialEq> Organism<V> {
     pub fn same_genetic_code(&self, other: &Organism<V>) -> bool {
-        return self.genotype.eq(&other.genotype);
+        self.genotype.eq(&other.genotype)
     }
 }
 
@@ -48,6 +158,31 @@ pub trait Metric {
     fn distance_to(&self, other: &Self) -> f64;
 }
 
+/// Population-level diversity measures, only some of them make sense for a given genome type
+pub trait Diversity: Metric + Sized {
+    fn distinct_genotypes(population: &[&Self]) -> usize;
+
+    /// Mean distance of the genomes to their centroid, for real-valued genomes
+    fn centroid_dispersion(_population: &[&Self]) -> Option<f64> {
+        None
+    }
+
+    /// Mean over loci of the entropy of the allele frequencies, for bit strings
+    fn locus_entropy(_population: &[&Self]) -> Option<f64> {
+        None
+    }
+
+    /// Entropy of the frequencies of the edges used by the population, for tours
+    fn edge_entropy(_population: &[&Self]) -> Option<f64> {
+        None
+    }
+}
+
+/// Checks of a genotype made outside of the problem, such as one read from a file
+pub trait Validate<P> {
+    fn validate(&self, problem: &P) -> Result<(), String>;
+}
+
 impl<V: Metric> Metric for Organism<V> {
     fn distance_to(&self, other: &Organism<V>) -> f64 {
         self.genotype.distance_to(&other.genotype)
@@ -57,7 +192,16 @@ impl<V: Metric> Metric for Organism<V> {
 pub trait OrganismGenerator<V,P>: Named + Parametrized {
     fn generate(&self, problem: &P) -> V;
     fn generate_organism(&self, problem: &P) -> Organism<V> {
-        return Organism{genotype: self.generate(problem),
-                        score: Option::None}
+        Organism::new(self.generate(problem))
+    }
+
+    /// Initial population of a run, independent draws of `generate` by default
+    fn generate_population(&self, problem: &P, size: usize) -> Vec<Organism<V>> {
+        (0..size).map(|_| self.generate_organism(problem)).collect()
+    }
+
+    /// Whether the genomes generated fit `problem`, they always do unless they come from outside of it
+    fn check(&self, _problem: &P) -> Result<(), String> {
+        Ok(())
     }
 }
\ No newline at end of file
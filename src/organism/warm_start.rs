@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::rc::Rc;
+use rand::thread_rng;
+use rand::seq::SliceRandom;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, Map};
+use crate::common::{Named, Parametrized, str_param};
+use crate::organism::{Organism, OrganismGenerator, Validate};
+
+/// Seeds the initial population with genomes saved by a previous run.
+///
+/// The file is in JSON Lines, as written by the population export: every line is either an object
+/// with a `"genotype"` (and optionally a `"score"`) or a bare genotype.
+pub struct WarmStartGenerator<V,P> {
+    pub seeds: Vec<V>,
+    /// Generator of the organisms beyond the seeds, the seeds are repeated when `None`
+    pub fill: Option<Rc<dyn OrganismGenerator<V,P>>>,
+    pub source: String
+}
+
+impl<V: DeserializeOwned,P> WarmStartGenerator<V,P> {
+    /// Reads the seeds of `path`, keeping only the `best` highest scoring ones if given
+    pub fn from_file(path: &Path, best: Option<usize>, fill: Option<Rc<dyn OrganismGenerator<V,P>>>) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut seeds: Vec<(Option<f64>, V)> = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let json: Value = serde_json::from_str(&line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+            let score = json.get("score").and_then(Value::as_f64);
+            let genotype = match json.get("genotype") {
+                Some(g) => g.clone(),
+                None => json
+            };
+            let genotype = serde_json::from_value(genotype)
+                .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+            seeds.push((score, genotype));
+        }
+
+        if let Some(best) = best {
+            // unscored genomes come last
+            seeds.sort_by(|(a, _), (b, _)| b.unwrap_or(f64::NEG_INFINITY)
+                .partial_cmp(&a.unwrap_or(f64::NEG_INFINITY))
+                .unwrap_or(std::cmp::Ordering::Equal));
+            seeds.truncate(best);
+        }
+
+        if seeds.is_empty() {
+            return Err(format!("{}: no genome to start from", path.display()));
+        }
+
+        Ok(WarmStartGenerator {
+            seeds: seeds.into_iter().map(|(_, g)| g).collect(),
+            fill,
+            source: path.display().to_string()
+        })
+    }
+}
+
+impl<V,P> Named for WarmStartGenerator<V,P> {
+    fn name(&self) -> String {
+        "Warm start generator".to_string()
+    }
+}
+
+impl<V,P> Parametrized for WarmStartGenerator<V,P> {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("source".to_string(), str_param(&self.source));
+        hm.insert("number of seeds".to_string(), self.seeds.len().into());
+        hm.insert("fill generator".to_string(), match &self.fill {
+            Some(g) => g.name().into(),
+            None => Value::Null
+        });
+        Value::Object(hm)
+    }
+}
+
+impl<V: Clone + Validate<P>,P> OrganismGenerator<V,P> for WarmStartGenerator<V,P> {
+    fn generate(&self, _problem: &P) -> V {
+        self.seeds.choose(&mut thread_rng()).unwrap().clone()
+    }
+
+    /// The seeds in order, then organisms of the fill generator or the seeds again
+    fn generate_population(&self, problem: &P, size: usize) -> Vec<Organism<V>> {
+        let mut population: Vec<Organism<V>> = self.seeds.iter().take(size).cloned().map(Organism::new).collect();
+
+        while population.len() < size {
+            let org = match &self.fill {
+                Some(fill) => fill.generate_organism(problem),
+                None => Organism::new(self.seeds[population.len() % self.seeds.len()].clone())
+            };
+            population.push(org);
+        }
+
+        population
+    }
+
+    fn check(&self, problem: &P) -> Result<(), String> {
+        for (i, seed) in self.seeds.iter().enumerate() {
+            seed.validate(problem).map_err(|e| format!("{} genome {}: {}", self.source, i + 1, e))?;
+        }
+        match &self.fill {
+            Some(fill) => fill.check(problem),
+            None => Ok(())
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+use crate::metadata::sidecar_path;
+
+//
+// Convergence curves of a results file, rendered to SVG without any plotting dependency
+//
+
+/// Iterations kept per run at most, runs are subsampled evenly above that
+const MAX_POINTS: u64 = 500;
+const WIDTH: f64 = 1000.0;
+const HEIGHT: f64 = 600.0;
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 330.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 60.0;
+
+/// Columns of the results file that get a plot, with the name of their axis
+const METRICS: [(&str, &str); 4] = [
+    ("max score", "best score"),
+    ("best score so far", "best score so far"),
+    ("mean score", "mean score"),
+    ("mean genetic distance", "genetic distance")
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum XAxis {
+    Iteration,
+    Generations,
+    /// Genomes scored by the solver, as logged in the results
+    Evaluations
+}
+
+impl XAxis {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "iteration" => Ok(XAxis::Iteration),
+            "generations" => Ok(XAxis::Generations),
+            "evaluations" => Ok(XAxis::Evaluations),
+            other => Err(format!("unknown x axis \"{}\", expected iteration, generations or evaluations", other))
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            XAxis::Iteration => "iteration",
+            XAxis::Generations => "generations",
+            XAxis::Evaluations => "evaluations"
+        }
+    }
+}
+
+/// Display name of an algorithm, in the style of `scripts/analyze.py`
+pub fn algorithm_label(algorithm: &Value) -> String {
+    let name = algorithm["algorithm name"].as_str().unwrap_or("unknown");
+    let config = &algorithm["algorithm config"];
+    let flag = |key: &str| config.get(key).and_then(Value::as_bool).unwrap_or(false);
+
+    let mut label = if name.to_lowercase().contains("generalized map elite") {
+        let mapping = match (flag("use features"), flag("use spatial hyperparameters")) {
+            (false, false) => "only grid",
+            (false, true) => "map hyperparameters",
+            (true, false) => "map features",
+            (true, true) => "map both"
+        };
+        format!("GMAP Elite: {}", mapping)
+    } else {
+        name.to_string()
+    };
+
+    match algorithm["elitism"].as_str() {
+        Some("Metropolis-Hastings") => label.push_str(" - MH"),
+        Some("Greedy_selection") => label.push_str(" - greedy"),
+        Some(other) => label.push_str(&format!(" - {}", other)),
+        None => {}
+    }
+    label
+}
+
+fn colour(algorithm: &Value) -> &'static str {
+    let name = algorithm["algorithm name"].as_str().unwrap_or("");
+    let config = &algorithm["algorithm config"];
+    let flag = |key: &str| config.get(key).and_then(Value::as_bool).unwrap_or(false);
+
+    match (name, flag("use features"), flag("use spatial hyperparameters")) {
+        ("SimpleReplacement", _, _) => "grey",
+        ("Generalized MAP Elite algorithm", false, false) => "hotpink",
+        ("Generalized MAP Elite algorithm", false, true) => "turquoise",
+        ("Generalized MAP Elite algorithm", true, false) => "green",
+        ("Generalized MAP Elite algorithm", true, true) => "indigo",
+        ("MAP Elite", _, _) => "gold",
+        ("Simple Adaptive GA", _, _) => "darkred",
+        _ => "black"
+    }
+}
+
+/// x, first quartile, median and third quartile
+type BandPoint = (f64, f64, f64, f64);
+
+/// Curve of one algorithm in a plot
+struct Series {
+    label: String,
+    colour: &'static str,
+    dashed: bool,
+    band: Vec<BandPoint>
+}
+
+/// Quantile of sorted values, interpolated between the closest ranks
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let (low, high) = (position.floor() as usize, position.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (position - low as f64)
+}
+
+/// Median and interquartile range of every metric, per algorithm and x position
+struct Curves {
+    /// metric -> algorithm -> x -> values of every repetition
+    values: Vec<BTreeMap<usize, BTreeMap<u64, Vec<f64>>>>,
+    /// x axis value of the subsampled iterations of every algorithm, generations depend on the population size
+    x_values: BTreeMap<(usize, u64), f64>
+}
+
+impl Curves {
+    fn band(&self, metric: usize, algorithm: usize) -> Vec<BandPoint> {
+        let points = match self.values[metric].get(&algorithm) {
+            Some(p) => p,
+            None => return Vec::new()
+        };
+        points.iter().map(|(&iteration, values)| {
+            let mut sorted = values.clone();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            (self.x_values[&(algorithm, iteration)], quantile(&sorted, 0.25), quantile(&sorted, 0.5), quantile(&sorted, 0.75))
+        }).collect()
+    }
+}
+
+/// Configuration of a results file and a CSV reader over its rows.
+///
+/// The configuration is read from the metadata sidecar, or from the quoted JSON line
+/// that starts the results files of older versions.
+fn open_results(path: &Path) -> Result<(Value, csv::Reader<BufReader<File>>), String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+
+    let starts_with_json = reader.fill_buf().map_err(|e| format!("{}: {}", path.display(), e))?.starts_with(b"\"{");
+    let configuration: Value = if starts_with_json {
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let json = first_line.trim().trim_start_matches('"').trim_end_matches('"');
+        serde_json::from_str(json).map_err(|e| format!("{}: JSON header: {}", path.display(), e))?
+    } else {
+        let sidecar = sidecar_path(path);
+        let text = std::fs::read_to_string(&sidecar).map_err(|e| format!("{}: {}", sidecar.display(), e))?;
+        let metadata: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", sidecar.display(), e))?;
+        metadata["configuration"].clone()
+    };
+
+    Ok((configuration, csv::Reader::from_reader(reader)))
+}
+
+/// Last logged values of the run being read, sampled on the regular iteration grid of the plots.
+///
+/// Runs may only log some of their iterations, every point of the grid takes the last values
+/// logged at or before it.
+struct RunSampler {
+    algorithm: usize,
+    repetition: String,
+    next_point: u64,
+    /// Organisms in the population at the last logged iteration, to count generations
+    number_of_organisms: f64,
+    /// Evaluations at the last logged iteration
+    evaluations: f64,
+    last_values: Vec<Option<f64>>
+}
+
+impl RunSampler {
+    /// Adds the points of the grid strictly before `until` to the curves
+    fn sample_until(&mut self, until: u64, stride: u64, x_axis: XAxis, curves: &mut Curves) {
+        while self.next_point < until {
+            let iteration = self.next_point;
+            curves.x_values.entry((self.algorithm, iteration)).or_insert(match x_axis {
+                XAxis::Iteration => iteration as f64,
+                XAxis::Generations => iteration as f64 / self.number_of_organisms,
+                XAxis::Evaluations => self.evaluations
+            });
+            for (m, value) in self.last_values.iter().enumerate() {
+                if let Some(v) = value {
+                    curves.values[m].entry(self.algorithm).or_default().entry(iteration).or_default().push(*v);
+                }
+            }
+            self.next_point += stride;
+        }
+    }
+}
+
+fn read_curves(path: &Path, header: &Value, mut reader: csv::Reader<BufReader<File>>, x_axis: XAxis) -> Result<Curves, String> {
+    let columns = reader.headers().map_err(|e| format!("{}: {}", path.display(), e))?.clone();
+    let column = |name: &str| columns.iter().position(|c| c == name)
+        .ok_or_else(|| format!("{}: no \"{}\" column", path.display(), name));
+
+    let algorithm_column = column("algorithm index")?;
+    let repetition_column = column("repetition")?;
+    let iteration_column = column("iteration")?;
+    let organisms_column = column("number of organisms")?;
+    let evaluations_column = match x_axis {
+        XAxis::Evaluations => Some(column("evaluations")
+            .map_err(|e| format!("{}, the results were written before evaluations were logged", e))?),
+        _ => None
+    };
+    // files written before a metric existed are plotted without it
+    let metrics: Vec<(usize, usize)> = METRICS.iter().enumerate()
+        .filter_map(|(m, (name, _))| column(name).ok().map(|c| (m, c)))
+        .collect();
+
+    let iterations = header["common"]["iterations per run"].as_u64().unwrap_or(MAX_POINTS);
+    let stride = (iterations / MAX_POINTS).max(1);
+
+    let mut curves = Curves {
+        values: vec![BTreeMap::new(); METRICS.len()],
+        x_values: BTreeMap::new()
+    };
+    let mut run: Option<RunSampler> = None;
+
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("{}: {}", path.display(), e))?;
+        let parse = |i: usize| record.get(i).and_then(|v| v.parse::<f64>().ok()).filter(|v| v.is_finite());
+
+        let (iteration, algorithm) = match (record.get(iteration_column).and_then(|v| v.parse::<u64>().ok()),
+                                            record.get(algorithm_column).and_then(|v| v.parse::<usize>().ok())) {
+            (Some(i), Some(a)) => (i, a),
+            _ => continue
+        };
+        let repetition = record.get(repetition_column).unwrap_or("").to_string();
+
+        let same_run = run.as_ref().is_some_and(|r| r.algorithm == algorithm && r.repetition == repetition);
+        if !same_run {
+            if let Some(mut finished) = run.take() {
+                finished.sample_until(iterations, stride, x_axis, &mut curves);
+            }
+            run = Some(RunSampler {
+                algorithm,
+                repetition,
+                next_point: 0,
+                number_of_organisms: 1.0,
+                evaluations: 0.0,
+                last_values: vec![None; METRICS.len()]
+            });
+        }
+
+        let sampler = run.as_mut().unwrap();
+        sampler.sample_until(iteration, stride, x_axis, &mut curves);
+        sampler.number_of_organisms = parse(organisms_column).unwrap_or(sampler.number_of_organisms);
+        if let Some(c) = evaluations_column {
+            sampler.evaluations = parse(c).unwrap_or(sampler.evaluations);
+        }
+        for &(m, c) in &metrics {
+            if let Some(v) = parse(c) {
+                sampler.last_values[m] = Some(v);
+            }
+        }
+    }
+    if let Some(mut finished) = run.take() {
+        finished.sample_until(iterations, stride, x_axis, &mut curves);
+    }
+
+    Ok(curves)
+}
+
+/// Round tick positions covering `[min, max]`, with their labels
+fn ticks(min: f64, max: f64) -> Vec<(f64, String)> {
+    let range = max - min;
+    let raw = range / 5.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|m| m * magnitude).find(|&s| s >= raw).unwrap_or(raw);
+
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+
+    let mut ticks = Vec::new();
+    let mut k = (min / step).ceil();
+    while k * step <= max + step * 1e-9 {
+        ticks.push((k * step, format!("{:.*}", decimals, k * step)));
+        k += 1.0;
+    }
+    ticks
+}
+
+/// Text of an SVG element, algorithm names and metrics may hold markup characters
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+fn write_svg(path: &Path, title: &str, x_label: &str, y_label: &str,
+             series: &[Series]) -> std::io::Result<()> {
+    let points = series.iter().flat_map(|s| s.band.iter());
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY);
+    for &(x, q1, _, q3) in points {
+        x_min = x_min.min(x);
+        x_max = x_max.max(x);
+        y_min = y_min.min(q1);
+        y_max = y_max.max(q3);
+    }
+    if x_max <= x_min || x_max.is_nan() {
+        x_max = x_min + 1.0;
+    }
+    if y_max <= y_min || y_max.is_nan() {
+        y_max = y_min + 1.0;
+    }
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let sx = |x: f64| MARGIN_LEFT + (x - x_min) / (x_max - x_min) * plot_width;
+    let sy = |y: f64| MARGIN_TOP + plot_height - (y - y_min) / (y_max - y_min) * plot_height;
+
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">", WIDTH, HEIGHT)?;
+    writeln!(w, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>")?;
+    writeln!(w, "<text x=\"{}\" y=\"24\" font-size=\"16\" text-anchor=\"middle\">{}</text>", MARGIN_LEFT + plot_width / 2.0, escape(title))?;
+
+    for (t, label) in ticks(x_min, x_max) {
+        writeln!(w, "<line x1=\"{0}\" x2=\"{0}\" y1=\"{1}\" y2=\"{2}\" stroke=\"#ddd\"/>", sx(t), MARGIN_TOP, MARGIN_TOP + plot_height)?;
+        writeln!(w, "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>", sx(t), MARGIN_TOP + plot_height + 18.0, label)?;
+    }
+    for (t, label) in ticks(y_min, y_max) {
+        writeln!(w, "<line x1=\"{}\" x2=\"{}\" y1=\"{2}\" y2=\"{2}\" stroke=\"#ddd\"/>", MARGIN_LEFT, MARGIN_LEFT + plot_width, sy(t))?;
+        writeln!(w, "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>", MARGIN_LEFT - 6.0, sy(t) + 4.0, label)?;
+    }
+    writeln!(w, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>", MARGIN_LEFT, MARGIN_TOP, plot_width, plot_height)?;
+    writeln!(w, "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>", MARGIN_LEFT + plot_width / 2.0, HEIGHT - 16.0, escape(x_label))?;
+    writeln!(w, "<text transform=\"translate(20,{}) rotate(-90)\" text-anchor=\"middle\">{}</text>", MARGIN_TOP + plot_height / 2.0, escape(y_label))?;
+
+    for (i, Series { label, colour, dashed, band }) in series.iter().enumerate() {
+        if band.is_empty() {
+            continue;
+        }
+        let upper = band.iter().map(|&(x, _, _, q3)| format!("{:.2},{:.2}", sx(x), sy(q3)));
+        let lower = band.iter().rev().map(|&(x, q1, _, _)| format!("{:.2},{:.2}", sx(x), sy(q1)));
+        writeln!(w, "<polygon points=\"{}\" fill=\"{}\" fill-opacity=\"0.15\" stroke=\"none\"/>",
+                 upper.chain(lower).collect::<Vec<String>>().join(" "), colour)?;
+
+        let median = band.iter().map(|&(x, _, m, _)| format!("{:.2},{:.2}", sx(x), sy(m))).collect::<Vec<String>>().join(" ");
+        let dash = if *dashed { " stroke-dasharray=\"2,3\"" } else { "" };
+        writeln!(w, "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"{}/>", median, colour, dash)?;
+
+        let y = MARGIN_TOP + 10.0 + i as f64 * 18.0;
+        let x = MARGIN_LEFT + plot_width + 15.0;
+        writeln!(w, "<line x1=\"{}\" x2=\"{}\" y1=\"{2}\" y2=\"{2}\" stroke=\"{3}\" stroke-width=\"2\"{4}/>", x, x + 25.0, y, colour, dash)?;
+        writeln!(w, "<text x=\"{}\" y=\"{}\">{}</text>", x + 32.0, y + 4.0, escape(label))?;
+    }
+
+    writeln!(w, "</svg>")?;
+    w.flush()
+}
+
+/// Renders the median and interquartile band of every algorithm for each metric of a results file,
+/// returns the paths of the written files
+pub fn plot_results(path: &Path, x_axis: XAxis, output_prefix: Option<&str>) -> Result<Vec<PathBuf>, String> {
+    let (header, reader) = open_results(path)?;
+    let curves = read_curves(path, &header, reader, x_axis)?;
+
+    let algorithms = header["algorithms"].as_array().cloned().unwrap_or_default();
+    let prefix = match output_prefix {
+        Some(p) => p.to_string(),
+        None => path.with_extension("").display().to_string()
+    };
+
+    let mut written = Vec::new();
+    for (m, (column, axis_name)) in METRICS.iter().enumerate() {
+        let series: Vec<Series> = curves.values[m].keys().map(|&a| {
+            let (label, colour, dashed) = match algorithms.get(a) {
+                Some(algorithm) => (algorithm_label(algorithm), colour(algorithm),
+                                    algorithm["elitism"].as_str() == Some("Metropolis-Hastings")),
+                None => (format!("algorithm {}", a), "black", false)
+            };
+            Series { label, colour, dashed, band: curves.band(m, a) }
+        }).collect();
+
+        if series.iter().all(|s| s.band.is_empty()) {
+            continue;
+        }
+
+        let file = PathBuf::from(format!("{}_{}.svg", prefix, column.replace(' ', "_")));
+        write_svg(&file, &format!("{} (median and interquartile range)", axis_name), x_axis.label(), axis_name, &series)
+            .map_err(|e| format!("{}: {}", file.display(), e))?;
+        written.push(file);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_markup_characters() {
+        assert_eq!(escape("GMAP Elite: map both - MH"), "GMAP Elite: map both - MH");
+        assert_eq!(escape("<a & \"b\"> 'c'"), "&lt;a &amp; &quot;b&quot;&gt; &apos;c&apos;");
+    }
+}
@@ -0,0 +1,308 @@
+use std::cell::Cell;
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::scoring::Scorer;
+
+//
+// Dynamic problems, whose fitness landscape changes during a run
+//
+
+/// Fitness landscape of a dynamic problem, keeping its current state between changes
+pub trait Dynamics<V,P>: Named + Parametrized {
+    /// Score of a genome in the current landscape
+    fn score(&self, genome: &V, problem: &P) -> f64;
+    /// Sets up the landscape of a run on `problem`
+    fn reset(&self, problem: &P);
+    /// Moves to the next landscape, returns whether the landscape changed
+    fn change(&self, problem: &P) -> bool;
+    /// Highest score of the current landscape, when known
+    fn optimum(&self, _problem: &P) -> Option<f64> {
+        None
+    }
+}
+
+/// Scores genomes in a landscape changing every `period` iterations
+pub struct DynamicScorer<D> {
+    pub dynamics: D,
+    pub period: u64,
+    /// Whether the landscape was set up, genomes scored before the run starts set it up
+    started: Cell<bool>
+}
+
+impl<D> DynamicScorer<D> {
+    pub fn new(dynamics: D, period: u64) -> Self {
+        DynamicScorer {
+            dynamics,
+            period: period.max(1),
+            started: Cell::new(false)
+        }
+    }
+}
+
+impl<D: Named> Named for DynamicScorer<D> {
+    fn name(&self) -> String {
+        self.dynamics.name()
+    }
+}
+
+impl<D: Parametrized> Parametrized for DynamicScorer<D> {
+    fn parameters(&self) -> Value {
+        let mut hm = match self.dynamics.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
+        };
+        hm.insert("change period".to_string(), self.period.into());
+        Value::Object(hm)
+    }
+}
+
+impl<V, P, D: Dynamics<V,P>> Scorer<V,P> for DynamicScorer<D> {
+    fn score(&self, genome: &V, problem: &P) -> f64 {
+        if !self.started.get() {
+            self.start_run(problem);
+        }
+        self.dynamics.score(genome, problem)
+    }
+
+    fn start_run(&self, problem: &P) {
+        self.dynamics.reset(problem);
+        self.started.set(true);
+    }
+
+    fn iteration_started(&self, iteration: u64, problem: &P) -> bool {
+        if iteration == 0 || !iteration.is_multiple_of(self.period) {
+            return false;
+        }
+        self.dynamics.change(problem)
+    }
+
+    fn is_dynamic(&self) -> bool {
+        true
+    }
+
+    fn optimum(&self, problem: &P) -> Option<f64> {
+        self.dynamics.optimum(problem)
+    }
+}
+
+/// Performance of a run on a dynamic problem
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DynamicStats {
+    /// Changes of the landscape since the start of the run
+    pub changes: u64,
+    /// Highest score of the population since the last change
+    pub best_since_change: f64,
+    /// Mean over the iterations of the best score since the last change
+    pub offline_performance: f64,
+    /// Mean over the iterations of the gap between the optimum and the best score since the last change,
+    /// when the dynamics know their optimum
+    pub offline_error: Option<f64>,
+    /// Changes after which the population got back to its performance before the change
+    pub recovered: u64,
+    /// Mean number of iterations to recover from a change
+    pub mean_recovery_time: Option<f64>
+}
+
+impl DynamicStats {
+    pub fn header() -> Vec<String> {
+        [
+            "landscape changes",
+            "best since change",
+            "offline performance",
+            "offline error",
+            "recovered changes",
+            "mean recovery time"
+        ].iter().map(|s| s.to_string()).collect()
+    }
+
+    pub fn row(stats: &Option<DynamicStats>) -> Vec<Value> {
+        match stats {
+            Some(s) => vec![
+                s.changes.into(),
+                s.best_since_change.into(),
+                s.offline_performance.into(),
+                s.offline_error.into(),
+                s.recovered.into(),
+                s.mean_recovery_time.into()
+            ],
+            None => vec![Value::Null; 6]
+        }
+    }
+}
+
+/// Performance before the last change, the population recovers when it gets back to it
+struct PendingRecovery {
+    change_iteration: u64,
+    error: Option<f64>,
+    best: f64
+}
+
+/// Follows the best scores of a run on a dynamic problem, iteration after iteration
+pub struct DynamicTracker {
+    changes: u64,
+    iterations: u64,
+    best_since_change: f64,
+    sum_best: f64,
+    sum_error: f64,
+    error_iterations: u64,
+    last_error: Option<f64>,
+    pending: Option<PendingRecovery>,
+    recovered: u64,
+    sum_recovery_time: u64
+}
+
+impl Default for DynamicTracker {
+    fn default() -> Self {
+        DynamicTracker {
+            changes: 0,
+            iterations: 0,
+            best_since_change: f64::NEG_INFINITY,
+            sum_best: 0.0,
+            sum_error: 0.0,
+            error_iterations: 0,
+            last_error: None,
+            pending: None,
+            recovered: 0,
+            sum_recovery_time: 0
+        }
+    }
+}
+
+impl DynamicTracker {
+    /// The landscape changed at the start of `iteration`, a change before the recovery from the previous
+    /// one counts as not recovered
+    pub fn change(&mut self, iteration: u64) {
+        self.pending = Some(PendingRecovery {
+            change_iteration: iteration,
+            error: self.last_error,
+            best: self.best_since_change
+        });
+        self.changes += 1;
+        self.best_since_change = f64::NEG_INFINITY;
+        self.last_error = None;
+    }
+
+    /// Best score of the population at `iteration`, with the optimum of the current landscape when known
+    pub fn observe(&mut self, iteration: u64, max_score: f64, optimum: Option<f64>) {
+        self.best_since_change = self.best_since_change.max(max_score);
+        self.iterations += 1;
+        self.sum_best += self.best_since_change;
+
+        self.last_error = optimum.map(|o| o - self.best_since_change);
+        if let Some(error) = self.last_error {
+            self.sum_error += error;
+            self.error_iterations += 1;
+        }
+
+        if let Some(pending) = &self.pending {
+            let recovered = match (pending.error, self.last_error) {
+                (Some(before), Some(error)) => error <= before,
+                _ => self.best_since_change >= pending.best
+            };
+            if recovered {
+                self.recovered += 1;
+                self.sum_recovery_time += iteration - pending.change_iteration;
+                self.pending = None;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> DynamicStats {
+        DynamicStats {
+            changes: self.changes,
+            best_since_change: self.best_since_change,
+            offline_performance: self.sum_best / self.iterations.max(1) as f64,
+            offline_error: if self.error_iterations > 0 { Some(self.sum_error / self.error_iterations as f64) } else { None },
+            recovered: self.recovered,
+            mean_recovery_time: if self.recovered > 0 { Some(self.sum_recovery_time as f64 / self.recovered as f64) } else { None }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_without_optimum() {
+        let mut tracker = DynamicTracker::default();
+        tracker.observe(0, 5.0, None);
+        tracker.observe(1, 8.0, None);
+        // recovers 2 iterations after the change, when the best since the change gets back to 8
+        tracker.change(2);
+        tracker.observe(2, 3.0, None);
+        tracker.observe(3, 7.0, None);
+        tracker.observe(4, 8.0, None);
+        // recovers at once, then a change before the recovery from the third one leaves it not recovered
+        tracker.change(5);
+        tracker.observe(5, 9.0, None);
+        tracker.change(6);
+        tracker.observe(6, 1.0, None);
+        tracker.change(7);
+        tracker.observe(7, 0.5, None);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.changes, 4);
+        assert_eq!(stats.recovered, 2);
+        assert_eq!(stats.mean_recovery_time, Some(1.0));
+        assert_eq!(stats.best_since_change, 0.5);
+        assert_eq!(stats.offline_error, None);
+        assert_eq!(stats.offline_performance, (5.0 + 8.0 + 3.0 + 7.0 + 8.0 + 9.0 + 1.0 + 0.5) / 8.0);
+    }
+
+    #[test]
+    fn recovery_against_the_optimum() {
+        let mut tracker = DynamicTracker::default();
+        tracker.observe(0, 8.0, Some(10.0));
+        // the optimum rose with the change, a best of 8 is not enough, the gap must get back to 2
+        tracker.change(1);
+        tracker.observe(1, 8.0, Some(12.0));
+        tracker.observe(2, 10.0, Some(12.0));
+
+        let stats = tracker.stats();
+        assert_eq!(stats.recovered, 1);
+        assert_eq!(stats.mean_recovery_time, Some(1.0));
+        assert_eq!(stats.offline_error, Some((2.0 + 4.0 + 2.0) / 3.0));
+    }
+
+    /// Changes only when it has steps left
+    struct Steps(Cell<u32>);
+
+    impl Named for Steps {
+        fn name(&self) -> String {
+            String::from("steps")
+        }
+    }
+
+    impl Parametrized for Steps {
+        fn parameters(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    impl Dynamics<(), ()> for Steps {
+        fn score(&self, _genome: &(), _problem: &()) -> f64 {
+            self.0.get() as f64
+        }
+
+        fn reset(&self, _problem: &()) {}
+
+        fn change(&self, _problem: &()) -> bool {
+            if self.0.get() == 0 {
+                return false;
+            }
+            self.0.set(self.0.get() - 1);
+            true
+        }
+    }
+
+    #[test]
+    fn changes_are_reported_only_when_the_landscape_changed() {
+        let scorer = DynamicScorer::new(Steps(Cell::new(1)), 2);
+        assert!(!scorer.iteration_started(0, &()));
+        assert!(!scorer.iteration_started(1, &()));
+        assert!(scorer.iteration_started(2, &()));
+        assert!(!scorer.iteration_started(4, &()));
+        assert_eq!(scorer.score(&(), &()), 0.0);
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use serde_json::{Value, Map};
+use crate::common::{Named, Parametrized, str_param};
+use crate::problems::{Environment, Hyperparameter, check_hyperparameter};
+
+//
+// Hyperparameter landscapes defined by data instead of code
+//
+
+/// Relative position of a cell along an axis, from 0 (first cell) to 1 (last cell)
+fn position(index: usize, size: usize) -> f64 {
+    if size <= 1 {
+        0.5
+    } else {
+        index as f64 / (size - 1) as f64
+    }
+}
+
+/// Index into a list of `len` values stretched over an axis of `size` cells
+fn scaled_index(index: usize, size: usize, len: usize) -> usize {
+    (index * len / size.max(1)).min(len - 1)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AxisMapping {
+    /// Evenly spaced values from `min` on the first cell to `max` on the last one
+    Linear { min: f64, max: f64 },
+    /// Log-spaced values from `min` on the first cell to `max` on the last one
+    Logarithmic { min: f64, max: f64 },
+    /// `start * ratio^i` on the i-th cell
+    Geometric { start: f64, ratio: f64 },
+    /// Explicit values, stretched over the axis if it does not have as many cells
+    Values(Vec<f64>)
+}
+
+impl AxisMapping {
+    pub fn value(&self, index: usize, size: usize) -> f64 {
+        match self {
+            AxisMapping::Linear { min, max } => min + (max - min) * position(index, size),
+            AxisMapping::Logarithmic { min, max } => {
+                (min.ln() + (max.ln() - min.ln()) * position(index, size)).exp()
+            },
+            AxisMapping::Geometric { start, ratio } => start * ratio.powi(index as i32),
+            AxisMapping::Values(values) => values[scaled_index(index, size, values.len())]
+        }
+    }
+
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let number = |key: &str| -> Result<f64, String> {
+            value.get(key)
+                .and_then(Value::as_f64)
+                .ok_or_else(|| format!("axis {} is missing the numeric field \"{}\"", value, key))
+        };
+
+        let kind = value.get("type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("axis {} has no \"type\"", value))?;
+
+        let mapping = match kind {
+            "linear" => AxisMapping::Linear { min: number("min")?, max: number("max")? },
+            "logarithmic" => {
+                let (min, max) = (number("min")?, number("max")?);
+                if min <= 0.0 || max <= 0.0 {
+                    return Err(format!("logarithmic axis {} must have positive bounds", value));
+                }
+                AxisMapping::Logarithmic { min, max }
+            },
+            "geometric" => AxisMapping::Geometric { start: number("start")?, ratio: number("ratio")? },
+            "values" => {
+                let values = value.get("values")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| format!("axis {} has no \"values\" list", value))?
+                    .iter()
+                    .map(|v| v.as_f64().ok_or_else(|| format!("{} is not a number", v)))
+                    .collect::<Result<Vec<f64>, String>>()?;
+                if values.is_empty() {
+                    return Err(format!("axis {} has an empty \"values\" list", value));
+                }
+                AxisMapping::Values(values)
+            },
+            other => return Err(format!("unknown axis type \"{}\"", other))
+        };
+
+        Ok(mapping)
+    }
+
+    /// Checks the values the axis gives on grids of any size for the hyperparameter at `index` of `H`
+    fn check<H: Hyperparameter>(&self, index: usize) -> Result<(), String> {
+        // values are monotonic along the axis, the extreme ones bound all the others
+        let extremes = match self {
+            AxisMapping::Linear { min, max } | AxisMapping::Logarithmic { min, max } => vec![*min, *max],
+            AxisMapping::Geometric { start, ratio } => {
+                // a growing probability reaches 1 on a large enough grid
+                if H::is_probability(index) && *ratio > 1.0 && *start != 0.0 {
+                    vec![*start, start * f64::INFINITY]
+                } else {
+                    vec![*start, start * ratio]
+                }
+            },
+            AxisMapping::Values(values) => values.clone()
+        };
+        for value in extremes {
+            check_hyperparameter::<H>(index, value).map_err(|e| format!("axis {}: {}", index, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Parametrized for AxisMapping {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        match self {
+            AxisMapping::Linear { min, max } => {
+                hm.insert("type".to_string(), str_param("linear"));
+                hm.insert("min".to_string(), (*min).into());
+                hm.insert("max".to_string(), (*max).into());
+            },
+            AxisMapping::Logarithmic { min, max } => {
+                hm.insert("type".to_string(), str_param("logarithmic"));
+                hm.insert("min".to_string(), (*min).into());
+                hm.insert("max".to_string(), (*max).into());
+            },
+            AxisMapping::Geometric { start, ratio } => {
+                hm.insert("type".to_string(), str_param("geometric"));
+                hm.insert("start".to_string(), (*start).into());
+                hm.insert("ratio".to_string(), (*ratio).into());
+            },
+            AxisMapping::Values(values) => {
+                hm.insert("type".to_string(), str_param("values"));
+                hm.insert("values".to_string(), values.clone().into());
+            }
+        }
+        Value::Object(hm)
+    }
+}
+
+/// Hyperparameters of every cell of a grid, stretched over the actual grid if its shape differs
+#[derive(Clone, Debug)]
+pub struct LookupTable {
+    pub shape: Vec<usize>,
+    pub cells: HashMap<Vec<usize>, Vec<f64>>
+}
+
+impl LookupTable {
+    fn new<H: Hyperparameter>(rows: Vec<(Vec<usize>, Vec<f64>)>) -> Result<Self, String> {
+        let number_of_axes = rows.first().map_or(0, |(coords, _)| coords.len());
+        let mut shape = vec![0; number_of_axes];
+        let mut cells = HashMap::with_capacity(rows.len());
+
+        for (coords, values) in rows {
+            if coords.len() != number_of_axes {
+                return Err(format!("cell {:?} has {} coordinates instead of {}", coords, coords.len(), number_of_axes));
+            }
+            for (i, &value) in values.iter().enumerate() {
+                check_hyperparameter::<H>(i, value).map_err(|e| format!("cell {:?}: {}", coords, e))?;
+            }
+            for (s, &c) in shape.iter_mut().zip(coords.iter()) {
+                *s = (*s).max(c + 1);
+            }
+            if cells.insert(coords.clone(), values).is_some() {
+                return Err(format!("cell {:?} is defined more than once", coords));
+            }
+        }
+
+        let expected: usize = shape.iter().product();
+        if cells.is_empty() || cells.len() != expected {
+            return Err(format!("table of shape {:?} defines {} cells instead of {}", shape, cells.len(), expected));
+        }
+
+        Ok(LookupTable { shape, cells })
+    }
+
+    fn get(&self, coordinates: &[(usize, usize)]) -> Option<&Vec<f64>> {
+        let key: Vec<usize> = self.shape.iter().enumerate()
+            .map(|(axis, &len)| match coordinates.get(axis) {
+                Some(&(index, size)) => scaled_index(index, size, len),
+                None => 0
+            })
+            .collect();
+        self.cells.get(&key)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Landscape {
+    /// The n-th hyperparameter only depends on the position along the n-th grid axis
+    Axes(Vec<AxisMapping>),
+    Table(LookupTable)
+}
+
+/// Environment whose hyperparameter landscape is read from a JSON or CSV file.
+///
+/// A JSON file contains either `{"axes": [...]}`, one axis per hyperparameter such as
+/// `{"type": "logarithmic", "min": 0.001, "max": 0.5}`, or
+/// `{"table": [{"coordinates": [0, 1], "hyperparameters": [0.1, 0.2]}, ...]}`.
+/// A CSV file is a table with a header, the coordinate columns followed by one column per hyperparameter.
+#[derive(Clone, Debug)]
+pub struct DataEnvironment {
+    pub landscape: Landscape,
+    pub source: String
+}
+
+impl DataEnvironment {
+    /// Reads the landscape of hyperparameters of type `H`, checking every value it can give
+    pub fn from_file<H: Hyperparameter>(path: &Path) -> Result<Self, String> {
+        let landscape = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Self::read_csv::<H>(path)?,
+            Some("json") => {
+                let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let json: Value = serde_json::from_reader(file).map_err(|e| format!("{}: {}", path.display(), e))?;
+                Self::from_json::<H>(&json).map_err(|e| format!("{}: {}", path.display(), e))?
+            },
+            _ => return Err(format!("{}: expected a .csv or .json file", path.display()))
+        };
+
+        Ok(DataEnvironment {
+            landscape,
+            source: path.display().to_string()
+        })
+    }
+
+    fn from_json<H: Hyperparameter>(json: &Value) -> Result<Landscape, String> {
+        let number_of_hyperparameters = H::number_of_hyperparameters();
+        if let Some(axes) = json.get("axes").and_then(Value::as_array) {
+            if axes.len() != number_of_hyperparameters {
+                return Err(format!("{} axes defined for {} hyperparameters", axes.len(), number_of_hyperparameters));
+            }
+            let axes = axes.iter().map(AxisMapping::from_json).collect::<Result<Vec<_>, String>>()?;
+            for (i, axis) in axes.iter().enumerate() {
+                axis.check::<H>(i)?;
+            }
+            return Ok(Landscape::Axes(axes));
+        }
+
+        let rows = json.get("table")
+            .and_then(Value::as_array)
+            .ok_or_else(|| "expected an \"axes\" or a \"table\" list".to_string())?;
+
+        let mut table = Vec::with_capacity(rows.len());
+        for row in rows {
+            let coords = row.get("coordinates")
+                .and_then(Value::as_array)
+                .ok_or_else(|| format!("row {} has no \"coordinates\"", row))?
+                .iter()
+                .map(|c| c.as_u64().map(|c| c as usize).ok_or_else(|| format!("invalid coordinate {}", c)))
+                .collect::<Result<Vec<usize>, String>>()?;
+            let values = row.get("hyperparameters")
+                .and_then(Value::as_array)
+                .ok_or_else(|| format!("row {} has no \"hyperparameters\"", row))?
+                .iter()
+                .map(|v| v.as_f64().ok_or_else(|| format!("invalid hyperparameter {}", v)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            if values.len() != number_of_hyperparameters {
+                return Err(format!("row {} has {} hyperparameters instead of {}", row, values.len(), number_of_hyperparameters));
+            }
+            table.push((coords, values));
+        }
+
+        Ok(Landscape::Table(LookupTable::new::<H>(table)?))
+    }
+
+    fn read_csv<H: Hyperparameter>(path: &Path) -> Result<Landscape, String> {
+        let number_of_hyperparameters = H::number_of_hyperparameters();
+        let mut reader = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut rows = Vec::new();
+
+        for record in reader.records() {
+            let record = record.map_err(|e| format!("{}: {}", path.display(), e))?;
+            if record.len() <= number_of_hyperparameters {
+                return Err(format!("{}: rows need coordinates and {} hyperparameters", path.display(), number_of_hyperparameters));
+            }
+            let number_of_axes = record.len() - number_of_hyperparameters;
+
+            let coords = record.iter().take(number_of_axes)
+                .map(|c| c.trim().parse::<usize>().map_err(|e| format!("{}: coordinate \"{}\": {}", path.display(), c, e)))
+                .collect::<Result<Vec<usize>, String>>()?;
+            let values = record.iter().skip(number_of_axes)
+                .map(|v| v.trim().parse::<f64>().map_err(|e| format!("{}: value \"{}\": {}", path.display(), v, e)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            rows.push((coords, values));
+        }
+
+        Ok(Landscape::Table(LookupTable::new::<H>(rows).map_err(|e| format!("{}: {}", path.display(), e))?))
+    }
+}
+
+impl Named for DataEnvironment {
+    fn name(&self) -> String {
+        "Data-defined environment".to_string()
+    }
+}
+
+impl Parametrized for DataEnvironment {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("source".to_string(), str_param(&self.source));
+        match &self.landscape {
+            Landscape::Axes(axes) => {
+                hm.insert("axes".to_string(), Value::Array(axes.iter().map(|a| a.parameters()).collect()));
+            },
+            Landscape::Table(table) => {
+                hm.insert("table shape".to_string(), table.shape.clone().into());
+            }
+        }
+        Value::Object(hm)
+    }
+}
+
+impl<H: Hyperparameter> Environment<H> for DataEnvironment {
+    fn map_hyperparameters(&self, coordinates: &[(usize, usize)]) -> H {
+        match &self.landscape {
+            Landscape::Axes(axes) => {
+                let values: Vec<f64> = axes.iter().zip(coordinates.iter())
+                    .map(|(axis, &(index, size))| axis.value(index, size))
+                    .collect();
+                H::from_values(&values)
+            },
+            // every cell of the table was checked to be defined when it was read
+            Landscape::Table(table) => H::from_values(table.get(coordinates).expect("cell missing from a checked table"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::problems::{ContinuousHyperparameters, DiscreteHyperparameters};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn axis_values() {
+        let linear = AxisMapping::Linear { min: 0.1, max: 0.5 };
+        assert!(close(linear.value(0, 5), 0.1));
+        assert!(close(linear.value(2, 5), 0.3));
+        assert!(close(linear.value(4, 5), 0.5));
+        // a single cell sits in the middle of the range
+        assert!(close(linear.value(0, 1), 0.3));
+
+        let logarithmic = AxisMapping::Logarithmic { min: 0.001, max: 0.1 };
+        assert!(close(logarithmic.value(0, 3), 0.001));
+        assert!(close(logarithmic.value(1, 3), 0.01));
+        assert!(close(logarithmic.value(2, 3), 0.1));
+
+        let geometric = AxisMapping::Geometric { start: 0.5, ratio: 0.5 };
+        assert!(close(geometric.value(0, 10), 0.5));
+        assert!(close(geometric.value(3, 10), 0.0625));
+
+        let values = AxisMapping::Values(vec![0.1, 0.2]);
+        assert_eq!(values.value(1, 2), 0.2);
+    }
+
+    #[test]
+    fn values_are_stretched_over_the_axis() {
+        // two values over four cells, then four values over two cells
+        assert_eq!((0..4).map(|i| scaled_index(i, 4, 2)).collect::<Vec<_>>(), vec![0, 0, 1, 1]);
+        assert_eq!((0..2).map(|i| scaled_index(i, 2, 4)).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!((0..3).map(|i| scaled_index(i, 3, 3)).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn invalid_axes_are_rejected() {
+        let axes = |axes: Value| DataEnvironment::from_json::<ContinuousHyperparameters>(&json!({"axes": axes}));
+        let size = json!({"type": "linear", "min": 0.1, "max": 1.0});
+
+        assert!(axes(json!([{"type": "linear", "min": 0.0, "max": 0.5}, size])).is_ok());
+        assert!(axes(json!([{"type": "linear", "min": 0.0, "max": 0.5}])).is_err());
+        assert!(axes(json!([{"type": "spiral"}, size])).is_err());
+        assert!(axes(json!([{"type": "linear", "min": 0.0}, size])).is_err());
+        assert!(axes(json!([{"type": "logarithmic", "min": 0.0, "max": 0.5}, size])).is_err());
+        assert!(axes(json!([{"type": "values", "values": []}, size])).is_err());
+        assert!(DataEnvironment::from_json::<ContinuousHyperparameters>(&json!({"grid": []})).is_err());
+
+        // a mutation chance of 1 would never stop mutating
+        assert!(axes(json!([{"type": "linear", "min": 0.0, "max": 1.0}, size])).is_err());
+        assert!(axes(json!([{"type": "values", "values": [0.1, -0.1]}, size])).is_err());
+        assert!(axes(json!([{"type": "geometric", "start": 0.1, "ratio": 1.5}, size])).is_err());
+        assert!(axes(json!([{"type": "geometric", "start": 0.1, "ratio": 0.5}, size])).is_ok());
+
+        // mutation sizes only need to be positive
+        let chance = json!({"type": "linear", "min": 0.0, "max": 0.5});
+        assert!(axes(json!([chance, {"type": "geometric", "start": 1.0, "ratio": 2.0}])).is_ok());
+        assert!(axes(json!([chance, {"type": "linear", "min": 0.0, "max": 1.0}])).is_err());
+    }
+
+    #[test]
+    fn tables_need_every_cell_once() {
+        let table = |rows: Value| DataEnvironment::from_json::<DiscreteHyperparameters>(&json!({"table": rows}));
+        let row = |coordinates: Value, chance: f64| json!({"coordinates": coordinates, "hyperparameters": [chance]});
+
+        let landscape = table(json!([row(json!([0, 0]), 0.1), row(json!([0, 1]), 0.2),
+                                     row(json!([1, 0]), 0.3), row(json!([1, 1]), 0.4)])).unwrap();
+        let environment = DataEnvironment { landscape, source: String::new() };
+        let hyperparameters: DiscreteHyperparameters = environment.map_hyperparameters(&[(1, 2), (0, 2)]);
+        assert_eq!(hyperparameters.mutation_chance, 0.3);
+        // a grid larger than the table stretches it
+        let hyperparameters: DiscreteHyperparameters = environment.map_hyperparameters(&[(3, 4), (1, 4)]);
+        assert_eq!(hyperparameters.mutation_chance, 0.3);
+
+        assert!(table(json!([row(json!([0]), 0.1), row(json!([0]), 0.2)])).is_err());
+        assert!(table(json!([row(json!([0]), 0.1), row(json!([2]), 0.2)])).is_err());
+        assert!(table(json!([row(json!([0, 0]), 0.1), row(json!([1]), 0.2)])).is_err());
+        assert!(table(json!([row(json!([0]), 1.0)])).is_err());
+        assert!(table(json!([{"coordinates": [0], "hyperparameters": [0.1, 0.2]}])).is_err());
+        assert!(table(json!([])).is_err());
+    }
+
+    #[test]
+    fn read_csv_tables() {
+        let path = std::env::temp_dir().join(format!("genetic_testbed_landscape_{}.csv", std::process::id()));
+        std::fs::write(&path, "x,chance,size\n0, 0.1, 1.0\n1, 0.2, 2.0\n").unwrap();
+        let environment = DataEnvironment::from_file::<ContinuousHyperparameters>(&path);
+        std::fs::write(&path, "x,chance,size\n0,0.1,1.0\n1,0.2,0.0\n").unwrap();
+        let invalid = DataEnvironment::from_file::<ContinuousHyperparameters>(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let environment = environment.unwrap();
+        let hyperparameters: ContinuousHyperparameters = environment.map_hyperparameters(&[(1, 2)]);
+        assert_eq!(hyperparameters.mutation_chance, 0.2);
+        assert_eq!(hyperparameters.mutation_size, 2.0);
+        assert!(invalid.is_err());
+    }
+}
@@ -1,12 +1,15 @@
 pub mod rastrigin;
 pub mod travelling_salesman;
 pub mod onemax;
+pub mod landscape;
+pub mod schedule;
+pub mod dynamic;
 
 use crate::common::Named;
 use crate::common::Parametrized;
-use std::ops::Div;
-use crate::organism::Organism;
 use serde_json::{Value, Map};
+use rand::{thread_rng, Rng};
+use rand_distr::StandardNormal;
 
 pub trait ProblemInstanceGenerator<P>: Named + Parametrized {
     fn generate_problem(&self) -> P;
@@ -14,13 +17,35 @@ pub trait ProblemInstanceGenerator<P>: Named + Parametrized {
 
 pub trait Hyperparameter {
     fn number_of_hyperparameters() -> usize;
+    /// Builds the hyperparameters from their values, in the order of `to_values`
+    fn from_values(values: &[f64]) -> Self;
+    fn to_values(&self) -> Vec<f64>;
+    /// Whether the value at `index` of `to_values` is a probability, the others are sizes
+    fn is_probability(index: usize) -> bool;
 }
 
-pub trait Environment<H: Hyperparameter>: Named {
+/// Checks the value at `index` of hyperparameters of type `H`: probabilities must be in [0, 1), the
+/// mutators drawing until a draw exceeds them, and sizes must be positive
+pub fn check_hyperparameter<H: Hyperparameter>(index: usize, value: f64) -> Result<(), String> {
+    if H::is_probability(index) {
+        if !(0.0..1.0).contains(&value) {
+            return Err(format!("hyperparameter {} should be a probability in [0, 1), not {}", index, value));
+        }
+    } else if !(value > 0.0 && value.is_finite()) {
+        return Err(format!("hyperparameter {} should be a positive size, not {}", index, value));
+    }
+    Ok(())
+}
+
+pub trait Environment<H: Hyperparameter>: Named + Parametrized {
     fn number_of_hyperparameters(&self) -> usize {
-        return H::number_of_hyperparameters()
+        H::number_of_hyperparameters()
+    }
+    fn map_hyperparameters(&self, coordinates: &[(usize, usize)]) -> H;
+    /// Hyperparameters of a cell at the given iteration of the run, static by default
+    fn map_hyperparameters_at(&self, coordinates: &[(usize, usize)], _iteration: u64) -> H {
+        self.map_hyperparameters(coordinates)
     }
-    fn map_hyperparameters(&self, coordinates: &Vec<(usize, usize)>) -> H;
 }
 
 impl Named for SpatialMapper {
@@ -29,6 +54,8 @@ impl Named for SpatialMapper {
     }
 }
 
+impl Parametrized for SpatialMapper {}
+
 #[derive(Copy, Clone)]
 pub struct DiscreteHyperparameters {
     pub mutation_chance: f64
@@ -42,13 +69,142 @@ pub struct ContinuousHyperparameters {
 
 impl Hyperparameter for DiscreteHyperparameters {
     fn number_of_hyperparameters() -> usize {
-        return 1;
+        1
+    }
+
+    fn from_values(values: &[f64]) -> Self {
+        DiscreteHyperparameters {
+            mutation_chance: values[0]
+        }
+    }
+
+    fn to_values(&self) -> Vec<f64> {
+        vec![self.mutation_chance]
+    }
+
+    fn is_probability(_index: usize) -> bool {
+        true
     }
 }
 
 impl Hyperparameter for ContinuousHyperparameters {
     fn number_of_hyperparameters() -> usize {
-        return 2;
+        2
+    }
+
+    fn from_values(values: &[f64]) -> Self {
+        ContinuousHyperparameters {
+            mutation_chance: values[0],
+            mutation_size: values[1]
+        }
+    }
+
+    fn to_values(&self) -> Vec<f64> {
+        vec![self.mutation_chance, self.mutation_size]
+    }
+
+    /// The mutation chance, the mutation size is a size
+    fn is_probability(index: usize) -> bool {
+        index == 0
+    }
+}
+
+//
+// Self-adaptation of hyperparameters
+//
+
+/// Settings shared by all self-adaptive encodings
+#[derive(Copy, Clone, Debug)]
+pub struct AdaptationSettings {
+    pub prior_a: i64,
+    pub prior_b: i64,
+    /// Learning rate of log-normal self-adaptation
+    pub learning_rate: f64
+}
+
+/// Hyperparameters that can be carried by organisms and evolve along with them
+pub trait SelfAdaptive: Hyperparameter + Sized {
+    /// Heritable representation of the hyperparameters
+    type Encoding: Clone;
+
+    fn initial_encoding(constant: &Self, settings: &AdaptationSettings) -> Self::Encoding;
+    fn mutate_encoding(encoding: &mut Self::Encoding, settings: &AdaptationSettings);
+    fn decode(encoding: &Self::Encoding) -> Self;
+}
+
+/// Probability a/(a+b) evolved by a random walk on a and b
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BetaWalk {
+    pub a: i64,
+    pub b: i64
+}
+
+impl BetaWalk {
+    pub fn probability(&self) -> f64 {
+        let af = self.a as f64;
+        af/(af + self.b as f64)
+    }
+
+    pub fn mutate(&mut self) {
+        let mut rng = thread_rng();
+
+        while rng.gen::<f64>() < self.probability() {
+            let p_ref = if rng.gen_bool(0.5) {
+                &mut self.a
+            }
+            else {
+                &mut self.b
+            };
+
+            if rng.gen_bool(0.5) {
+                *p_ref += 1;
+            }
+            else {
+                *p_ref -= 1;
+            }
+
+            *p_ref = (*p_ref).max(1);
+        }
+    }
+}
+
+impl SelfAdaptive for DiscreteHyperparameters {
+    type Encoding = BetaWalk;
+
+    fn initial_encoding(_constant: &Self, settings: &AdaptationSettings) -> BetaWalk {
+        BetaWalk { a: settings.prior_a, b: settings.prior_b }
+    }
+
+    fn mutate_encoding(encoding: &mut BetaWalk, _settings: &AdaptationSettings) {
+        encoding.mutate();
+    }
+
+    fn decode(encoding: &BetaWalk) -> Self {
+        DiscreteHyperparameters {
+            mutation_chance: encoding.probability()
+        }
+    }
+}
+
+impl SelfAdaptive for ContinuousHyperparameters {
+    /// Mutation chance walk and mutation size
+    type Encoding = (BetaWalk, f64);
+
+    fn initial_encoding(constant: &Self, settings: &AdaptationSettings) -> (BetaWalk, f64) {
+        (BetaWalk { a: settings.prior_a, b: settings.prior_b }, constant.mutation_size)
+    }
+
+    fn mutate_encoding(encoding: &mut (BetaWalk, f64), settings: &AdaptationSettings) {
+        encoding.0.mutate();
+        let n: f64 = thread_rng().sample(StandardNormal);
+        encoding.1 *= (settings.learning_rate * n).exp();
+    }
+
+    fn decode(encoding: &(BetaWalk, f64)) -> Self {
+        ContinuousHyperparameters {
+            mutation_chance: encoding.0.probability(),
+            mutation_size: encoding.1
+        }
     }
 }
 
@@ -72,17 +228,17 @@ impl Parametrized for ContinuousSpatialMapper {
         let mut hm = Map::new();
         hm.insert("mean mutation size".to_string(), self.mean_mutation_size.into());
 
-        return Value::Object(hm);
+        Value::Object(hm)
     }
 }
 
 impl Environment<ContinuousHyperparameters> for ContinuousSpatialMapper {
-    fn map_hyperparameters(&self, coordinates: &Vec<(usize, usize)>) -> ContinuousHyperparameters {
-        let (v1, m1) = coordinates.get(0).unwrap();
+    fn map_hyperparameters(&self, coordinates: &[(usize, usize)]) -> ContinuousHyperparameters {
+        let (v1, m1) = coordinates.first().unwrap();
         let (v2, m2) = coordinates.get(1).unwrap();
         let prob1: f64 = (v1+1) as f64/((m1+2) as f64);
         let prob2: f64 = ((v2+1) as f64/((m2+2) as f64))*self.mean_mutation_size/2.0;
-        return ContinuousHyperparameters {
+        ContinuousHyperparameters {
             mutation_chance: prob1,
             mutation_size: prob2
         }
@@ -99,13 +255,51 @@ pub struct SpatialMapper {
 }
 
 impl Environment<DiscreteHyperparameters> for SpatialMapper {
-    fn map_hyperparameters(&self, coordinates: &Vec<(usize, usize)>) -> DiscreteHyperparameters {
-        let (val,max) = coordinates.get(0).unwrap();
+    fn map_hyperparameters(&self, coordinates: &[(usize, usize)]) -> DiscreteHyperparameters {
+        let (val,max) = coordinates.first().unwrap();
         let prob: f64 = (val+1) as f64/((max+2) as f64);
         //println!("prob: {}", &prob);
-        return DiscreteHyperparameters{
+        DiscreteHyperparameters{
             mutation_chance: prob
-        };
+        }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: AdaptationSettings = AdaptationSettings { prior_a: 1, prior_b: 3, learning_rate: 0.2 };
+
+    #[test]
+    fn initial_encoding_keeps_the_constant_mutation_size() {
+        let constant = ContinuousHyperparameters { mutation_chance: 0.5, mutation_size: 0.7 };
+        let decoded = ContinuousHyperparameters::decode(&ContinuousHyperparameters::initial_encoding(&constant, &SETTINGS));
+        assert_eq!(decoded.mutation_size, 0.7);
+        // the mutation chance starts from the prior
+        assert_eq!(decoded.mutation_chance, 0.25);
+
+        let constant = DiscreteHyperparameters { mutation_chance: 0.5 };
+        let decoded = DiscreteHyperparameters::decode(&DiscreteHyperparameters::initial_encoding(&constant, &SETTINGS));
+        assert_eq!(decoded.mutation_chance, 0.25);
+    }
+
+    #[test]
+    fn mutation_size_follows_a_log_normal_update() {
+        let constant = ContinuousHyperparameters { mutation_chance: 0.5, mutation_size: 2.0 };
+        let samples = 20000;
+        let steps: Vec<f64> = (0..samples).map(|_| {
+            let mut encoding = ContinuousHyperparameters::initial_encoding(&constant, &SETTINGS);
+            ContinuousHyperparameters::mutate_encoding(&mut encoding, &SETTINGS);
+            assert!(encoding.1 > 0.0);
+            (encoding.1 / 2.0).ln()
+        }).collect();
+
+        // the log of the size moves by a centred Gaussian step of standard deviation the learning rate
+        let mean = steps.iter().sum::<f64>() / samples as f64;
+        let deviation = (steps.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (samples - 1) as f64).sqrt();
+        assert!(mean.abs() < 5.0 * SETTINGS.learning_rate / (samples as f64).sqrt());
+        assert!((deviation - SETTINGS.learning_rate).abs() < 0.05 * SETTINGS.learning_rate);
+    }
+}
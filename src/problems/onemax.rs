@@ -1,13 +1,17 @@
-use crate::organism::{Metric, OrganismGenerator, Organism};
+use crate::organism::{Metric, Diversity, OrganismGenerator, Validate};
+use std::collections::HashSet;
+use std::cell::RefCell;
+use serde::{Serialize, Deserialize};
 use crate::common::{Named, Parametrized};
 use serde_json::{Value, Map};
 use crate::problems::{ProblemInstanceGenerator, DiscreteHyperparameters};
 use crate::scoring::Scorer;
+use crate::problems::dynamic::Dynamics;
 use crate::algorithm::mutation::Mutator;
 use rand::{thread_rng, Rng};
 use crate::features::FeatureMapper;
 
-#[derive(Clone,Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[derive(Clone,Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct OneMaxValue {
     values: Vec<u8>
 }
@@ -23,7 +27,41 @@ impl Metric for OneMaxValue {
             acc += xor.count_ones();
         }
 
-        return acc as f64;
+        acc as f64
+    }
+}
+
+impl Diversity for OneMaxValue {
+    fn distinct_genotypes(population: &[&Self]) -> usize {
+        population.iter().collect::<HashSet<_>>().len()
+    }
+
+    fn locus_entropy(population: &[&Self]) -> Option<f64> {
+        let first = population.first()?;
+        let n = population.len() as f64;
+        let number_of_bits = first.values.len() * 8;
+
+        let mut total = 0.0;
+        for bit in 0..number_of_bits {
+            let ones = population.iter()
+                .filter(|g| g.values[bit / 8] & (1 << (bit % 8)) != 0)
+                .count() as f64;
+            let p = ones / n;
+            if p > 0.0 && p < 1.0 {
+                total -= p * p.log2() + (1.0 - p) * (1.0 - p).log2();
+            }
+        }
+
+        Some(total / number_of_bits as f64)
+    }
+}
+
+impl Validate<OneMax> for OneMaxValue {
+    fn validate(&self, problem: &OneMax) -> Result<(), String> {
+        if self.values.len() != problem.size {
+            return Err(format!("{} octets in a problem of {}", self.values.len(), problem.size));
+        }
+        Ok(())
     }
 }
 
@@ -43,7 +81,7 @@ impl Parametrized for OneMax {
     fn parameters(&self) -> Value {
         let mut hm = Map::new();
         hm.insert("problem size (max score)".to_string(), self.size.into());
-        return Value::Object(hm);
+        Value::Object(hm)
     }
 }
 
@@ -54,19 +92,99 @@ impl ProblemInstanceGenerator<OneMax> for OneMax {
 #[derive(Copy, Clone)]
 pub struct OneMaxScorer {}
 
+impl Named for OneMaxScorer {
+    fn name(&self) -> String {
+        "Proportion of ones".to_string()
+    }
+}
+
+impl Parametrized for OneMaxScorer {}
+
 impl Scorer<OneMaxValue, OneMax> for OneMaxScorer {
-    fn score(&self, genome: &OneMaxValue, problem: &OneMax) -> f64 {
+    fn score(&self, genome: &OneMaxValue, _problem: &OneMax) -> f64 {
         let mut acc = 0;
         for &b in &genome.values {
             acc += b.count_ones();
         }
-        return (acc as f64) / ((genome.values.len()*8) as f64);
+        (acc as f64) / ((genome.values.len()*8) as f64)
+    }
+}
+
+/// OneMax with a target bit pattern shifting during a run: the score is the proportion of bits
+/// equal to those of the target, which starts as all ones and has `bits_per_change` random bits
+/// flipped at every change.
+pub struct ShiftingTarget {
+    pub bits_per_change: usize,
+    target: RefCell<Vec<u8>>
+}
+
+impl ShiftingTarget {
+    pub fn new(bits_per_change: usize) -> Self {
+        ShiftingTarget {
+            bits_per_change,
+            target: RefCell::new(Vec::new())
+        }
+    }
+}
+
+impl Named for ShiftingTarget {
+    fn name(&self) -> String {
+        "Proportion of bits matching a shifting target".to_string()
+    }
+}
+
+impl Parametrized for ShiftingTarget {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("bits flipped per change".to_string(), self.bits_per_change.into());
+        Value::Object(hm)
+    }
+}
+
+impl Dynamics<OneMaxValue, OneMax> for ShiftingTarget {
+    fn score(&self, genome: &OneMaxValue, _problem: &OneMax) -> f64 {
+        let target = self.target.borrow();
+        let mut acc = 0;
+        for (&b, &t) in genome.values.iter().zip(target.iter()) {
+            acc += (!(b ^ t)).count_ones();
+        }
+        (acc as f64) / ((genome.values.len()*8) as f64)
+    }
+
+    fn reset(&self, problem: &OneMax) {
+        *self.target.borrow_mut() = vec![u8::MAX; problem.size];
+    }
+
+    fn change(&self, _problem: &OneMax) -> bool {
+        let mut target = self.target.borrow_mut();
+        if target.is_empty() || self.bits_per_change == 0 {
+            return false;
+        }
+        let mut rng = thread_rng();
+        for _i in 0..self.bits_per_change {
+            let i = rng.gen_range(0, target.len());
+            target[i] ^= 1 << rng.gen_range(0, 8);
+        }
+        true
+    }
+
+    /// The target itself matches every bit
+    fn optimum(&self, _problem: &OneMax) -> Option<f64> {
+        Some(1.0)
     }
 }
 
 #[derive(Copy, Clone)]
 pub struct OneMaxMutator {}
 
+impl Named for OneMaxMutator {
+    fn name(&self) -> String {
+        "Random bit flips".to_string()
+    }
+}
+
+impl Parametrized for OneMaxMutator {}
+
 impl Mutator<OneMaxValue, DiscreteHyperparameters> for OneMaxMutator {
     fn mutate(&self, genome: &mut OneMaxValue, hyperparameters: &DiscreteHyperparameters) -> bool {
 
@@ -100,21 +218,21 @@ impl Parametrized for OneMaxMapper {
     fn parameters(&self) -> Value {
         let mut hm = Map::new();
         hm.insert("number of bits mapped".to_string(), self.number_of_octets.into());
-        return Value::Object(hm);
+        Value::Object(hm)
     }
 }
 
 impl FeatureMapper<OneMaxValue, Vec<u8>, OneMax> for OneMaxMapper {
-    fn number_of_possible_features(&self, problem: &OneMax) -> usize {
+    fn number_of_possible_features(&self, _problem: &OneMax) -> usize {
         2_usize.pow((self.number_of_octets * 8) as u32)
     }
 
     fn project(&self, genome: &OneMaxValue) -> Vec<u8> {
-        return genome.values[..self.number_of_octets].to_vec();
+        genome.values[..self.number_of_octets].to_vec()
     }
 
     fn default_features(&self) -> Vec<u8> {
-        return Vec::new();
+        Vec::new()
     }
 }
 
@@ -137,5 +255,52 @@ impl OrganismGenerator<OneMaxValue, OneMax> for OneMaxGenerator {
     }
 }
 
+#[derive(Copy, Clone)]
+pub struct OneMaxRandomGenerator {}
+
+impl Named for OneMaxRandomGenerator {
+    fn name(&self) -> String {
+        "Uniform random bits generator".to_string()
+    }
+}
+
+impl Parametrized for OneMaxRandomGenerator {}
+
+impl OrganismGenerator<OneMaxValue, OneMax> for OneMaxRandomGenerator {
+    fn generate(&self, problem: &OneMax) -> OneMaxValue {
+        let mut rng = thread_rng();
+        OneMaxValue {
+            values: (0..problem.size).map(|_| rng.gen()).collect()
+        }
+    }
+}
+
+
+
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn genome(values: &[u8]) -> OneMaxValue {
+        OneMaxValue { values: values.to_vec() }
+    }
 
+    #[test]
+    fn locus_entropy_of_bit_strings() {
+        // only the lowest bit differs, in half of the population: 1 bit of entropy over 8 loci
+        let a = genome(&[0b0000_0001]);
+        let b = genome(&[0b0000_0000]);
+        assert_eq!(OneMaxValue::locus_entropy(&[&a, &b]), Some(1.0 / 8.0));
+        assert_eq!(OneMaxValue::locus_entropy(&[&a, &a]), Some(0.0));
+        assert_eq!(OneMaxValue::locus_entropy(&[]), None);
+    }
+
+    #[test]
+    fn distinct_bit_strings() {
+        let a = genome(&[1, 2]);
+        let b = genome(&[1, 3]);
+        assert_eq!(OneMaxValue::distinct_genotypes(&[&a, &b, &a]), 2);
+        assert_eq!(OneMaxValue::centroid_dispersion(&[&a, &b]), None);
+    }
+}
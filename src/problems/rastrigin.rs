@@ -10,7 +10,13 @@ use rand::{thread_rng, Rng};
 use rand_distr::Normal;
 use crate::features::FeatureMapper;
 use num::pow;
-use crate::organism::{OrganismGenerator, Metric};
+use serde::{Serialize, Deserialize};
+use crate::organism::{OrganismGenerator, Organism, Metric, Diversity, Validate};
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::cell::RefCell;
+use rand_distr::StandardNormal;
+use crate::problems::dynamic::Dynamics;
 
 pub fn rastrigin(a: f64, x: &[f64]) -> f64 {
     let n: f64 = x.len() as f64;
@@ -21,11 +27,11 @@ pub fn rastrigin(a: f64, x: &[f64]) -> f64 {
         sum += v * v - a * (2.0 * PI * *v).cos();
     }
 
-    return a * n + sum;
+    a * n + sum
 }
 
 pub fn custom_rastrigin(a: f64, x: &[f64]) -> f64 {
-    return (100.0 - rastrigin(a, x)).max(0.0);
+    (100.0 - rastrigin(a, x)).max(0.0)
 }
 
 pub fn regularized_rastrigin(a: f64, b: f64, x: &[f64]) -> f64 {
@@ -44,10 +50,10 @@ pub fn regularized_rastrigin(a: f64, b: f64, x: &[f64]) -> f64 {
 
     let reg = (reg_sum/b).exp();
 
-    return sum*reg/n;
+    sum*reg/n
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RastriginValue {
     pub value: Vec<f64>
 }
@@ -62,6 +68,39 @@ impl Metric for RastriginValue {
     }
 }
 
+impl Diversity for RastriginValue {
+    fn distinct_genotypes(population: &[&Self]) -> usize {
+        population.iter()
+            .map(|g| g.value.iter().map(|x| x.to_bits()).collect::<Vec<u64>>())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    fn centroid_dispersion(population: &[&Self]) -> Option<f64> {
+        let first = population.first()?;
+        let n = population.len() as f64;
+
+        let mut centroid = vec![0.0; first.value.len()];
+        for genome in population {
+            for (c, x) in centroid.iter_mut().zip(genome.value.iter()) {
+                *c += x / n;
+            }
+        }
+        let centroid = RastriginValue { value: centroid };
+
+        Some(population.iter().map(|g| g.distance_to(&centroid)).sum::<f64>() / n)
+    }
+}
+
+impl Validate<Rastrigin> for RastriginValue {
+    fn validate(&self, problem: &Rastrigin) -> Result<(), String> {
+        if self.value.len() != problem.nb_dimensions {
+            return Err(format!("point of {} dimensions in a space of {}", self.value.len(), problem.nb_dimensions));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Rastrigin {
     pub a: f64,
@@ -83,7 +122,7 @@ impl Parametrized for Rastrigin {
         hm.insert("b".to_string(), self.b.into());
         hm.insert("absolute boundary value".to_string(), self.max_abs_val.into());
         hm.insert("number of dimensions".to_string(), self.nb_dimensions.into());
-        return Value::Object(hm);
+        Value::Object(hm)
     }
 }
 
@@ -96,15 +135,129 @@ impl ProblemInstanceGenerator<Rastrigin> for Rastrigin {
 #[derive(Copy, Clone)]
 pub struct RegRastriginScorer {}
 
+impl Named for RegRastriginScorer {
+    fn name(&self) -> String {
+        "Regularized Rastrigin".to_string()
+    }
+}
+
+impl Parametrized for RegRastriginScorer {}
+
 impl Scorer<RastriginValue, Rastrigin> for RegRastriginScorer {
     fn score(&self, genome: &RastriginValue, problem: &Rastrigin) -> f64 {
-        return regularized_rastrigin(problem.a, problem.b, genome.value.as_slice());
+        regularized_rastrigin(problem.a, problem.b, genome.value.as_slice())
+    }
+}
+
+/// Cone of the moving peaks landscape
+#[derive(Clone, Debug)]
+struct Peak {
+    center: Vec<f64>,
+    height: f64,
+    width: f64
+}
+
+/// Moving peaks benchmark in the domain of the Rastrigin instance: the score of a genome is the
+/// highest of height / (1 + width * squared distance to the center) over the peaks. At every change
+/// the peaks move by `shift_severity` in a random direction and their heights and widths drift.
+pub struct MovingPeaks {
+    pub number_of_peaks: usize,
+    pub shift_severity: f64,
+    pub height_severity: f64,
+    pub width_severity: f64,
+    peaks: RefCell<Vec<Peak>>
+}
+
+impl MovingPeaks {
+    const HEIGHTS: (f64, f64) = (30.0, 70.0);
+    const WIDTHS: (f64, f64) = (1.0, 12.0);
+
+    pub fn new(number_of_peaks: usize, shift_severity: f64, height_severity: f64, width_severity: f64) -> Self {
+        MovingPeaks {
+            number_of_peaks,
+            shift_severity,
+            height_severity,
+            width_severity,
+            peaks: RefCell::new(Vec::new())
+        }
+    }
+}
+
+impl Named for MovingPeaks {
+    fn name(&self) -> String {
+        "Moving peaks".to_string()
+    }
+}
+
+impl Parametrized for MovingPeaks {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("number of peaks".to_string(), self.number_of_peaks.into());
+        hm.insert("shift severity".to_string(), self.shift_severity.into());
+        hm.insert("height severity".to_string(), self.height_severity.into());
+        hm.insert("width severity".to_string(), self.width_severity.into());
+        Value::Object(hm)
+    }
+}
+
+impl Dynamics<RastriginValue, Rastrigin> for MovingPeaks {
+    fn score(&self, genome: &RastriginValue, _problem: &Rastrigin) -> f64 {
+        self.peaks.borrow().iter()
+            .map(|peak| {
+                let distance: f64 = genome.value.iter().zip(peak.center.iter()).map(|(x, c)| (x - c).powi(2)).sum();
+                peak.height / (1.0 + peak.width * distance)
+            })
+            .fold(0.0, f64::max)
+    }
+
+    fn reset(&self, problem: &Rastrigin) {
+        let mut rng = thread_rng();
+        let bound = problem.max_abs_val;
+        *self.peaks.borrow_mut() = (0..self.number_of_peaks).map(|_| Peak {
+            center: (0..problem.nb_dimensions).map(|_| rng.gen_range(-bound, bound)).collect(),
+            height: rng.gen_range(MovingPeaks::HEIGHTS.0, MovingPeaks::HEIGHTS.1),
+            width: rng.gen_range(MovingPeaks::WIDTHS.0, MovingPeaks::WIDTHS.1)
+        }).collect();
+    }
+
+    fn change(&self, problem: &Rastrigin) -> bool {
+        if self.peaks.borrow().is_empty() {
+            return false;
+        }
+        let mut rng = thread_rng();
+        let bound = problem.max_abs_val;
+        for peak in self.peaks.borrow_mut().iter_mut() {
+            let direction: Vec<f64> = peak.center.iter().map(|_| rng.sample::<f64,_>(StandardNormal)).collect();
+            let norm = direction.iter().map(|d| d * d).sum::<f64>().sqrt().max(f64::MIN_POSITIVE);
+            for (c, d) in peak.center.iter_mut().zip(direction) {
+                *c = (*c + self.shift_severity * d / norm).clamp(-bound, bound);
+            }
+
+            peak.height = (peak.height + self.height_severity * rng.sample::<f64,_>(StandardNormal))
+                .clamp(MovingPeaks::HEIGHTS.0, MovingPeaks::HEIGHTS.1);
+            peak.width = (peak.width + self.width_severity * rng.sample::<f64,_>(StandardNormal))
+                .clamp(MovingPeaks::WIDTHS.0, MovingPeaks::WIDTHS.1);
+        }
+        true
+    }
+
+    /// The highest peak, every cone reaching its height at its center
+    fn optimum(&self, _problem: &Rastrigin) -> Option<f64> {
+        self.peaks.borrow().iter().map(|peak| peak.height).fold(None, |best, h| Some(best.map_or(h, |b: f64| b.max(h))))
     }
 }
 
 #[derive(Copy, Clone)]
 pub struct RastriginMutator {}
 
+impl Named for RastriginMutator {
+    fn name(&self) -> String {
+        "Gaussian coordinate mutation".to_string()
+    }
+}
+
+impl Parametrized for RastriginMutator {}
+
 impl Mutator<RastriginValue, ContinuousHyperparameters> for RastriginMutator {
     fn mutate(&self, genome: &mut RastriginValue, hyperparameters: &ContinuousHyperparameters) -> bool {
         let mut rng = thread_rng();
@@ -127,7 +280,7 @@ pub struct RastriginMapper {
     pub max_abs_val: f64
 }
 
-#[derive(Clone,Eq,Hash,Ord, PartialOrd, PartialEq)]
+#[derive(Clone,Eq,Hash,Ord, PartialOrd, PartialEq, Serialize)]
 pub struct RastriginFeature {
     pub bin_coords: Vec<isize>
 }
@@ -144,9 +297,10 @@ impl Parametrized for RastriginMapper {
 
 impl FeatureMapper<RastriginValue, RastriginFeature, Rastrigin> for RastriginMapper {
     fn number_of_possible_features(&self, _problem: &Rastrigin) -> usize {
-        return pow(self.resolution, self.number_of_dimensions);
+        pow(self.resolution, self.number_of_dimensions)
     }
 
+    #[allow(clippy::unnecessary_cast)]
     fn project(&self, genome: &RastriginValue) -> RastriginFeature {
         let mut features = Vec::with_capacity(self.number_of_dimensions);
         for x in &genome.value[..self.number_of_dimensions] {
@@ -154,7 +308,7 @@ impl FeatureMapper<RastriginValue, RastriginFeature, Rastrigin> for RastriginMap
             let int_x = (x_norm as f64 * self.resolution as f64 * 0.5) as isize;
             features.push(int_x);
         }
-        return RastriginFeature {
+        RastriginFeature {
             bin_coords: features
         }
     }
@@ -179,15 +333,96 @@ impl OrganismGenerator<RastriginValue, Rastrigin> for RastriginGenerator {
     fn generate(&self, problem: &Rastrigin) -> RastriginValue {
         let mut new_val = Vec::with_capacity(problem.nb_dimensions);
 
-        let mut rng = thread_rng();
-
-        for d in 0..problem.nb_dimensions {
+        for _d in 0..problem.nb_dimensions {
             //new_val.push(rng.gen_range(-problem.max_abs_val, problem.max_abs_val));
             new_val.push(problem.max_abs_val);
         }
 
-        return RastriginValue {
+        RastriginValue {
             value: new_val
-        };
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RastriginUniformGenerator {}
+
+impl Named for RastriginUniformGenerator {
+    fn name(&self) -> String {
+        "Rastrigin uniform generator".to_string()
+    }
+}
+
+impl Parametrized for RastriginUniformGenerator {}
+
+impl OrganismGenerator<RastriginValue, Rastrigin> for RastriginUniformGenerator {
+    fn generate(&self, problem: &Rastrigin) -> RastriginValue {
+        let mut rng = thread_rng();
+        RastriginValue {
+            value: (0..problem.nb_dimensions)
+                .map(|_| rng.gen_range(-problem.max_abs_val, problem.max_abs_val))
+                .collect()
+        }
+    }
+}
+
+/// Spreads the initial population with a Latin hypercube: along every dimension,
+/// each of the `size` strata of the domain holds exactly one organism
+#[derive(Copy, Clone)]
+pub struct RastriginLatinHypercubeGenerator {}
+
+impl Named for RastriginLatinHypercubeGenerator {
+    fn name(&self) -> String {
+        "Rastrigin Latin hypercube generator".to_string()
+    }
+}
+
+impl Parametrized for RastriginLatinHypercubeGenerator {}
+
+impl OrganismGenerator<RastriginValue, Rastrigin> for RastriginLatinHypercubeGenerator {
+    fn generate(&self, problem: &Rastrigin) -> RastriginValue {
+        RastriginUniformGenerator{}.generate(problem)
     }
-}
\ No newline at end of file
+
+    fn generate_population(&self, problem: &Rastrigin, size: usize) -> Vec<Organism<RastriginValue>> {
+        let mut rng = thread_rng();
+        let width = 2.0 * problem.max_abs_val / size as f64;
+
+        let mut values = vec![Vec::with_capacity(problem.nb_dimensions); size];
+        for _d in 0..problem.nb_dimensions {
+            let mut strata: Vec<usize> = (0..size).collect();
+            strata.shuffle(&mut rng);
+            for (value, &stratum) in values.iter_mut().zip(strata.iter()) {
+                value.push(-problem.max_abs_val + (stratum as f64 + rng.gen::<f64>()) * width);
+            }
+        }
+
+        values.into_iter().map(|value| Organism::new(RastriginValue { value })).collect()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(value: &[f64]) -> RastriginValue {
+        RastriginValue { value: value.to_vec() }
+    }
+
+    #[test]
+    fn centroid_dispersion_of_points() {
+        // the centroid of (0, 0), (2, 0), (1, 3) and (1, -3) is (1, 0)
+        let points = [point(&[0.0, 0.0]), point(&[2.0, 0.0]), point(&[1.0, 3.0]), point(&[1.0, -3.0])];
+        let population: Vec<&RastriginValue> = points.iter().collect();
+        assert_eq!(RastriginValue::centroid_dispersion(&population), Some(2.0));
+        assert_eq!(RastriginValue::centroid_dispersion(&population[..1]), Some(0.0));
+        assert_eq!(RastriginValue::centroid_dispersion(&[]), None);
+    }
+
+    #[test]
+    fn distinct_points() {
+        let a = point(&[0.5, 1.0]);
+        let b = point(&[0.5, 1.5]);
+        assert_eq!(RastriginValue::distinct_genotypes(&[&a, &b, &a.clone()]), 2);
+        assert_eq!(RastriginValue::locus_entropy(&[&a, &b]), None);
+    }
+}
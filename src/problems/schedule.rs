@@ -0,0 +1,247 @@
+use std::f64::consts::PI;
+use std::rc::Rc;
+use serde_json::{Value, Map};
+use crate::common::{Named, Parametrized, str_param};
+use crate::problems::{Environment, Hyperparameter};
+
+//
+// Environments changing during a run
+//
+
+const MAX_PROBABILITY: f64 = 0.99;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Schedule {
+    /// The landscape slides along every axis by `speed` cells per iteration, bouncing on the grid borders
+    Drift { speed: f64 },
+    /// The landscape turns around the grid center, one full turn every `period` iterations
+    Rotation { period: u64 },
+    /// Hyperparameters are scaled by `1 + amplitude * sin(2π t / period)`
+    Seasons { period: u64, amplitude: f64 },
+    /// The landscape is mirrored on every axis every `interval` iterations
+    Shifts { interval: u64 }
+}
+
+impl Schedule {
+    /// Parses `drift:<speed>`, `rotation:<period>`, `seasons:<period>:<amplitude>` or `shifts:<interval>`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+
+        let float = |i: usize| -> Result<f64, String> {
+            parts.get(i)
+                .ok_or_else(|| format!("schedule \"{}\" is missing a parameter", text))?
+                .parse::<f64>().map_err(|e| format!("schedule \"{}\": {}", text, e))
+        };
+        let integer = |i: usize| -> Result<u64, String> {
+            let v = parts.get(i)
+                .ok_or_else(|| format!("schedule \"{}\" is missing a parameter", text))?
+                .parse::<u64>().map_err(|e| format!("schedule \"{}\": {}", text, e))?;
+            if v == 0 {
+                return Err(format!("schedule \"{}\" needs a positive period", text));
+            }
+            Ok(v)
+        };
+
+        match parts[0] {
+            "drift" => Ok(Schedule::Drift { speed: float(1)? }),
+            "rotation" => Ok(Schedule::Rotation { period: integer(1)? }),
+            "seasons" => {
+                let amplitude = float(2)?;
+                if !(0.0..=1.0).contains(&amplitude) {
+                    return Err(format!("schedule \"{}\" needs an amplitude between 0 and 1", text));
+                }
+                Ok(Schedule::Seasons { period: integer(1)?, amplitude })
+            },
+            "shifts" => Ok(Schedule::Shifts { interval: integer(1)? }),
+            other => Err(format!("unknown schedule \"{}\"", other))
+        }
+    }
+
+    /// Cell of the static landscape seen by the given cell at iteration `t`
+    fn transform(&self, coordinates: &[(usize, usize)], t: u64) -> Vec<(usize, usize)> {
+        match *self {
+            Schedule::Drift { speed } => {
+                let offset = speed * t as f64;
+                coordinates.iter().map(|&(index, size)| {
+                    if size <= 1 {
+                        return (0, size);
+                    }
+                    let last = (size - 1) as f64;
+                    let p = (index as f64 + offset).rem_euclid(2.0 * last);
+                    let folded = if p > last { 2.0 * last - p } else { p };
+                    (folded.round() as usize, size)
+                }).collect()
+            },
+            Schedule::Rotation { period } => {
+                let angle = 2.0 * PI * (t % period) as f64 / period as f64;
+                let (sin, cos) = angle.sin_cos();
+
+                // coordinates relative to the grid center, in [-1, 1]
+                let centered: Vec<f64> = coordinates.iter().map(|&(index, size)| {
+                    if size <= 1 { 0.0 } else { 2.0 * index as f64 / (size - 1) as f64 - 1.0 }
+                }).collect();
+
+                let mut rotated = centered.clone();
+                if centered.len() >= 2 {
+                    rotated[0] = centered[0] * cos - centered[1] * sin;
+                    rotated[1] = centered[0] * sin + centered[1] * cos;
+                } else if let Some(x) = rotated.get_mut(0) {
+                    // a single axis can only be projected, the gradient flattens then reverses
+                    *x *= cos;
+                }
+
+                rotated.iter().zip(coordinates.iter()).map(|(&u, &(_, size))| {
+                    let last = size.max(1) - 1;
+                    let index = ((u.clamp(-1.0, 1.0) + 1.0) * 0.5 * last as f64).round() as usize;
+                    (index.min(last), size)
+                }).collect()
+            },
+            Schedule::Seasons { .. } => coordinates.to_vec(),
+            Schedule::Shifts { interval } => {
+                if (t / interval) % 2 == 1 {
+                    coordinates.iter().map(|&(index, size)| (size - 1 - index, size)).collect()
+                } else {
+                    coordinates.to_vec()
+                }
+            }
+        }
+    }
+
+    fn scale(&self, t: u64) -> f64 {
+        match *self {
+            Schedule::Seasons { period, amplitude } => {
+                1.0 + amplitude * (2.0 * PI * (t % period) as f64 / period as f64).sin()
+            },
+            _ => 1.0
+        }
+    }
+}
+
+impl Parametrized for Schedule {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        match *self {
+            Schedule::Drift { speed } => {
+                hm.insert("type".to_string(), str_param("drift"));
+                hm.insert("speed (cells per iteration)".to_string(), speed.into());
+            },
+            Schedule::Rotation { period } => {
+                hm.insert("type".to_string(), str_param("rotation"));
+                hm.insert("period".to_string(), period.into());
+            },
+            Schedule::Seasons { period, amplitude } => {
+                hm.insert("type".to_string(), str_param("seasons"));
+                hm.insert("period".to_string(), period.into());
+                hm.insert("amplitude".to_string(), amplitude.into());
+            },
+            Schedule::Shifts { interval } => {
+                hm.insert("type".to_string(), str_param("shifts"));
+                hm.insert("interval".to_string(), interval.into());
+            }
+        }
+        Value::Object(hm)
+    }
+}
+
+/// Makes any environment time-varying by applying a schedule to its landscape
+pub struct ScheduledEnvironment<H> {
+    pub environment: Rc<dyn Environment<H>>,
+    pub schedule: Schedule
+}
+
+impl<H> Named for ScheduledEnvironment<H> {
+    fn name(&self) -> String {
+        format!("Scheduled {}", self.environment.name())
+    }
+}
+
+impl<H> Parametrized for ScheduledEnvironment<H> {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("environment".to_string(), self.environment.name().into());
+        hm.insert("environment parameters".to_string(), self.environment.parameters());
+        hm.insert("schedule".to_string(), self.schedule.parameters());
+        Value::Object(hm)
+    }
+}
+
+impl<H: Hyperparameter> Environment<H> for ScheduledEnvironment<H> {
+    fn number_of_hyperparameters(&self) -> usize {
+        self.environment.number_of_hyperparameters()
+    }
+
+    fn map_hyperparameters(&self, coordinates: &[(usize, usize)]) -> H {
+        self.map_hyperparameters_at(coordinates, 0)
+    }
+
+    fn map_hyperparameters_at(&self, coordinates: &[(usize, usize)], iteration: u64) -> H {
+        let coords = self.schedule.transform(coordinates, iteration);
+        let hyper = self.environment.map_hyperparameters_at(&coords, iteration);
+
+        let scale = self.schedule.scale(iteration);
+        if scale == 1.0 {
+            return hyper;
+        }
+        // probabilities stay below 1, mutators keep mutating while a draw is below the mutation chance
+        let values: Vec<f64> = hyper.to_values().iter().enumerate().map(|(i, &v)| {
+            if H::is_probability(i) { (v * scale).clamp(0.0, MAX_PROBABILITY) } else { v * scale }
+        }).collect();
+        H::from_values(&values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::ContinuousHyperparameters;
+
+    /// Same hyperparameters in every cell
+    struct Uniform(ContinuousHyperparameters);
+
+    impl Named for Uniform {
+        fn name(&self) -> String {
+            String::from("uniform")
+        }
+    }
+
+    impl Parametrized for Uniform {}
+
+    impl Environment<ContinuousHyperparameters> for Uniform {
+        fn map_hyperparameters(&self, _coordinates: &[(usize, usize)]) -> ContinuousHyperparameters {
+            self.0
+        }
+    }
+
+    #[test]
+    fn parse_schedules() {
+        assert_eq!(Schedule::parse("drift:0.5"), Ok(Schedule::Drift { speed: 0.5 }));
+        assert_eq!(Schedule::parse("rotation:200"), Ok(Schedule::Rotation { period: 200 }));
+        assert_eq!(Schedule::parse("seasons:100:0.3"), Ok(Schedule::Seasons { period: 100, amplitude: 0.3 }));
+        assert_eq!(Schedule::parse("shifts:50"), Ok(Schedule::Shifts { interval: 50 }));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_schedules() {
+        assert!(Schedule::parse("tides:10").is_err());
+        assert!(Schedule::parse("drift").is_err());
+        assert!(Schedule::parse("drift:fast").is_err());
+        assert!(Schedule::parse("rotation:0").is_err());
+        assert!(Schedule::parse("rotation:-5").is_err());
+        assert!(Schedule::parse("seasons:100").is_err());
+        assert!(Schedule::parse("seasons:100:1.5").is_err());
+        assert!(Schedule::parse("shifts:0").is_err());
+    }
+
+    #[test]
+    fn seasons_clamp_only_probabilities() {
+        let environment = ScheduledEnvironment {
+            environment: Rc::new(Uniform(ContinuousHyperparameters { mutation_chance: 0.8, mutation_size: 0.6 })),
+            schedule: Schedule::Seasons { period: 4, amplitude: 1.0 }
+        };
+        // the season peaks a quarter of the period in, doubling the hyperparameters
+        let peak = environment.map_hyperparameters_at(&[(0, 1)], 1);
+        assert!(peak.mutation_chance < 1.0);
+        assert_eq!(peak.mutation_chance, MAX_PROBABILITY);
+        assert!((peak.mutation_size - 1.2).abs() < 1e-12);
+    }
+}
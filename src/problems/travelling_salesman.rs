@@ -7,21 +7,23 @@ use std::hash::Hash;
 
 use self::super::super::common::*;
 use self::super::super::features::FeatureMapper;
-use crate::organism::{OrganismGenerator};
-use crate::organism::Organism;
+use crate::organism::{OrganismGenerator, Validate};
 use rand::{thread_rng, Rng};
 use rand::prelude::SliceRandom;
-use std::ops::Range;
 
 use super::ProblemInstanceGenerator;
 use crate::problems::DiscreteHyperparameters;
-use std::rc::Rc;
+use std::cell::RefCell;
+use rand_distr::StandardNormal;
 use crate::scoring::Scorer;
+use crate::problems::dynamic::Dynamics;
 use crate::algorithm::mutation::Mutator;
 use serde_json::{Value, Map};
-use crate::organism::organism::Metric;
+use crate::organism::organism::{Metric, Diversity};
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
 pub struct TSPValue<T> {
     pub permutation: Vec<T>
 }
@@ -35,13 +37,71 @@ impl<T: Eq> Metric for TSPValue<T> {
                 d += 1;
             }
         }
-        return d as f64;
+        d as f64
+    }
+}
+
+impl<T: Eq + Hash + Clone + Ord> Diversity for TSPValue<T> {
+    fn distinct_genotypes(population: &[&Self]) -> usize {
+        population.iter().collect::<HashSet<_>>().len()
+    }
+
+    fn edge_entropy(population: &[&Self]) -> Option<f64> {
+        if population.is_empty() {
+            return None;
+        }
+
+        let mut frequencies: HashMap<(T,T), usize> = HashMap::new();
+        for genome in population {
+            let cities = &genome.permutation;
+            // the last city goes back to the first one
+            for (x,y) in cities.iter().zip(cities.iter().cycle().skip(1)) {
+                // tours are symmetric, an edge is the same in both directions
+                let edge = if x <= y { (x.clone(), y.clone()) } else { (y.clone(), x.clone()) };
+                *frequencies.entry(edge).or_insert(0) += 1;
+            }
+        }
+
+        let n = population.len() as f64;
+        let entropy = frequencies.values()
+            .map(|&count| {
+                let f = count as f64 / n;
+                f * (1.0 / f).ln()
+            })
+            .sum();
+
+        Some(entropy)
+    }
+}
+
+impl Validate<TSPInstance<usize>> for TSPValue<usize> {
+    fn validate(&self, problem: &TSPInstance<usize>) -> Result<(), String> {
+        if self.permutation.len() != problem.number_of_cities {
+            return Err(format!("tour of {} cities in an instance of {}", self.permutation.len(), problem.number_of_cities));
+        }
+        let mut visited = vec![false; problem.number_of_cities];
+        for &city in &self.permutation {
+            match visited.get_mut(city) {
+                None => return Err(format!("no city {} in an instance of {}", city, problem.number_of_cities)),
+                Some(true) => return Err(format!("city {} visited twice", city)),
+                Some(v) => *v = true
+            }
+        }
+        Ok(())
     }
 }
 
 #[derive(Copy, Clone)]
 pub struct TSPScorer {}
 
+impl Named for TSPScorer {
+    fn name(&self) -> String {
+        "Normalized tour length".to_string()
+    }
+}
+
+impl Parametrized for TSPScorer {}
+
 impl<T: Eq + Hash + Clone> Scorer<TSPValue<T>, TSPInstance<T>> for TSPScorer {
     fn score(&self, genome: &TSPValue<T>, problem: &TSPInstance<T>) -> f64 {
         let mut sum = 0.0;
@@ -52,13 +112,21 @@ impl<T: Eq + Hash + Clone> Scorer<TSPValue<T>, TSPInstance<T>> for TSPScorer {
         }
 
         let max_travel_distance = problem.max_dist*genome.permutation.len() as f64;
-        return (max_travel_distance-sum)/max_travel_distance;
+        (max_travel_distance-sum)/max_travel_distance
     }
 }
 
 #[derive(Copy, Clone)]
 pub struct TSPMutator {}
 
+impl Named for TSPMutator {
+    fn name(&self) -> String {
+        "Random city swaps".to_string()
+    }
+}
+
+impl Parametrized for TSPMutator {}
+
 impl<T: Clone> Mutator<TSPValue<T>, DiscreteHyperparameters> for TSPMutator {
     fn mutate(&self, genome: &mut TSPValue<T>, hyperparameters: &DiscreteHyperparameters) -> bool {
         let cities = &mut genome.permutation;
@@ -81,7 +149,7 @@ impl<T: Clone> Mutator<TSPValue<T>, DiscreteHyperparameters> for TSPMutator {
             changed = true;
         }
 
-        return changed;
+        changed
     }
 }
 
@@ -89,8 +157,9 @@ impl<T: Clone> Mutator<TSPValue<T>, DiscreteHyperparameters> for TSPMutator {
 pub struct TSPInstance<T> {
     distances: HashMap<(T, T), f64>,
     max_dist: f64,
-    min_dist: f64,
-    number_of_cities: usize
+    number_of_cities: usize,
+    /// Positions of the cities, empty when the instance was only given by its distances
+    coordinates: Vec<Vec<f64>>
 }
 
 pub struct TSPFeatureMapper {
@@ -107,7 +176,7 @@ impl Parametrized for TSPFeatureMapper {
     fn parameters(&self) -> Value {
         let mut params = Map::new();
         params.insert("number_of_cities_mapped".to_string(), int_param(self.number_cities_mapped as i64));
-        return Value::Object(params);
+        Value::Object(params)
     }
 }
 
@@ -120,7 +189,7 @@ impl<T: Hash + Clone + Eq> FeatureMapper<TSPValue<T>, Vec<T>,TSPInstance<T>> for
             n -= 1;
         }
 
-        return r;
+        r
     }
 
     fn project(&self, genome: &TSPValue<T>) -> Vec<T> {
@@ -136,27 +205,44 @@ impl<T: Hash + Clone + Eq> FeatureMapper<TSPValue<T>, Vec<T>,TSPInstance<T>> for
 impl<T> TSPInstance<T> {
     fn new(distances: HashMap<(T, T), f64>, number_of_cities: usize) -> Self {
         let max_dist: f64 = distances.values().map(|x| OrderedFloat::from(*x)).max().unwrap().into();
-        let min_dist: f64 = distances.values().map(|x| OrderedFloat::from(*x)).min().unwrap().into();
         TSPInstance {
             distances,
             max_dist,
-            min_dist,
-            number_of_cities
+            number_of_cities,
+            coordinates: Vec::new()
         }
     }
 }
 
-impl<T> TSPInstance<T> {
-    fn distance(city_1: &T, city_2: &T) -> f64 {
-        2.0
+impl TSPInstance<usize> {
+    /// Euclidean instance of cities at the given positions
+    pub fn from_coordinates(coordinates: Vec<Vec<f64>>) -> Self {
+        let mut dists = HashMap::new();
+        for i in 0..coordinates.len() {
+            for j in (i+1)..coordinates.len() {
+                let dist = coordinates[i].iter()
+                                        .zip(coordinates[j].iter())
+                                        .map(|(&x,&y)| (x-y).powf(2.0))
+                                        .sum::<f64>()
+                                        .sqrt();
+
+                dists.insert((i,j), dist);
+                dists.insert((j,i), dist);
+            }
+        }
+
+        let mut instance = TSPInstance::new(dists, coordinates.len());
+        instance.coordinates = coordinates;
+        instance
     }
 }
 
+#[derive(Default)]
 pub struct TSPRandomSolution{}
 
 impl TSPRandomSolution {
     pub fn new() -> Self {
-        return TSPRandomSolution{};
+        TSPRandomSolution{}
     }
 }
 
@@ -169,13 +255,68 @@ impl Named for TSPRandomSolution {
 impl Parametrized for TSPRandomSolution {}
 
 impl OrganismGenerator<TSPValue<usize>,TSPInstance<usize>> for TSPRandomSolution {
+    fn generate(&self, problem: &TSPInstance<usize>) -> TSPValue<usize> {
+        let v: Vec<usize> = (0..problem.number_of_cities).collect();
+        //v.shuffle(&mut rng);
+
+        TSPValue{permutation: v}
+    }
+}
+
+pub struct TSPShuffleGenerator {}
+
+impl Named for TSPShuffleGenerator {
+    fn name(&self) -> String {
+        String::from("TSP random shuffle generator")
+    }
+}
+
+impl Parametrized for TSPShuffleGenerator {}
+
+impl OrganismGenerator<TSPValue<usize>,TSPInstance<usize>> for TSPShuffleGenerator {
     fn generate(&self, problem: &TSPInstance<usize>) -> TSPValue<usize> {
         let mut v: Vec<usize> = (0..problem.number_of_cities).collect();
-        let mut rng = thread_rng();
+        v.shuffle(&mut thread_rng());
+        TSPValue{permutation: v}
+    }
+}
 
-        //v.shuffle(&mut rng);
+/// Builds tours by always going to the nearest unvisited city, from a random first city
+pub struct TSPNearestNeighbourGenerator {}
 
-        return TSPValue{permutation: v};
+impl Named for TSPNearestNeighbourGenerator {
+    fn name(&self) -> String {
+        String::from("TSP nearest neighbour generator")
+    }
+}
+
+impl Parametrized for TSPNearestNeighbourGenerator {}
+
+impl OrganismGenerator<TSPValue<usize>,TSPInstance<usize>> for TSPNearestNeighbourGenerator {
+    fn generate(&self, problem: &TSPInstance<usize>) -> TSPValue<usize> {
+        let n = problem.number_of_cities;
+        if n == 0 {
+            return TSPValue{permutation: Vec::new()};
+        }
+
+        let mut visited = vec![false; n];
+        let mut current = thread_rng().gen_range(0, n);
+        let mut permutation = Vec::with_capacity(n);
+
+        for _i in 0..n {
+            visited[current] = true;
+            permutation.push(current);
+
+            let next = (0..n)
+                .filter(|&c| !visited[c])
+                .min_by_key(|&c| OrderedFloat(problem.distances[&(current, c)]));
+            match next {
+                Some(c) => current = c,
+                None => break
+            }
+        }
+
+        TSPValue{permutation}
     }
 }
 
@@ -196,39 +337,174 @@ impl Parametrized for SimpleTSPInstanceGenerator {
         let mut hm = Map::new();
         hm.insert("number_of_cities_generated".to_string(),
                   int_param(self.number_of_cities as i64));
-        return Value::Object(hm);
+        Value::Object(hm)
     }
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 impl<'a> ProblemInstanceGenerator<TSPInstance<usize>> for SimpleTSPInstanceGenerator {
     fn generate_problem(&self) -> TSPInstance<usize> {
-        let mut dists = HashMap::new();
         let mut rng = thread_rng();
 
         let mut cities = Vec::with_capacity(self.number_of_cities);
-        for i in 0..self.number_of_cities {
+        for _i in 0..self.number_of_cities {
             let mut coords = Vec::with_capacity(self.number_of_dimensions);
-            for d in 0..self.number_of_dimensions {
+            for _d in 0..self.number_of_dimensions {
                 coords.push(rng.gen_range(0.0,100.0));
             }
             cities.push(coords);
         }
 
-        for i in 0..self.number_of_cities {
-            for j in (i+1)..self.number_of_cities {
+        TSPInstance::from_coordinates(cities)
+    }
+}
 
-                //let dist = rng.gen_range(0.0,100.0);
-                let dist = cities[i].iter()
-                                        .zip(cities[j].iter())
-                                        .map(|(&x,&y)| (x-y as f64).powf(2.0))
-                                        .sum::<f64>()
-                                        .sqrt();
+/// Cities moving during a run: at every change `moved_cities` random cities move by a Gaussian step
+/// of standard deviation `step` and `relocated_cities` others jump to random positions. Tours are
+/// permutations of a fixed set of cities, so cities are never added nor removed, a relocated city
+/// stands for one disappearing and another appearing elsewhere. Cities stay within the bounding box
+/// of the instance. Tours are scored as by `TSPScorer` on the current positions of the cities.
+pub struct MovingCities {
+    pub moved_cities: usize,
+    pub step: f64,
+    pub relocated_cities: usize,
+    current: RefCell<Option<TSPInstance<usize>>>
+}
 
-                dists.insert((i,j), dist);
-                dists.insert((j,i), dist);
+impl MovingCities {
+    pub fn new(moved_cities: usize, step: f64, relocated_cities: usize) -> Self {
+        MovingCities {
+            moved_cities,
+            step,
+            relocated_cities,
+            current: RefCell::new(None)
+        }
+    }
+}
+
+impl Named for MovingCities {
+    fn name(&self) -> String {
+        String::from("Moving cities tour length")
+    }
+}
+
+impl Parametrized for MovingCities {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("moved cities".to_string(), self.moved_cities.into());
+        hm.insert("step".to_string(), self.step.into());
+        hm.insert("relocated cities".to_string(), self.relocated_cities.into());
+        Value::Object(hm)
+    }
+}
+
+impl Dynamics<TSPValue<usize>, TSPInstance<usize>> for MovingCities {
+    fn score(&self, genome: &TSPValue<usize>, problem: &TSPInstance<usize>) -> f64 {
+        match self.current.borrow().as_ref() {
+            Some(current) => TSPScorer{}.score(genome, current),
+            None => TSPScorer{}.score(genome, problem)
+        }
+    }
+
+    fn reset(&self, _problem: &TSPInstance<usize>) {
+        *self.current.borrow_mut() = None;
+    }
+
+    /// Instances given only by their distances have no positions to move and never change
+    fn change(&self, problem: &TSPInstance<usize>) -> bool {
+        let mut coordinates = match self.current.borrow().as_ref() {
+            Some(current) => current.coordinates.clone(),
+            None => problem.coordinates.clone()
+        };
+        let n = coordinates.len();
+        let moved = self.moved_cities.min(n);
+        let relocated = self.relocated_cities.min(n - moved);
+        if moved + relocated == 0 {
+            return false;
+        }
+
+        let bounds: Vec<(f64, f64)> = (0..problem.coordinates[0].len())
+            .map(|d| problem.coordinates.iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), city| (low.min(city[d]), high.max(city[d]))))
+            .collect();
+
+        let mut rng = thread_rng();
+        let mut cities: Vec<usize> = (0..n).collect();
+        cities.shuffle(&mut rng);
+
+        for &city in &cities[..moved] {
+            for (x, &(low, high)) in coordinates[city].iter_mut().zip(&bounds) {
+                *x = (*x + self.step * rng.sample::<f64,_>(StandardNormal)).clamp(low, high);
+            }
+        }
+        for &city in &cities[moved..(moved + relocated)] {
+            for (x, &(low, high)) in coordinates[city].iter_mut().zip(&bounds) {
+                *x = if low < high { rng.gen_range(low, high) } else { low };
             }
         }
 
-        return TSPInstance::new(dists, self.number_of_cities)
+        *self.current.borrow_mut() = Some(TSPInstance::from_coordinates(coordinates));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tour(permutation: &[usize]) -> TSPValue<usize> {
+        TSPValue { permutation: permutation.to_vec() }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn edge_entropy_closes_the_tour() {
+        // the three edges of a 3-city tour are all present once, whatever the direction
+        let a = tour(&[0, 1, 2]);
+        let b = tour(&[2, 1, 0]);
+        assert_eq!(TSPValue::edge_entropy(&[&a]), Some(0.0));
+        assert_eq!(TSPValue::edge_entropy(&[&a, &b]), Some(0.0));
+    }
+
+    #[test]
+    fn edge_entropy_of_different_tours() {
+        // edges 0-3 and 1-2 are shared, the four others appear in half of the tours
+        let a = tour(&[0, 1, 2, 3]);
+        let b = tour(&[0, 2, 1, 3]);
+        let entropy = TSPValue::edge_entropy(&[&a, &b]).unwrap();
+        assert!((entropy - 2.0 * 2.0_f64.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn distinct_tours() {
+        let a = tour(&[0, 1, 2]);
+        let b = tour(&[0, 2, 1]);
+        assert_eq!(TSPValue::distinct_genotypes(&[&a, &b, &a.clone()]), 2);
+    }
+
+    #[test]
+    fn moving_cities_stay_in_the_instance_box() {
+        let coordinates = vec![vec![1000.0, 5.0], vec![1010.0, 6.0], vec![1005.0, 5.5], vec![1002.0, 5.2]];
+        let instance = TSPInstance::from_coordinates(coordinates);
+        let dynamics = MovingCities::new(2, 50.0, 2);
+        dynamics.reset(&instance);
+        for _ in 0..20 {
+            assert!(dynamics.change(&instance));
+            for city in &dynamics.current.borrow().as_ref().unwrap().coordinates {
+                assert!((1000.0..=1010.0).contains(&city[0]));
+                assert!((5.0..=6.0).contains(&city[1]));
+            }
+        }
+
+        let still = MovingCities::new(0, 50.0, 0);
+        assert!(!still.change(&instance));
+    }
+
+    #[test]
+    fn validate_tours_against_the_instance() {
+        let instance = TSPInstance::from_coordinates(vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!(tour(&[2, 0, 1]).validate(&instance).is_ok());
+        assert!(tour(&[0, 1]).validate(&instance).is_err());
+        assert!(tour(&[0, 1, 3]).validate(&instance).is_err());
+        assert!(tour(&[0, 1, 1]).validate(&instance).is_err());
+    }
+}
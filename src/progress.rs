@@ -0,0 +1,285 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+//
+// Progress of the experiment, reported while the runs execute
+//
+
+/// Interval between two progress reports when the output is a terminal
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(200);
+/// Interval between two progress reports in logs and in JSON mode
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// The clock is only read every this many iterations
+const CLOCK_CHECK_PERIOD: u64 = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProgressMode {
+    /// Status line of the current run and a table of all runs at the end
+    Human,
+    Quiet,
+    /// One JSON object per line and per event, for job scripts
+    Json
+}
+
+impl ProgressMode {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "human" => Ok(ProgressMode::Human),
+            "quiet" => Ok(ProgressMode::Quiet),
+            "json" => Ok(ProgressMode::Json),
+            other => Err(format!("unknown progress mode \"{}\", expected human, quiet or json", other))
+        }
+    }
+}
+
+/// Outcome of a finished run, for the final table
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+    pub problem: String,
+    pub repetition: u64,
+    pub algorithm_index: usize,
+    pub algorithm: String,
+    pub iterations: u64,
+    /// Genomes scored by the solver, several per iteration for solvers scoring batches
+    pub evaluations: u64,
+    pub best_score: f64,
+    pub mean_score: Option<f64>,
+    pub duration: Duration
+}
+
+impl RunSummary {
+    fn evaluations_per_second(&self) -> f64 {
+        self.evaluations as f64 / self.duration.as_secs_f64().max(1e-9)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "problem": self.problem,
+            "repetition": self.repetition,
+            "algorithm index": self.algorithm_index,
+            "algorithm": self.algorithm,
+            "iterations": self.iterations,
+            "evaluations": self.evaluations,
+            "best score": self.best_score,
+            "mean score": self.mean_score,
+            "duration (s)": self.duration.as_secs_f64(),
+            "evaluations per second": self.evaluations_per_second()
+        })
+    }
+}
+
+/// State of the run in progress
+struct CurrentRun {
+    summary: RunSummary,
+    total_iterations: u64,
+    start: Instant
+}
+
+pub struct ProgressReporter {
+    mode: ProgressMode,
+    terminal: bool,
+    problem: String,
+    total_runs: u64,
+    experiment_start: Instant,
+    current: Option<CurrentRun>,
+    last_report: Instant,
+    finished: Vec<RunSummary>
+}
+
+fn format_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    format!("{}:{:02}:{:02}", s / 3600, (s / 60) % 60, s % 60)
+}
+
+impl ProgressReporter {
+    pub fn new(mode: ProgressMode, total_runs: u64) -> Self {
+        ProgressReporter {
+            mode,
+            terminal: std::io::stdout().is_terminal(),
+            problem: String::new(),
+            total_runs,
+            experiment_start: Instant::now(),
+            current: None,
+            last_report: Instant::now(),
+            finished: Vec::new()
+        }
+    }
+
+    /// Informational message, only shown in human mode
+    pub fn info(&self, message: &str) {
+        if self.mode == ProgressMode::Human {
+            println!("{}", message);
+        }
+    }
+
+    fn emit(&self, event: &str, mut fields: Value) {
+        if let Value::Object(map) = &mut fields {
+            map.insert("event".to_string(), event.into());
+        }
+        println!("{}", fields);
+    }
+
+    pub fn start_problem(&mut self, problem: &str) {
+        self.problem = problem.to_string();
+    }
+
+    pub fn start_run(&mut self, repetition: u64, algorithm_index: usize, algorithm: &str, total_iterations: u64) {
+        let summary = RunSummary {
+            problem: self.problem.clone(),
+            repetition,
+            algorithm_index,
+            algorithm: algorithm.to_string(),
+            iterations: 0,
+            evaluations: 0,
+            best_score: f64::NEG_INFINITY,
+            mean_score: None,
+            duration: Duration::default()
+        };
+        if self.mode == ProgressMode::Json {
+            self.emit("run start", json!({
+                "run": self.finished.len() + 1,
+                "total runs": self.total_runs,
+                "problem": summary.problem,
+                "repetition": repetition,
+                "algorithm index": algorithm_index,
+                "algorithm": summary.algorithm,
+                "total iterations": total_iterations
+            }));
+        }
+        self.current = Some(CurrentRun {
+            summary,
+            total_iterations,
+            start: Instant::now()
+        });
+        self.last_report = Instant::now();
+    }
+
+    /// Records an iteration of the current run and the evaluations since its start, the mean score is only
+    /// known on logged iterations
+    pub fn iteration(&mut self, iteration: u64, evaluations: u64, best_score: f64, mean_score: Option<f64>) {
+        let current = match &mut self.current {
+            Some(c) => c,
+            None => return
+        };
+        current.summary.iterations = iteration + 1;
+        current.summary.evaluations = evaluations;
+        current.summary.best_score = best_score;
+        if mean_score.is_some() {
+            current.summary.mean_score = mean_score;
+        }
+
+        if self.mode == ProgressMode::Quiet || !iteration.is_multiple_of(CLOCK_CHECK_PERIOD) {
+            return;
+        }
+        let interval = if self.terminal && self.mode == ProgressMode::Human { TERMINAL_INTERVAL } else { LOG_INTERVAL };
+        if self.last_report.elapsed() >= interval {
+            self.last_report = Instant::now();
+            self.report();
+        }
+    }
+
+    /// Estimated remaining time of the current run and of the whole experiment
+    fn eta(&self, current: &CurrentRun) -> (Duration, Duration) {
+        let elapsed = current.start.elapsed();
+        let done = current.summary.iterations.max(1) as f64;
+        let run_remaining = elapsed.mul_f64((current.total_iterations as f64 - done).max(0.0) / done);
+
+        let runs_done = self.finished.len() as f64;
+        let run_estimate = if runs_done > 0.0 {
+            self.finished.iter().map(|r| r.duration).sum::<Duration>().div_f64(runs_done)
+        } else {
+            elapsed + run_remaining
+        };
+        let runs_left = (self.total_runs as f64 - runs_done - 1.0).max(0.0);
+        (run_remaining, run_remaining + run_estimate.mul_f64(runs_left))
+    }
+
+    fn report(&self) {
+        let current = match &self.current {
+            Some(c) => c,
+            None => return
+        };
+        let (run_eta, total_eta) = self.eta(current);
+        let rate = current.summary.evaluations as f64 / current.start.elapsed().as_secs_f64().max(1e-9);
+
+        match self.mode {
+            ProgressMode::Human => {
+                let line = format!("run {}/{} {} rep {} {}: iteration {}/{} best {:.6} mean {} {:.0} eval/s ETA run {} total {}",
+                                   self.finished.len() + 1, self.total_runs, current.summary.problem,
+                                   current.summary.repetition, current.summary.algorithm,
+                                   current.summary.iterations, current.total_iterations, current.summary.best_score,
+                                   current.summary.mean_score.map_or("-".to_string(), |m| format!("{:.6}", m)),
+                                   rate, format_duration(run_eta), format_duration(total_eta));
+                if self.terminal {
+                    print!("\r\x1b[2K{}", line);
+                    std::io::stdout().flush().ok();
+                } else {
+                    println!("{}", line);
+                }
+            },
+            ProgressMode::Json => {
+                let mut fields = current.summary.to_json();
+                fields["run"] = (self.finished.len() + 1).into();
+                fields["total iterations"] = current.total_iterations.into();
+                fields["evaluations per second"] = rate.into();
+                fields["run eta (s)"] = run_eta.as_secs_f64().into();
+                fields["total eta (s)"] = total_eta.as_secs_f64().into();
+                self.emit("progress", fields);
+            },
+            ProgressMode::Quiet => {}
+        }
+    }
+
+    pub fn end_run(&mut self) {
+        let mut current = match self.current.take() {
+            Some(c) => c,
+            None => return
+        };
+        current.summary.duration = current.start.elapsed();
+
+        match self.mode {
+            ProgressMode::Human => {
+                if self.terminal {
+                    print!("\r\x1b[2K");
+                }
+                let s = &current.summary;
+                println!("run {}/{} done: {} rep {} {}: best {:.6} in {} ({:.0} eval/s)",
+                         self.finished.len() + 1, self.total_runs, s.problem, s.repetition, s.algorithm,
+                         s.best_score, format_duration(s.duration), s.evaluations_per_second());
+            },
+            ProgressMode::Json => {
+                let mut fields = current.summary.to_json();
+                fields["run"] = (self.finished.len() + 1).into();
+                self.emit("run end", fields);
+            },
+            ProgressMode::Quiet => {}
+        }
+        self.finished.push(current.summary);
+    }
+
+    /// Table of all the runs, or their list in JSON mode
+    pub fn finish(&self) {
+        match self.mode {
+            ProgressMode::Human => {
+                println!();
+                println!("{:>4} {:<20} {:>4} {:>5} {:<40} {:>10} {:>12} {:>12} {:>10} {:>10}",
+                         "run", "problem", "rep", "algo", "algorithm", "iterations", "best score", "mean score", "duration", "eval/s");
+                for (i, s) in self.finished.iter().enumerate() {
+                    println!("{:>4} {:<20} {:>4} {:>5} {:<40} {:>10} {:>12.6} {:>12} {:>10} {:>10.0}",
+                             i + 1, s.problem, s.repetition, s.algorithm_index, s.algorithm, s.iterations, s.best_score,
+                             s.mean_score.map_or("-".to_string(), |m| format!("{:.6}", m)),
+                             format_duration(s.duration), s.evaluations_per_second());
+                }
+                println!("Finished {} runs in {}", self.finished.len(), format_duration(self.experiment_start.elapsed()));
+            },
+            ProgressMode::Json => {
+                self.emit("summary", json!({
+                    "runs": self.finished.iter().map(|s| s.to_json()).collect::<Vec<Value>>(),
+                    "duration (s)": self.experiment_start.elapsed().as_secs_f64()
+                }));
+            },
+            ProgressMode::Quiet => {}
+        }
+    }
+}
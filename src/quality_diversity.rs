@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+use serde_json::{Value, Map};
+use crate::common::{Named, Parametrized};
+use crate::features::FeatureMapper;
+use crate::statistics::{Binning, Distribution};
+
+//
+// Quality-diversity metrics, measured in a feature space shared by all algorithms
+//
+
+pub const NICHE_SCORE_BINS: Binning = Binning::Linear { min: 0.0, max: 1.0, bins: 10 };
+
+/// Feature space in which the populations of every algorithm are compared
+pub trait QDReference<V,P>: Named + Parametrized {
+    /// Archive for a new run, empty until it observes a population
+    fn new_archive(&self, problem: &P) -> Box<dyn QDArchive<V>>;
+}
+
+pub trait QDArchive<V> {
+    /// Compares the niches occupied by a population with the ones occupied by the previous observations
+    fn observe(&mut self, population: &[(&V, f64)]) -> QDStats;
+}
+
+/// Reference built from a feature mapper, scores are normalised with `score_bounds` before being summed
+pub struct ReferenceFeatures<V,F,P> {
+    pub feature_mapper: Rc<dyn FeatureMapper<V,F,P>>,
+    /// Lowest and highest possible scores of the problem
+    pub score_bounds: (f64, f64)
+}
+
+impl<V,F,P> Named for ReferenceFeatures<V,F,P> {
+    fn name(&self) -> String {
+        format!("Reference {}", self.feature_mapper.name())
+    }
+}
+
+impl<V,F,P> Parametrized for ReferenceFeatures<V,F,P> {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("feature mapper".to_string(), self.feature_mapper.name().into());
+        hm.insert("feature mapper parameters".to_string(), self.feature_mapper.parameters());
+        hm.insert("score bounds".to_string(), vec![self.score_bounds.0, self.score_bounds.1].into());
+        Value::Object(hm)
+    }
+}
+
+impl<V: 'static, F: 'static + Hash + Clone + Eq, P: 'static> QDReference<V,P> for ReferenceFeatures<V,F,P> {
+    fn new_archive(&self, problem: &P) -> Box<dyn QDArchive<V>> {
+        Box::new(NicheArchive {
+            feature_mapper: self.feature_mapper.clone(),
+            score_bounds: self.score_bounds,
+            number_of_niches: self.feature_mapper.number_of_possible_features(problem),
+            best: HashMap::new()
+        })
+    }
+}
+
+/// Best normalised score ever observed in every niche of a run
+pub struct NicheArchive<V,F,P> {
+    feature_mapper: Rc<dyn FeatureMapper<V,F,P>>,
+    score_bounds: (f64, f64),
+    number_of_niches: usize,
+    best: HashMap<F, f64>
+}
+
+impl<V,F,P> NicheArchive<V,F,P> {
+    fn normalise(&self, score: f64) -> f64 {
+        let (min, max) = self.score_bounds;
+        ((score - min) / (max - min)).clamp(0.0, 1.0)
+    }
+}
+
+impl<V, F: Hash + Clone + Eq, P> QDArchive<V> for NicheArchive<V,F,P> {
+    fn observe(&mut self, population: &[(&V, f64)]) -> QDStats {
+        let mut niches: HashMap<F, f64> = HashMap::new();
+        for &(genome, score) in population {
+            let s = self.normalise(score);
+            let best = niches.entry(self.feature_mapper.project(genome)).or_insert(s);
+            *best = best.max(s);
+        }
+
+        let mut discoveries = 0;
+        let mut improvements = 0;
+        for (niche, &s) in &niches {
+            match self.best.get_mut(niche) {
+                None => {
+                    discoveries += 1;
+                    self.best.insert(niche.clone(), s);
+                },
+                Some(best) => {
+                    if s > *best {
+                        improvements += 1;
+                        *best = s;
+                    }
+                }
+            }
+        }
+
+        let best_in_niche: Vec<f64> = niches.values().cloned().collect();
+
+        QDStats {
+            coverage: niches.len() as f64 / self.number_of_niches as f64,
+            qd_score: best_in_niche.iter().sum(),
+            best_in_niche: Distribution::of(&best_in_niche, NICHE_SCORE_BINS),
+            discoveries,
+            improvements
+        }
+    }
+}
+
+/// Quality-diversity of a population seen as an archive of the reference feature space
+#[derive(Clone, Debug, PartialEq)]
+pub struct QDStats {
+    /// Fraction of the niches occupied by the population
+    pub coverage: f64,
+    /// Sum of the normalised best score of every occupied niche
+    pub qd_score: f64,
+    pub best_in_niche: Option<Distribution>,
+    /// Niches occupied for the first time of the run since the last observation
+    pub discoveries: u64,
+    /// Niches whose best score was beaten since the last observation
+    pub improvements: u64
+}
+
+impl QDStats {
+    pub fn header() -> Vec<String> {
+        [
+            "niche coverage",
+            "QD score",
+            "best in niche mean",
+            "best in niche variance",
+            "best in niche histogram",
+            "niche discoveries",
+            "niche improvements"
+        ].iter().map(|s| s.to_string()).collect()
+    }
+
+    pub fn row(stats: &Option<QDStats>) -> Vec<Value> {
+        match stats {
+            Some(s) => {
+                let mut row = vec![s.coverage.into(), s.qd_score.into()];
+                match &s.best_in_niche {
+                    Some(d) => row.extend(vec![d.mean.into(), d.variance.into(), d.histogram_string().into()]),
+                    None => row.extend(vec![Value::Null; 3])
+                }
+                row.push(s.discoveries.into());
+                row.push(s.improvements.into());
+                row
+            },
+            None => vec![Value::Null; 7]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Niche of an integer genome, its tens digit, among ten niches
+    struct Tens;
+
+    impl Named for Tens {
+        fn name(&self) -> String {
+            String::from("tens")
+        }
+    }
+
+    impl Parametrized for Tens {}
+
+    impl FeatureMapper<u32, u32, ()> for Tens {
+        fn number_of_possible_features(&self, _problem: &()) -> usize {
+            10
+        }
+
+        fn project(&self, genome: &u32) -> u32 {
+            genome / 10
+        }
+
+        fn default_features(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn observe_niches_over_two_populations() {
+        let reference = ReferenceFeatures { feature_mapper: Rc::new(Tens), score_bounds: (0.0, 10.0) };
+        let mut archive = reference.new_archive(&());
+
+        // niches 0 and 1, the best of niche 0 is 5
+        let stats = archive.observe(&[(&1, 2.0), (&2, 5.0), (&15, 3.0)]);
+        assert_eq!(stats.coverage, 0.2);
+        assert!((stats.qd_score - 0.8).abs() < 1e-12);
+        assert_eq!(stats.discoveries, 2);
+        assert_eq!(stats.improvements, 0);
+
+        // niche 0 gets worse, niche 1 better and niche 2 is discovered, scores beyond the bounds are clamped
+        let stats = archive.observe(&[(&3, 4.0), (&16, 6.0), (&27, 20.0)]);
+        assert!((stats.coverage - 0.3).abs() < 1e-12);
+        assert!((stats.qd_score - (0.4 + 0.6 + 1.0)).abs() < 1e-12);
+        assert_eq!(stats.discoveries, 1);
+        assert_eq!(stats.improvements, 1);
+        assert_eq!(stats.best_in_niche.unwrap().histogram, vec![0, 0, 0, 0, 1, 0, 1, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use crate::algorithm::algorithm::ReplacementSelection;
+use crate::algorithm::config::ProblemConfig;
+use crate::algorithm::evaluation::ReEvaluation;
+use crate::algorithm::generational::GenerationalGA;
+use crate::algorithm::island::IslandModel;
+use crate::algorithm::grid_ga::GeneralizedMAPElite;
+use crate::algorithm::map_elite::MAPElite;
+use crate::algorithm::mutation::Mutator;
+use crate::algorithm::selection::{Elitism, GreedySelection, MetropolisHastings};
+use crate::algorithm::simple::SimpleReplacement;
+use crate::algorithm::simple_adaptive::SimpleAdaptive;
+use crate::features::FeatureMapper;
+use crate::organism::{Diversity, OrganismGenerator, Validate};
+use crate::organism::warm_start::WarmStartGenerator;
+use crate::problems::{ProblemInstanceGenerator, Environment, SelfAdaptive};
+use crate::problems::{DiscreteHyperparameters, ContinuousHyperparameters, SpatialMapper, ContinuousSpatialMapper};
+use crate::problems::landscape::DataEnvironment;
+use crate::problems::schedule::{Schedule, ScheduledEnvironment};
+use crate::problems::dynamic::{Dynamics, DynamicScorer};
+use crate::problems::travelling_salesman::{TSPValue, TSPInstance, SimpleTSPInstanceGenerator, TSPRandomSolution,
+                                           TSPShuffleGenerator, TSPNearestNeighbourGenerator, TSPScorer, TSPMutator, TSPFeatureMapper, MovingCities};
+use crate::problems::rastrigin::{RastriginValue, Rastrigin, RastriginFeature, RastriginGenerator, RastriginUniformGenerator,
+                                 RastriginLatinHypercubeGenerator, RegRastriginScorer, RastriginMutator, RastriginMapper, MovingPeaks};
+use crate::problems::onemax::{OneMaxValue, OneMax, OneMaxGenerator, OneMaxRandomGenerator, OneMaxScorer, OneMaxMutator, OneMaxMapper, ShiftingTarget};
+use crate::quality_diversity::{QDReference, ReferenceFeatures};
+use crate::scoring::Scorer;
+use crate::scoring::cache::CachedScorer;
+use crate::scoring::noise::{Noise, NoisyScorer};
+use crate::scoring::parallel::ParallelScorer;
+use crate::scoring::external::ExternalScorer;
+use crate::runner::{Config, MyConfig, AlgoConfig, CommonParameters};
+use crate::statistics::DiversityEstimator;
+
+//
+// Components built by name from their JSON parameters
+//
+// A component is given either as its registered name or as `{"name": ..., "parameters": {...}}`.
+//
+
+/// Builds a component from its parameters, the definition it belongs to gives access to the other components
+pub type Constructor<T, D> = Rc<dyn Fn(&Value, &D) -> Result<T, String>>;
+
+pub struct ComponentTable<T, D> {
+    kind: &'static str,
+    constructors: BTreeMap<String, Constructor<T, D>>
+}
+
+impl<T, D> ComponentTable<T, D> {
+    pub fn new(kind: &'static str) -> Self {
+        ComponentTable {
+            kind,
+            constructors: BTreeMap::new()
+        }
+    }
+
+    /// Registers `constructor` under `name`, replacing any component registered under the same name
+    pub fn register<C: 'static + Fn(&Value, &D) -> Result<T, String>>(&mut self, name: &str, constructor: C) {
+        self.constructors.insert(name.to_string(), Rc::new(constructor));
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.constructors.keys().cloned().collect()
+    }
+
+    pub fn build(&self, spec: &Value, definition: &D) -> Result<T, String> {
+        let (name, parameters) = match spec {
+            Value::String(name) => (name.as_str(), &Value::Null),
+            Value::Object(map) => match map.get("name") {
+                Some(Value::String(name)) => (name.as_str(), map.get("parameters").unwrap_or(&Value::Null)),
+                _ => return Err(format!("{} {} has no name", self.kind, spec))
+            },
+            _ => return Err(format!("expected the name of a {} or an object with its name and parameters, got {}", self.kind, spec))
+        };
+        let constructor = self.constructors.get(name).ok_or_else(|| {
+            format!("unknown {} \"{}\", expected one of {}", self.kind, name, self.names().join(", "))
+        })?;
+        constructor(parameters, definition).map_err(|e| format!("{} \"{}\": {}", self.kind, name, e))
+    }
+}
+
+/// Name of a component given by its name or as `{"name": ..., "parameters": {...}}`
+pub fn component_name(spec: &Value) -> Option<&str> {
+    match spec {
+        Value::String(name) => Some(name.as_str()),
+        Value::Object(map) => map.get("name").and_then(|n| n.as_str()),
+        _ => None
+    }
+}
+
+pub fn number_param(parameters: &Value, key: &str, default: Option<f64>) -> Result<f64, String> {
+    match parameters.get(key) {
+        Some(v) => v.as_f64().ok_or_else(|| format!("\"{}\" should be a number, got {}", key, v)),
+        None => default.ok_or_else(|| format!("missing parameter \"{}\"", key))
+    }
+}
+
+pub fn count_param(parameters: &Value, key: &str, default: Option<usize>) -> Result<usize, String> {
+    match parameters.get(key) {
+        Some(v) => v.as_u64().map(|c| c as usize).ok_or_else(|| format!("\"{}\" should be a positive integer, got {}", key, v)),
+        None => default.ok_or_else(|| format!("missing parameter \"{}\"", key))
+    }
+}
+
+pub fn flag_param(parameters: &Value, key: &str, default: bool) -> Result<bool, String> {
+    match parameters.get(key) {
+        Some(v) => v.as_bool().ok_or_else(|| format!("\"{}\" should be true or false, got {}", key, v)),
+        None => Ok(default)
+    }
+}
+
+pub fn text_param<'a>(parameters: &'a Value, key: &str) -> Result<&'a str, String> {
+    match parameters.get(key) {
+        Some(v) => v.as_str().ok_or_else(|| format!("\"{}\" should be a string, got {}", key, v)),
+        None => Err(format!("missing parameter \"{}\"", key))
+    }
+}
+
+/// Scorer of a problem, scoring batches on several threads when the "threads" parameter is above 1
+pub fn threaded_scorer<V: Sync, P: Sync, S: 'static + Scorer<V,P> + Sync>(scorer: S, parameters: &Value) -> Result<Rc<dyn Scorer<V,P>>, String> {
+    let threads = count_param(parameters, "threads", Some(1))?;
+    if threads > 1 {
+        return Ok(Rc::new(ParallelScorer::new(scorer, threads)));
+    }
+    Ok(Rc::new(scorer))
+}
+
+/// Scorer of a dynamic problem, whose landscape changes every "period" iterations
+pub fn dynamic_scorer<V: 'static, P: 'static, D: 'static + Dynamics<V,P>>(dynamics: D, parameters: &Value) -> Result<Rc<dyn Scorer<V,P>>, String> {
+    let period = count_param(parameters, "period", Some(1000))?;
+    if period == 0 {
+        return Err("the change period should be at least 1 iteration".to_string());
+    }
+    Ok(Rc::new(DynamicScorer::new(dynamics, period as u64)))
+}
+
+//
+// Problem definitions
+//
+
+type Algorithms<V,P,H> = Vec<Rc<AlgoConfig<V,P,H>>>;
+type ScorerWrapper<V,P> = fn(Rc<dyn Scorer<V,P>>, usize) -> Rc<dyn Scorer<V,P>>;
+
+/// Entries of a problem specification, missing entries are taken from the defaults of the problem
+const SPECIFICATION_KEYS: [&str; 14] = ["problem", "instance generator", "initialisation", "scorer", "noise", "fitness cache",
+    "re-evaluation", "mutator", "environment", "constant hyperparameters", "elitism", "algorithms", "quality diversity reference",
+    "diversity"];
+
+/// Diversity estimators given as a comma separated list or as a list of estimators, see `DiversityEstimator::parse`
+fn diversity_estimators(spec: &Value) -> Result<Vec<DiversityEstimator>, String> {
+    match spec {
+        Value::String(list) => DiversityEstimator::parse_list(list),
+        Value::Array(estimators) => estimators.iter()
+            .map(|e| e.as_str().ok_or_else(|| format!("diversity estimators should be names, got {}", e)).and_then(DiversityEstimator::parse))
+            .collect(),
+        other => Err(format!("\"diversity\" should be a list of estimators, got {}", other))
+    }
+}
+
+/// Everything a problem can be built from, and the specification of its usual configuration
+pub struct ProblemDefinition<V,P,F,H> {
+    pub instance_generators: ComponentTable<Rc<dyn ProblemInstanceGenerator<P>>, Self>,
+    pub initialisations: ComponentTable<Rc<dyn OrganismGenerator<V,P>>, Self>,
+    pub scorers: ComponentTable<Rc<dyn Scorer<V,P>>, Self>,
+    pub noises: ComponentTable<Noise, Self>,
+    pub re_evaluations: ComponentTable<ReEvaluation, Self>,
+    pub mutators: ComponentTable<Rc<dyn Mutator<V,H>>, Self>,
+    pub feature_mappers: ComponentTable<Rc<dyn FeatureMapper<V,F,P>>, Self>,
+    pub environments: ComponentTable<Rc<dyn Environment<H>>, Self>,
+    pub elitisms: ComponentTable<Rc<dyn Elitism>, Self>,
+    pub replacement_selections: ComponentTable<Rc<dyn ReplacementSelection<V,P,H>>, Self>,
+    /// Keeps the scores of a run in a cache of the given capacity, for problems whose genomes can be hashed
+    pub fitness_cache: Option<ScorerWrapper<V,P>>,
+    /// Feature of the organisms when an algorithm maps none
+    pub default_feature: F,
+    pub defaults: Value
+}
+
+impl<V: 'static + Clone + PartialEq + Diversity + Validate<P> + Serialize + DeserializeOwned,
+    P: 'static,
+    F: 'static + Eq + Clone + Hash + Serialize,
+    H: 'static + SelfAdaptive + Copy> ProblemDefinition<V,P,F,H> {
+    /// Definition with the components shared by all problems: selections, algorithms, landscapes and warm starts
+    pub fn new(default_feature: F, defaults: Value) -> Self {
+        let mut definition = ProblemDefinition {
+            instance_generators: ComponentTable::new("instance generator"),
+            initialisations: ComponentTable::new("initialisation"),
+            scorers: ComponentTable::new("scorer"),
+            noises: ComponentTable::new("noise"),
+            re_evaluations: ComponentTable::new("re-evaluation"),
+            mutators: ComponentTable::new("mutator"),
+            feature_mappers: ComponentTable::new("feature mapper"),
+            environments: ComponentTable::new("environment"),
+            elitisms: ComponentTable::new("elitism"),
+            replacement_selections: ComponentTable::new("replacement selection"),
+            fitness_cache: None,
+            default_feature,
+            defaults
+        };
+
+        definition.elitisms.register("greedy", |_, _| Ok(Rc::new(GreedySelection{})));
+        definition.elitisms.register("metropolis-hastings", |_, _| Ok(Rc::new(MetropolisHastings{})));
+
+        definition.replacement_selections.register("simple", |_, _| Ok(Rc::new(SimpleReplacement{})));
+        definition.replacement_selections.register("generalized-map-elite", |p, d: &Self| {
+            let feature_mapper = match p.get("feature mapper") {
+                None | Some(Value::Null) => None,
+                Some(spec) => Some(d.feature_mappers.build(spec, d)?)
+            };
+            let grid_shape = match p.get("grid shape") {
+                None | Some(Value::Null) => None,
+                Some(shape) => Some(serde_json::from_value::<Vec<usize>>(shape.clone())
+                    .map_err(|e| format!("\"grid shape\" should be a list of axis sizes: {}", e))?)
+            };
+            Ok(Rc::new(GeneralizedMAPElite {
+                default_feature: feature_mapper.as_ref().map_or(d.default_feature.clone(), |m| m.default_features()),
+                feature_mapper,
+                use_hyperparameter_mapping: flag_param(p, "hyperparameter mapping", false)?,
+                number_of_spatial_dimensions: count_param(p, "spatial dimensions", Some(1))?,
+                grid_shape
+            }))
+        });
+        definition.replacement_selections.register("map-elite", |p, d: &Self| {
+            let spec = p.get("feature mapper").ok_or("missing parameter \"feature mapper\"")?;
+            Ok(Rc::new(MAPElite {
+                feature_mapper: d.feature_mappers.build(spec, d)?,
+                batch_size: count_param(p, "batch size", Some(1))?
+            }))
+        });
+        definition.replacement_selections.register("generational", |p, _| Ok(Rc::new(GenerationalGA {
+            tournament_size: count_param(p, "tournament size", Some(3))?,
+            elites: count_param(p, "elites", Some(1))?
+        })));
+        definition.replacement_selections.register("island", |p, _| Ok(Rc::new(IslandModel {
+            number_of_islands: count_param(p, "islands", Some(8))?,
+            migration_interval: count_param(p, "migration interval", Some(100))? as u64,
+            migrants: count_param(p, "migrants", Some(1))?
+        })));
+        definition.replacement_selections.register("simple-adaptive", |p, _| {
+            // a prior of 0 gives a mutation chance of 0 or NaN, and the walk never moves from it
+            let prior = |key: &str| match count_param(p, key, Some(1))? {
+                0 => Err(format!("\"{}\" should be at least 1, got 0", key)),
+                prior => Ok(prior as i64)
+            };
+            Ok(Rc::new(SimpleAdaptive {
+                prior_a: prior("prior a")?,
+                prior_b: prior("prior b")?,
+                learning_rate: number_param(p, "learning rate", None)?
+            }))
+        });
+
+        definition.scorers.register("external", |p, _| {
+            // a string command is split on whitespace, programs or arguments holding spaces need the list form
+            let command = match p.get("command") {
+                Some(Value::String(command)) => command.split_whitespace().map(String::from).collect(),
+                Some(command) => serde_json::from_value::<Vec<String>>(command.clone())
+                    .map_err(|e| format!("\"command\" should be a string or a list of strings: {}", e))?,
+                None => return Err("missing parameter \"command\"".to_string())
+            };
+            Ok(Rc::new(ExternalScorer::new(
+                command,
+                Duration::from_secs_f64(number_param(p, "timeout", Some(10.0))?.max(0.0)),
+                number_param(p, "penalty", Some(0.0))?,
+                count_param(p, "batch size", Some(64))?
+            )?))
+        });
+
+        definition.noises.register("additive", |p, _| Ok(Noise::Additive { sigma: number_param(p, "sigma", None)? }));
+        definition.noises.register("multiplicative", |p, _| Ok(Noise::Multiplicative { sigma: number_param(p, "sigma", None)? }));
+        definition.noises.register("heavy-tailed", |p, _| Ok(Noise::HeavyTailed { sigma: number_param(p, "sigma", None)? }));
+
+        definition.re_evaluations.register("never", |_, _| Ok(ReEvaluation::Never));
+        definition.re_evaluations.register("incumbent", |_, _| Ok(ReEvaluation::Incumbent));
+        definition.re_evaluations.register("average", |p, _| Ok(ReEvaluation::Average { samples: count_param(p, "samples", None)? }));
+        definition.re_evaluations.register("age", |p, _| Ok(ReEvaluation::Age { max_age: count_param(p, "max age", None)? as u64 }));
+
+        definition.environments.register("landscape", |p, _| {
+            let file = text_param(p, "file")?;
+            Ok(Rc::new(DataEnvironment::from_file::<H>(Path::new(file))?))
+        });
+        definition.environments.register("scheduled", |p, d: &Self| {
+            // the schedule applies to the usual environment of the problem unless another one is given
+            let environment = match p.get("environment") {
+                None | Some(Value::Null) => d.environments.build(&d.defaults["environment"], d)?,
+                Some(spec) => d.environments.build(spec, d)?
+            };
+            Ok(Rc::new(ScheduledEnvironment {
+                environment,
+                schedule: Schedule::parse(text_param(p, "schedule")?)?
+            }))
+        });
+
+        definition.initialisations.register("warm-start", |p, d: &Self| {
+            // `fill` is either a flag completing the population with the usual initialisation, or an initialisation
+            let fill = match p.get("fill") {
+                None | Some(Value::Null) | Some(Value::Bool(false)) => None,
+                Some(Value::Bool(true)) => Some(d.initialisations.build(&d.defaults["initialisation"], d)?),
+                Some(spec) => Some(d.initialisations.build(spec, d)?)
+            };
+            let best = match p.get("best") {
+                None | Some(Value::Null) => None,
+                Some(_) => Some(count_param(p, "best", None)?)
+            };
+            Ok(Rc::new(WarmStartGenerator::from_file(Path::new(text_param(p, "file")?), best, fill)?))
+        });
+
+        definition
+    }
+
+    /// Entry `key` of a specification, or of the defaults when the specification has none
+    fn entry<'a>(&'a self, spec: &'a Value, key: &str) -> &'a Value {
+        match spec.get(key) {
+            Some(v) => v,
+            None => &self.defaults[key]
+        }
+    }
+
+    /// Algorithms of `spec`, those without an elitism use `default_elitism`
+    fn algorithms(&self, spec: &Value, default_elitism: &Value) -> Result<Algorithms<V,P,H>, String> {
+        let list = spec.as_array().ok_or("expected a list of algorithms")?;
+        let mut algorithms = Vec::with_capacity(list.len());
+        for (i, algo) in list.iter().enumerate() {
+            let elitism = algo.get("elitism").unwrap_or(&Value::Null);
+            let replacement_selection = algo.get("replacement selection")
+                .ok_or_else(|| format!("algorithm {} has no replacement selection", i))?;
+            algorithms.push(Rc::new(AlgoConfig {
+                elitism: self.elitisms.build(if elitism.is_null() { default_elitism } else { elitism }, self)
+                    .map_err(|e| format!("algorithm {}: {}", i, e))?,
+                replacement_selection: self.replacement_selections.build(replacement_selection, self)
+                    .map_err(|e| format!("algorithm {}: {}", i, e))?
+            }));
+        }
+        if algorithms.is_empty() {
+            return Err("no algorithm to run".to_string());
+        }
+        Ok(algorithms)
+    }
+
+    fn qd_reference(&self, spec: &Value) -> Result<Option<Rc<dyn QDReference<V,P>>>, String> {
+        if spec.is_null() {
+            return Ok(None);
+        }
+        let feature_mapper = self.feature_mappers.build(spec.get("feature mapper").ok_or("missing \"feature mapper\"")?, self)?;
+        let bounds = serde_json::from_value::<(f64, f64)>(spec.get("score bounds").cloned().unwrap_or(Value::Null))
+            .map_err(|e| format!("\"score bounds\" should be the lowest and highest scores: {}", e))?;
+        // scores are normalised by the width of the bounds
+        if bounds.0 >= bounds.1 {
+            return Err(format!("\"score bounds\" should have the lowest score below the highest, got [{}, {}]", bounds.0, bounds.1));
+        }
+        Ok(Some(Rc::new(ReferenceFeatures {
+            feature_mapper,
+            score_bounds: bounds
+        })))
+    }
+
+    pub fn build_config(&self, spec: &Value, common_config: Rc<CommonParameters>) -> Result<MyConfig<V,P,H>, String> {
+        if let Some(map) = spec.as_object() {
+            if let Some(key) = map.keys().find(|k| !SPECIFICATION_KEYS.contains(&k.as_str())) {
+                return Err(format!("unknown entry \"{}\", expected one of {}", key, SPECIFICATION_KEYS.join(", ")));
+            }
+        }
+
+        let constants = serde_json::from_value::<Vec<f64>>(self.entry(spec, "constant hyperparameters").clone())
+            .map_err(|e| format!("constant hyperparameters: {}", e))?;
+        if constants.len() != H::number_of_hyperparameters() {
+            return Err(format!("expected {} constant hyperparameters, got {}", H::number_of_hyperparameters(), constants.len()));
+        }
+
+        let mut scorer = self.scorers.build(self.entry(spec, "scorer"), self)?;
+        // the usual quality diversity reference has the score bounds of the usual scorer
+        let scorer_name = component_name(self.entry(spec, "scorer"));
+        if scorer_name != component_name(&self.defaults["scorer"]) && spec.get("quality diversity reference").is_none()
+            && !self.defaults["quality diversity reference"].is_null() {
+            return Err(format!("the usual quality diversity reference is bounded for the scores of the usual scorer, \
+                                give a \"quality diversity reference\" with the score bounds of scorer \"{}\", or null",
+                               scorer_name.unwrap_or_default()));
+        }
+        let noise = self.entry(spec, "noise");
+        if !noise.is_null() {
+            scorer = Rc::new(NoisyScorer {
+                scorer,
+                noise: self.noises.build(noise, self)?
+            });
+        }
+        if !self.entry(spec, "fitness cache").is_null() {
+            if !noise.is_null() {
+                return Err("a fitness cache would keep the first noisy score of every genotype".to_string());
+            }
+            let capacity = count_param(spec, "fitness cache", None)?;
+            let cache = self.fitness_cache.ok_or("the genomes of this problem cannot be kept in a fitness cache")?;
+            scorer = cache(scorer, capacity);
+        }
+
+        let problem_config = ProblemConfig {
+            random_organism_generator: self.initialisations.build(self.entry(spec, "initialisation"), self)?,
+            problem_instance_generator: self.instance_generators.build(self.entry(spec, "instance generator"), self)?,
+            constant_hyperparameters: H::from_values(&constants),
+            hyperparameter_mapper: self.environments.build(self.entry(spec, "environment"), self)?,
+            scorer,
+            mutator: self.mutators.build(self.entry(spec, "mutator"), self)?,
+            re_evaluation: match self.entry(spec, "re-evaluation") {
+                Value::Null => ReEvaluation::Never,
+                re_evaluation => self.re_evaluations.build(re_evaluation, self)?
+            }
+        };
+
+        // genomes from outside of the problem, such as warm starts, must fit its instances
+        problem_config.random_organism_generator.check(&problem_config.problem_instance_generator.generate_problem())
+            .map_err(|e| format!("initialisation: {}", e))?;
+
+        // the estimators of the specification replace those of the common parameters
+        let common_config = match spec.get("diversity") {
+            None | Some(Value::Null) => common_config,
+            Some(estimators) => {
+                let mut common = (*common_config).clone();
+                common.diversity_estimators = diversity_estimators(estimators).map_err(|e| format!("diversity: {}", e))?;
+                Rc::new(common)
+            }
+        };
+
+        Ok(MyConfig {
+            problem_config: Rc::new(problem_config),
+            common_config,
+            algorithms: self.algorithms(self.entry(spec, "algorithms"), self.entry(spec, "elitism"))?,
+            qd_reference: self.qd_reference(self.entry(spec, "quality diversity reference"))?,
+            observers: Vec::new()
+        })
+    }
+}
+
+impl<V: 'static + Clone + Hash + Eq,P: 'static,F,H> ProblemDefinition<V,P,F,H> {
+    /// Allows the "fitness cache" entry in the specifications of the problem
+    pub fn enable_fitness_cache(&mut self) {
+        self.fitness_cache = Some(|scorer, capacity| Rc::new(CachedScorer::new(scorer, capacity)));
+    }
+}
+
+/// Problem definition with its types erased, as stored in the registry
+pub trait ProblemBuilder {
+    fn build(&self, spec: &Value, common_config: Rc<CommonParameters>) -> Result<Rc<dyn Config>, String>;
+    /// Registered names of every kind of component of the problem
+    fn component_names(&self) -> Vec<(&'static str, Vec<String>)>;
+}
+
+impl<V: 'static + Clone + PartialEq + Diversity + Validate<P> + Serialize + DeserializeOwned,
+    P: 'static,
+    F: 'static + Eq + Clone + Hash + Serialize,
+    H: 'static + SelfAdaptive + Copy> ProblemBuilder for ProblemDefinition<V,P,F,H> {
+    fn build(&self, spec: &Value, common_config: Rc<CommonParameters>) -> Result<Rc<dyn Config>, String> {
+        Ok(Rc::new(self.build_config(spec, common_config)?))
+    }
+
+    fn component_names(&self) -> Vec<(&'static str, Vec<String>)> {
+        vec![("instance generators", self.instance_generators.names()),
+             ("initialisations", self.initialisations.names()),
+             ("scorers", self.scorers.names()),
+             ("noises", self.noises.names()),
+             ("re-evaluations", self.re_evaluations.names()),
+             ("mutators", self.mutators.names()),
+             ("feature mappers", self.feature_mappers.names()),
+             ("environments", self.environments.names()),
+             ("elitisms", self.elitisms.names()),
+             ("replacement selections", self.replacement_selections.names())]
+    }
+}
+
+/// The algorithms usually compared: plain, spatial and MAP-Elites variants, each with both elitisms
+pub fn default_algorithms(feature_mapper: Value, full_feature_mapper: Value, learning_rate: Option<f64>) -> Value {
+    let mut algorithms = Vec::new();
+    for elitism in &["greedy", "metropolis-hastings"] {
+        algorithms.push(json!({"elitism": elitism, "replacement selection": "simple"}));
+        for hyperparameter_mapping in &[false, true] {
+            for mapper in &[Value::Null, feature_mapper.clone()] {
+                algorithms.push(json!({
+                    "elitism": elitism,
+                    "replacement selection": {
+                        "name": "generalized-map-elite",
+                        "parameters": {
+                            "feature mapper": mapper,
+                            "hyperparameter mapping": hyperparameter_mapping,
+                            "spatial dimensions": 1
+                        }
+                    }
+                }));
+            }
+        }
+        algorithms.push(json!({
+            "elitism": elitism,
+            "replacement selection": {"name": "map-elite", "parameters": {"feature mapper": full_feature_mapper}}
+        }));
+    }
+    if let Some(learning_rate) = learning_rate {
+        for elitism in &["greedy", "metropolis-hastings"] {
+            algorithms.push(json!({
+                "elitism": elitism,
+                "replacement selection": {"name": "simple-adaptive", "parameters": {"learning rate": learning_rate}}
+            }));
+        }
+    }
+    Value::Array(algorithms)
+}
+
+//
+// Registry of the problems
+//
+
+pub struct Registry {
+    problems: BTreeMap<String, Rc<dyn ProblemBuilder>>
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            problems: BTreeMap::new()
+        }
+    }
+
+    /// Registry of the travelling salesman, Rastrigin and OneMax problems
+    pub fn builtin() -> Self {
+        let mut registry = Registry::new();
+        registry.register_problem("tsp", Rc::new(tsp_definition()));
+        registry.register_problem("rastrigin", Rc::new(rastrigin_definition()));
+        registry.register_problem("onemax", Rc::new(onemax_definition()));
+        registry
+    }
+
+    pub fn register_problem(&mut self, name: &str, definition: Rc<dyn ProblemBuilder>) {
+        self.problems.insert(name.to_string(), definition);
+    }
+
+    pub fn problem(&self, name: &str) -> Option<Rc<dyn ProblemBuilder>> {
+        self.problems.get(name).cloned()
+    }
+
+    pub fn problem_names(&self) -> Vec<String> {
+        self.problems.keys().cloned().collect()
+    }
+
+    /// Builds the configuration of the problem named by the `"problem"` entry of `spec`
+    pub fn build(&self, spec: &Value, common_config: Rc<CommonParameters>) -> Result<Rc<dyn Config>, String> {
+        let name = spec.get("problem").and_then(|p| p.as_str()).ok_or("the specification names no \"problem\"")?;
+        let definition = self.problem(name).ok_or_else(|| {
+            format!("unknown problem \"{}\", expected one of {}", name, self.problem_names().join(", "))
+        })?;
+        definition.build(spec, common_config).map_err(|e| format!("{}: {}", name, e))
+    }
+}
+
+//
+// Built-in problems
+//
+
+pub fn tsp_definition() -> ProblemDefinition<TSPValue<usize>, TSPInstance<usize>, Vec<usize>, DiscreteHyperparameters> {
+    let cities_mapper = |n: usize| json!({"name": "first-cities", "parameters": {"cities mapped": n}});
+    let mut definition = ProblemDefinition::new(Vec::new(), json!({
+        "instance generator": {"name": "random-euclidean", "parameters": {"cities": 50, "dimensions": 2}},
+        "initialisation": "identity",
+        "scorer": "tsp",
+        "mutator": "tsp",
+        "environment": "spatial",
+        "constant hyperparameters": [0.5],
+        "elitism": "greedy",
+        "algorithms": default_algorithms(cities_mapper(1), cities_mapper(2), Some(0.0)),
+        "quality diversity reference": {"feature mapper": cities_mapper(2), "score bounds": [0.0, 1.0]}
+    }));
+
+    definition.instance_generators.register("random-euclidean", |p, _| Ok(Rc::new(SimpleTSPInstanceGenerator {
+        number_of_cities: count_param(p, "cities", Some(50))?,
+        number_of_dimensions: count_param(p, "dimensions", Some(2))?
+    })));
+    definition.initialisations.register("identity", |_, _| Ok(Rc::new(TSPRandomSolution{})));
+    definition.initialisations.register("shuffle", |_, _| Ok(Rc::new(TSPShuffleGenerator{})));
+    definition.initialisations.register("uniform", |_, _| Ok(Rc::new(TSPShuffleGenerator{})));
+    definition.initialisations.register("nearest-neighbour", |_, _| Ok(Rc::new(TSPNearestNeighbourGenerator{})));
+    definition.scorers.register("tsp", |p, _| threaded_scorer(TSPScorer{}, p));
+    definition.scorers.register("moving-cities", |p, _| dynamic_scorer(MovingCities::new(
+        count_param(p, "moved cities", Some(5))?,
+        number_param(p, "step", Some(5.0))?,
+        count_param(p, "relocated cities", Some(0))?
+    ), p));
+    definition.enable_fitness_cache();
+    definition.mutators.register("tsp", |_, _| Ok(Rc::new(TSPMutator{})));
+    definition.feature_mappers.register("first-cities", |p, _| Ok(Rc::new(TSPFeatureMapper {
+        number_cities_mapped: count_param(p, "cities mapped", None)?
+    })));
+    definition.environments.register("spatial", |p, _| Ok(Rc::new(SpatialMapper {
+        number_of_additional_dimensions: count_param(p, "additional dimensions", Some(0))?
+    })));
+    definition
+}
+
+pub fn rastrigin_definition() -> ProblemDefinition<RastriginValue, Rastrigin, RastriginFeature, ContinuousHyperparameters> {
+    let mutation_size = 0.5;
+    let bins = |resolution: usize, dimensions: usize| json!({
+        "name": "rastrigin-bins",
+        "parameters": {"resolution": resolution, "dimensions": dimensions, "max abs value": 5.0}
+    });
+    let mut definition = ProblemDefinition::new(RastriginFeature { bin_coords: vec![] }, json!({
+        "instance generator": {"name": "rastrigin", "parameters": {"a": 10.0, "b": 20.0, "max abs value": 5.0, "dimensions": 10}},
+        "initialisation": "corner",
+        "scorer": "regularized-rastrigin",
+        "mutator": "rastrigin",
+        "environment": {"name": "continuous-spatial", "parameters": {"mean mutation size": mutation_size}},
+        "constant hyperparameters": [0.5, mutation_size],
+        "elitism": "greedy",
+        // usual 1/sqrt(n) learning rate for the 10 dimensions of the problem
+        "algorithms": default_algorithms(bins(10, 1), bins(7, 4), Some(1.0/10.0_f64.sqrt())),
+        // every term of the regularized Rastrigin is below a + max_abs_val^2 + 1
+        "quality diversity reference": {"feature mapper": bins(7, 4), "score bounds": [0.0, 36.0]}
+    }));
+
+    definition.instance_generators.register("rastrigin", |p, _| Ok(Rc::new(Rastrigin {
+        a: number_param(p, "a", Some(10.0))?,
+        b: number_param(p, "b", Some(20.0))?,
+        max_abs_val: number_param(p, "max abs value", Some(5.0))?,
+        nb_dimensions: count_param(p, "dimensions", Some(10))?
+    })));
+    definition.initialisations.register("corner", |_, _| Ok(Rc::new(RastriginGenerator{})));
+    definition.initialisations.register("uniform", |_, _| Ok(Rc::new(RastriginUniformGenerator{})));
+    definition.initialisations.register("latin-hypercube", |_, _| Ok(Rc::new(RastriginLatinHypercubeGenerator{})));
+    definition.scorers.register("regularized-rastrigin", |p, _| threaded_scorer(RegRastriginScorer{}, p));
+    definition.scorers.register("moving-peaks", |p, _| dynamic_scorer(MovingPeaks::new(
+        count_param(p, "peaks", Some(10))?,
+        number_param(p, "shift severity", Some(0.5))?,
+        number_param(p, "height severity", Some(7.0))?,
+        number_param(p, "width severity", Some(1.0))?
+    ), p));
+    definition.mutators.register("rastrigin", |_, _| Ok(Rc::new(RastriginMutator{})));
+    definition.feature_mappers.register("rastrigin-bins", |p, _| Ok(Rc::new(RastriginMapper {
+        resolution: count_param(p, "resolution", None)?,
+        number_of_dimensions: count_param(p, "dimensions", None)?,
+        max_abs_val: number_param(p, "max abs value", Some(5.0))?
+    })));
+    definition.environments.register("continuous-spatial", |p, _| Ok(Rc::new(ContinuousSpatialMapper {
+        mean_mutation_size: number_param(p, "mean mutation size", None)?
+    })));
+    definition
+}
+
+pub fn onemax_definition() -> ProblemDefinition<OneMaxValue, OneMax, Vec<u8>, DiscreteHyperparameters> {
+    let bits = json!({"name": "onemax-bits", "parameters": {"octets": 1}});
+    let mut definition = ProblemDefinition::new(Vec::new(), json!({
+        "instance generator": {"name": "onemax", "parameters": {"size": 100}},
+        "initialisation": "zeroes",
+        "scorer": "onemax",
+        "mutator": "onemax",
+        "environment": "spatial",
+        "constant hyperparameters": [0.5],
+        "elitism": "greedy",
+        "algorithms": default_algorithms(bits.clone(), bits.clone(), None),
+        "quality diversity reference": {"feature mapper": bits, "score bounds": [0.0, 1.0]}
+    }));
+
+    definition.instance_generators.register("onemax", |p, _| Ok(Rc::new(OneMax {
+        size: count_param(p, "size", Some(100))?
+    })));
+    definition.initialisations.register("zeroes", |_, _| Ok(Rc::new(OneMaxGenerator{})));
+    definition.initialisations.register("uniform", |_, _| Ok(Rc::new(OneMaxRandomGenerator{})));
+    definition.scorers.register("onemax", |p, _| threaded_scorer(OneMaxScorer{}, p));
+    definition.scorers.register("shifting-target", |p, _| dynamic_scorer(ShiftingTarget::new(count_param(p, "bits", Some(8))?), p));
+    definition.enable_fitness_cache();
+    definition.mutators.register("onemax", |_, _| Ok(Rc::new(OneMaxMutator{})));
+    definition.feature_mappers.register("onemax-bits", |p, _| Ok(Rc::new(OneMaxMapper {
+        number_of_octets: count_param(p, "octets", None)?
+    })));
+    definition.environments.register("spatial", |p, _| Ok(Rc::new(SpatialMapper {
+        number_of_additional_dimensions: count_param(p, "additional dimensions", Some(0))?
+    })));
+    definition
+}
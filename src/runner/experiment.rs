@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::metadata::{self, ResultsMetadata};
+use crate::progress::{ProgressMode, ProgressReporter};
+use crate::runner::Config;
+use crate::runner::iteration::Iteration;
+use crate::sinks::{SinkKind, IterationSink, create_sinks};
+
+/// Where and how the results of an experiment are written
+#[derive(Clone, Debug)]
+pub struct ExperimentOptions {
+    /// Results files are named `<file_prefix>_<problem>_results.*`, the other files of the runs start
+    /// with `<file_prefix>_<problem>` as well. A problem given several times gets its configuration index
+    /// appended from its second configuration on.
+    pub file_prefix: String,
+    pub sinks: Vec<SinkKind>,
+    /// Database of the SQLite sink, shared by all the problems
+    pub database: PathBuf,
+    pub progress: ProgressMode
+}
+
+impl Default for ExperimentOptions {
+    fn default() -> Self {
+        ExperimentOptions {
+            file_prefix: "final".to_string(),
+            sinks: vec![SinkKind::Csv],
+            database: PathBuf::from("final_results.sqlite"),
+            progress: ProgressMode::Human
+        }
+    }
+}
+
+/// Runs every repetition of every algorithm of each configuration, writing the statistics of
+/// every logged iteration to the sinks and the metadata next to the results
+pub fn run_experiments(configs: &[Rc<dyn Config>], options: &ExperimentOptions) -> Result<(), String> {
+    let mut total_number_repetitions = 0;
+
+    for conf in configs {
+        let common_conf = conf.get_common_config();
+        total_number_repetitions += common_conf.number_of_repetitions*conf.number_of_algorithms() as u64;
+    }
+    let progress = Rc::new(RefCell::new(ProgressReporter::new(options.progress, total_number_repetitions)));
+
+    progress.borrow().info("Configs:");
+    for (i,cfg) in configs.iter().enumerate() {
+        progress.borrow().info(&format!("{}: {}", i, cfg.get_problem_name()));
+    }
+    progress.borrow().info("");
+
+    let mut all_params = Vec::with_capacity(configs.len());
+    for cfg in configs {
+        let p_params = cfg.get_problem_config_parameters()
+            .map_err(|e| format!("Invalid configuration for {}: {}", cfg.get_problem_name(), e))?;
+        all_params.push(p_params);
+    }
+
+    progress.borrow().info(&format!("Computing {} runs", total_number_repetitions));
+
+    let environment = metadata::environment_info();
+
+    for (config_index, (config, p_params)) in configs.iter().zip(all_params).enumerate() {
+        progress.borrow().info(&format!("Config n°{}:\n{:?}", config_index ,p_params));
+
+        let mut problem_name = config.get_problem_name().replace("/","_");
+        if configs[..config_index].iter().any(|c| c.get_problem_name().replace("/","_") == problem_name) {
+            problem_name = format!("{}_{}", problem_name, config_index);
+        }
+        progress.borrow_mut().start_problem(&problem_name);
+
+        let results_prefix = format!("{}_{}", options.file_prefix, problem_name);
+        let results_path = PathBuf::from(format!("{}_results.csv", results_prefix));
+
+        let runs = config.get_common_config().number_of_repetitions * config.number_of_algorithms() as u64;
+        let mut results_metadata = ResultsMetadata::new(&results_path, p_params, environment.clone(), runs);
+        match results_metadata.write() {
+            Ok(()) => progress.borrow().info(&format!("Metadata written to {}", results_metadata.path.display())),
+            Err(e) => eprintln!("Metadata could not be written: {}", e)
+        }
+
+        let mut sinks: Vec<Box<dyn IterationSink>> = create_sinks(&options.sinks, &results_path, &options.database);
+        for sink in sinks.iter_mut() {
+            sink.start(&problem_name, &Iteration::header(), &results_metadata.to_json())
+                .map_err(|e| format!("Could not open output: {}", e))?;
+        }
+        for it in config.execute(&results_prefix, progress.clone()) {
+            for iteration in it {
+                let row = iteration.row();
+                for sink in sinks.iter_mut() {
+                    sink.write_row(&row)?;
+                }
+            }
+            for sink in sinks.iter_mut() {
+                sink.end_run()?;
+            }
+            if let Err(e) = results_metadata.run_completed() {
+                eprintln!("Metadata could not be updated: {}", e);
+            }
+        }
+        if let Err(e) = results_metadata.finish() {
+            eprintln!("Metadata could not be updated: {}", e);
+        }
+        for sink in sinks.iter_mut() {
+            if let Err(e) = sink.finish(&results_metadata.to_json()) {
+                eprintln!("Could not close output: {}", e);
+            }
+        }
+    }
+
+    progress.borrow().finish();
+    Ok(())
+}
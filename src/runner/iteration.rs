@@ -0,0 +1,98 @@
+use std::vec::Vec;
+use std::time::Duration;
+use serde_json::Value;
+use crate::statistics::{HyperparameterStats, OperatorStats, DiversityStats};
+use crate::quality_diversity::QDStats;
+use crate::scoring::cache::CacheStats;
+use crate::scoring::noise::TrueScoreStats;
+use crate::problems::dynamic::DynamicStats;
+
+#[derive(Clone, Debug)]
+pub struct Iteration {
+    pub iteration: u64,
+    pub repetition: u64,
+    pub index_algo: usize,
+    pub duration: Duration,
+    pub sum_scores: f64,
+    pub min_score: f64,
+    pub max_score: f64,
+    /// Highest score since the start of the run, including the iterations that were not logged.
+    /// With a noisy scorer, highest true score of the organism with the highest noisy score
+    pub best_so_far: f64,
+    pub mean_score: f64,
+    pub median_score: f64,
+    pub number_of_organisms: usize,
+    pub pop_score_variance: f64,
+    pub generations: f64,
+    /// Genomes scored by the solver since the start of the run, cache hits excluded
+    pub evaluations: u64,
+    pub diversity: Option<DiversityStats>,
+    pub quality_diversity: Option<QDStats>,
+    pub population_hyperparameters: Option<HyperparameterStats>,
+    pub offspring_hyperparameters: Option<HyperparameterStats>,
+    pub operator_stats: Option<OperatorStats>,
+    /// For runs with a fitness cache
+    pub fitness_cache: Option<CacheStats>,
+    /// For runs with a noisy scorer
+    pub true_scores: Option<TrueScoreStats>,
+    /// For runs on a dynamic problem
+    pub dynamic_problem: Option<DynamicStats>
+}
+
+impl Iteration {
+    pub fn header() -> Vec<String> {
+        let mut header: Vec<String> = [
+            "repetition",
+            "algorithm index",
+            "iteration",
+            "duration (ns)",
+            "sum score",
+            "min score",
+            "max score",
+            "best score so far",
+            "mean score",
+            "median score",
+            "number of organisms",
+            "variance",
+            "generations",
+            "evaluations"
+        ].iter().map(|s| s.to_string()).collect();
+        header.extend(DiversityStats::header());
+        header.extend(QDStats::header());
+        header.extend(HyperparameterStats::header("population"));
+        header.extend(HyperparameterStats::header("accepted offspring"));
+        header.extend(OperatorStats::header());
+        header.extend(CacheStats::header());
+        header.extend(TrueScoreStats::header());
+        header.extend(DynamicStats::header());
+        header
+    }
+    /// Values of the columns of `header`, `null` for the statistics not computed at this iteration
+    pub fn row(&self) -> Vec<Value> {
+        let mut row = vec![
+            self.repetition.into(),
+            self.index_algo.into(),
+            self.iteration.into(),
+            (self.duration.as_nanos() as u64).into(),
+            self.sum_scores.into(),
+            self.min_score.into(),
+            self.max_score.into(),
+            self.best_so_far.into(),
+            self.mean_score.into(),
+            self.median_score.into(),
+            self.number_of_organisms.into(),
+            self.pop_score_variance.into(),
+            self.generations.into(),
+            self.evaluations.into()
+        ];
+        row.extend(DiversityStats::row(&self.diversity));
+        row.extend(QDStats::row(&self.quality_diversity));
+        row.extend(HyperparameterStats::row(&self.population_hyperparameters));
+        row.extend(HyperparameterStats::row(&self.offspring_hyperparameters));
+        row.extend(OperatorStats::row(&self.operator_stats));
+        row.extend(CacheStats::row(&self.fitness_cache));
+        row.extend(TrueScoreStats::row(&self.true_scores));
+        row.extend(DynamicStats::row(&self.dynamic_problem));
+        row
+    }
+}
@@ -0,0 +1,290 @@
+use std::vec::Vec;
+use serde_json::{json, Map};
+use std::rc::Rc;
+use crate::common::Parametrized;
+use crate::common::{int_param,component_param};
+use crate::algorithm::config::ProblemConfig;
+use crate::algorithm::selection::Elitism;
+use crate::algorithm::algorithm::ReplacementSelection;
+use serde::Serialize;
+use crate::problems::Hyperparameter;
+use crate::organism::Diversity;
+use crate::statistics::{OperatorStats, DiversityEstimator, LoggingPolicy};
+use crate::snapshot::ImageFormat;
+use crate::progress::ProgressReporter;
+use std::cell::RefCell;
+use crate::quality_diversity::QDReference;
+use crate::problems::dynamic::DynamicTracker;
+use crate::scoring::counted::CountedScorer;
+
+mod iteration;
+mod state;
+pub mod experiment;
+pub mod observer;
+
+pub use self::iteration::Iteration;
+pub use self::experiment::{ExperimentOptions, run_experiments};
+use self::state::AlgorithmState;
+use self::observer::{Observer, ObserverFactory, RunInfo, GenealogyRecorder, SnapshotObserver, PopulationExport, EarlyStopping};
+
+//
+// Experiment configurations: a problem, the algorithms compared on it and the parameters shared by their runs
+//
+
+pub trait Config {
+    fn get_problem_config_parameters(&self) -> Result<serde_json::Value, String>;
+    fn get_common_config(&self) -> CommonParameters;
+    fn get_problem_name(&self) -> String;
+    fn number_of_algorithms(&self) -> usize;
+    /// Runs of the configuration, the files they write are named after `results_prefix`
+    fn execute(&self, results_prefix: &str, progress: Rc<RefCell<ProgressReporter>>) -> Box<dyn Iterator<Item=Box<dyn Iterator<Item=Iteration>>>>;
+}
+
+
+#[derive(Clone)]
+pub struct AlgoConfig<V,P,H> {
+    pub elitism: Rc<dyn Elitism>,
+    pub replacement_selection: Rc<dyn ReplacementSelection<V,P,H>>
+}
+
+
+#[derive(Clone)]
+pub struct CommonParameters {
+    pub population_size: usize,
+    pub number_of_repetitions: u64,
+    pub number_of_iterations: u64,
+    pub genome_stats_gap: u64,
+    pub diversity_estimators: Vec<DiversityEstimator>,
+    /// Log the genealogy of every run, organisms carry no lineage when disabled
+    pub track_lineage: bool,
+    /// Write the organisms kept by every solver at the end of its run
+    pub export_final_population: bool,
+    /// Draw the grid of spatial algorithms every `snapshot_gap` iterations
+    pub snapshot_gap: Option<u64>,
+    pub snapshot_format: ImageFormat,
+    /// Also assemble the snapshots of every run as animated GIFs
+    pub snapshot_animation: bool,
+    /// Iterations written to the results, independently of `genome_stats_gap`
+    pub logging_policy: LoggingPolicy,
+    /// Runs stop once their best score reaches this score
+    pub stop_score: Option<f64>,
+    /// Runs stop when their best score has not improved for this many iterations
+    pub patience: Option<u64>
+}
+
+impl Parametrized for CommonParameters {
+    fn parameters(&self) -> serde_json::Value {
+        let mut hm = Map::new();
+        hm.insert("population size".to_string(),int_param(self.population_size as i64));
+        hm.insert("total repetitions".to_string(), int_param(self.number_of_repetitions as i64));
+        hm.insert("iterations per run".to_string(), int_param(self.number_of_iterations as i64));
+        hm.insert("diversity estimators".to_string(),
+                  serde_json::Value::Array(self.diversity_estimators.iter().map(|d| d.parameters()).collect()));
+        hm.insert("track lineage".to_string(), self.track_lineage.into());
+        hm.insert("export final population".to_string(), self.export_final_population.into());
+        hm.insert("snapshot gap".to_string(), self.snapshot_gap.into());
+        hm.insert("logging policy".to_string(), self.logging_policy.parameters());
+        hm.insert("stop score".to_string(), self.stop_score.into());
+        hm.insert("patience".to_string(), self.patience.into());
+        serde_json::Value::Object(hm)
+    }
+}
+
+
+
+
+
+
+pub struct MyConfig<V,P,H> {
+    pub problem_config: Rc<ProblemConfig<V,P,H>>,
+    pub common_config: Rc<CommonParameters>,
+    pub algorithms: Vec<Rc<AlgoConfig<V,P,H>>>,
+    /// Feature space used to compare the quality-diversity of all the algorithms, if any
+    pub qd_reference: Option<Rc<dyn QDReference<V,P>>>,
+    /// Observers added to every run, after those enabled by the common parameters
+    pub observers: Vec<ObserverFactory<V,P>>
+}
+impl<V,P,H> Clone for MyConfig<V,P,H> {
+    fn clone(&self) -> Self {
+        MyConfig {
+            problem_config: self.problem_config.clone(),
+            common_config: self.common_config.clone(),
+            algorithms: self.algorithms.clone(),
+            qd_reference: self.qd_reference.clone(),
+            observers: self.observers.clone()
+        }
+    }
+}
+
+
+pub(crate) struct MyConfigIt<V,P,H> {
+    pub(crate) my_config: Rc<MyConfig<V,P,H>>,
+    pub(crate) instance: Rc<P>,
+    pub(crate) repetitions: u64,
+    pub(crate) index_algo: usize,
+    pub(crate) results_prefix: String,
+    pub(crate) progress: Rc<RefCell<ProgressReporter>>
+}
+
+impl<V,P,H> Clone for MyConfigIt<V,P,H> {
+    fn clone(&self) -> Self {
+        MyConfigIt {
+            my_config: self.my_config.clone(),
+            instance: self.instance.clone(),
+            repetitions: self.repetitions,
+            index_algo: self.index_algo,
+            results_prefix: self.results_prefix.clone(),
+            progress: self.progress.clone()
+        }
+    }
+}
+
+
+impl<V: 'static + Diversity + Serialize,P: 'static,H> MyConfig<V,P,H> {
+    /// Observers of a run: genealogy, snapshots, population export and early stopping when enabled, then the custom ones
+    fn run_observers(&self, info: &RunInfo) -> Vec<Box<dyn Observer<V,P>>> {
+        let common = &self.common_config;
+        let mut observers: Vec<Box<dyn Observer<V,P>>> = Vec::new();
+        if common.track_lineage {
+            observers.push(Box::new(GenealogyRecorder::new()));
+        }
+        if let Some(gap) = common.snapshot_gap {
+            observers.push(Box::new(SnapshotObserver::new(gap, common.snapshot_format, common.snapshot_animation)));
+        }
+        if common.export_final_population {
+            observers.push(Box::new(PopulationExport{}));
+        }
+        if common.stop_score.is_some() || common.patience.is_some() {
+            observers.push(Box::new(EarlyStopping::new(common.stop_score, common.patience)));
+        }
+        observers.extend(self.observers.iter().map(|factory| factory(info)));
+        observers
+    }
+}
+
+impl<V: 'static + Diversity + Serialize,P: 'static,H: 'static + Clone> Iterator for MyConfigIt<V,P,H> {
+    type Item = Box<dyn Iterator<Item=Iteration>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.repetitions >= self.my_config.common_config.number_of_repetitions {
+            return None;
+        }
+        let algo = self.my_config.algorithms.get(self.index_algo).unwrap();
+
+        // the solver scores through a counter of its own, the run statistics are not evaluations
+        let evaluations = Rc::new(CountedScorer::new(self.my_config.problem_config.scorer.clone()));
+        let problem_config = ProblemConfig {
+            scorer: evaluations.clone(),
+            ..self.my_config.problem_config.as_ref().clone()
+        };
+        let updatable_solver = algo.replacement_selection.initialize_solver(
+            self.my_config.common_config.population_size,
+            self.instance.clone(),
+            algo.elitism.clone(),
+            Rc::new(problem_config)
+        );
+
+        let run_info = RunInfo {
+            problem: self.my_config.problem_config.problem_instance_generator.name(),
+            repetition: self.repetitions + 1,
+            algorithm_index: self.index_algo,
+            algorithm: format!("{} ({})", algo.replacement_selection.name(), algo.elitism.name()),
+            number_of_iterations: self.my_config.common_config.number_of_iterations,
+            results_prefix: self.results_prefix.clone()
+        };
+
+        let ex = AlgorithmState {
+            my_config_it: Rc::new(self.clone()),
+            updatable_solver,
+            evaluations,
+            observers: self.my_config.run_observers(&run_info),
+            run_info,
+            stopped: false,
+            i: 0,
+            offspring: 0,
+            accepted_hyperparameters: Vec::new(),
+            operator_stats: OperatorStats::default(),
+            qd_archive: self.my_config.qd_reference.as_ref().map(|r| r.new_archive(&self.instance)),
+            log_schedule: self.my_config.common_config.logging_policy.schedule(),
+            best_so_far: f64::NEG_INFINITY,
+            genome_stats_due: false,
+            dynamic_problem: if self.my_config.problem_config.scorer.is_dynamic() { Some(DynamicTracker::default()) } else { None }
+        };
+
+        if self.index_algo >= self.my_config.algorithms.len()-1 {
+            self.index_algo = 0;
+            self.repetitions += 1;
+            self.instance = Rc::new(self.my_config.problem_config.problem_instance_generator.generate_problem());
+        }
+        else {
+            self.index_algo += 1;
+        }
+        Some(Box::new(ex))
+    }
+}
+
+impl<V: 'static + Diversity + Serialize,P: 'static,H: 'static + Clone + Hyperparameter> Config for MyConfig<V,P,H> {
+    fn get_problem_config_parameters(&self) -> Result<serde_json::Value, String> {
+        let common_params = self.common_config.parameters();
+        let mut algo_configs = Vec::new();
+
+        let instance = self.problem_config.problem_instance_generator.generate_problem();
+
+        for (i,algo) in self.algorithms.iter().enumerate() {
+            let run_params = algo.replacement_selection.run_parameters(
+                self.common_config.population_size,
+                &instance,
+                self.problem_config.as_ref()
+            ).map_err(|e| format!("algorithm {} ({}): {}", i, algo.replacement_selection.name(), e))?;
+
+            let mut algo_config = Map::new();
+            algo_config.insert("algorithm name".to_string(), algo.replacement_selection.name().into());
+            algo_config.insert("algorithm index".to_string(), int_param(i as i64));
+            algo_config.insert("elitism".to_string(), serde_json::Value::String(algo.elitism.name()));
+            algo_config.insert("algorithm config".to_string(), run_params);
+            algo_configs.push(serde_json::Value::Object(algo_config));
+        }
+
+        let mut final_config = Map::new();
+
+        final_config.insert("common".to_string(), common_params);
+        final_config.insert("problem".to_string(), json!({
+            "instance generator": component_param(self.problem_config.problem_instance_generator.as_ref()),
+            "scorer": component_param(self.problem_config.scorer.as_ref()),
+            "mutator": component_param(self.problem_config.mutator.as_ref()),
+            "re-evaluation": component_param(&self.problem_config.re_evaluation),
+            "constant hyperparameters": self.problem_config.constant_hyperparameters.to_values()
+        }));
+        final_config.insert("initialisation".to_string(), component_param(self.problem_config.random_organism_generator.as_ref()));
+        final_config.insert("environment".to_string(), component_param(self.problem_config.hyperparameter_mapper.as_ref()));
+        if let Some(reference) = &self.qd_reference {
+            final_config.insert("quality diversity reference".to_string(), component_param(reference.as_ref()));
+        }
+        final_config.insert("algorithms".to_string(), serde_json::Value::Array(algo_configs));
+
+        Ok(serde_json::Value::Object(final_config))
+    }
+
+    fn get_common_config(&self) -> CommonParameters {
+        self.common_config.as_ref().clone()
+    }
+
+    fn get_problem_name(&self) -> String {
+        self.problem_config.problem_instance_generator.name()
+    }
+
+    fn number_of_algorithms(&self) -> usize {
+        self.algorithms.len()
+    }
+
+    fn execute(&self, results_prefix: &str, progress: Rc<RefCell<ProgressReporter>>) -> Box<dyn Iterator<Item=Box<dyn Iterator<Item=Iteration>>>> {
+        Box::new(MyConfigIt{
+            my_config: Rc::new(self.clone()),
+            instance: Rc::new(self.problem_config.problem_instance_generator.generate_problem()),
+            repetitions: 0,
+            index_algo: 0,
+            results_prefix: results_prefix.to_string(),
+            progress
+        })
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::algorithm::algorithm::{UpdatableSolver, UpdateReport, ArchiveEntry};
+use crate::organism::Metric;
+use crate::organism::lineage::Lineage;
+use crate::scoring::Scorer;
+use crate::snapshot::{Heatmap, ImageFormat, SnapshotRecorder};
+
+//
+// Observers of the solver loop, notified of what happens during every run
+//
+
+/// Identity of a run
+#[derive(Clone, Debug)]
+pub struct RunInfo {
+    pub problem: String,
+    /// Repetition of the run, starting at 1
+    pub repetition: u64,
+    pub algorithm_index: usize,
+    pub algorithm: String,
+    pub number_of_iterations: u64,
+    /// Results files of the run are named `<results_prefix>_results.*`
+    pub results_prefix: String
+}
+
+impl RunInfo {
+    /// Path of a `kind` of file written for the run, next to the results of the experiment
+    pub fn run_file(&self, kind: &str, extension: &str) -> PathBuf {
+        PathBuf::from(format!("{}_{}_rep{}_algo{}{}", self.results_prefix, kind, self.repetition, self.algorithm_index, extension))
+    }
+}
+
+/// What an observer can see of the run when it is notified
+pub struct RunContext<'a, V, P> {
+    pub info: &'a RunInfo,
+    pub iteration: u64,
+    pub scorer: &'a dyn Scorer<V,P>,
+    pub instance: &'a P
+}
+
+impl<'a, V, P> RunContext<'a, V, P> {
+    /// Score of an organism, computed if the solver did not cache it
+    pub fn score_of(&self, entry: &ArchiveEntry<V>) -> f64 {
+        entry.organism.get_score().unwrap_or_else(|| entry.organism.only_score(self.scorer, self.instance))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RunControl {
+    Continue,
+    /// End the run after the current iteration
+    Stop
+}
+
+/// Receives the events of a run, every method does nothing by default
+pub trait Observer<V,P> {
+    fn run_started(&mut self, _context: &RunContext<V,P>, _solver: &mut dyn UpdatableSolver<V>) {}
+    /// Before the solver is updated
+    fn iteration_started(&mut self, _context: &RunContext<V,P>, _solver: &dyn UpdatableSolver<V>) {}
+    fn offspring_created(&mut self, _context: &RunContext<V,P>, _report: &UpdateReport) {}
+    fn offspring_accepted(&mut self, _context: &RunContext<V,P>, _report: &UpdateReport) {}
+    fn offspring_rejected(&mut self, _context: &RunContext<V,P>, _report: &UpdateReport) {}
+    /// An accepted offspring went to a niche no organism occupied
+    fn niche_discovered(&mut self, _context: &RunContext<V,P>, _report: &UpdateReport) {}
+    fn new_best(&mut self, _context: &RunContext<V,P>, _score: f64) {}
+    /// After the solver is updated, the run stops after this iteration if any observer asks to
+    fn iteration_finished(&mut self, _context: &RunContext<V,P>, _best_so_far: f64) -> RunControl {
+        RunControl::Continue
+    }
+    fn run_finished(&mut self, _context: &RunContext<V,P>, _solver: &mut dyn UpdatableSolver<V>) {}
+}
+
+/// Creates the observer of every run
+pub type ObserverFactory<V,P> = Rc<dyn Fn(&RunInfo) -> Box<dyn Observer<V,P>>>;
+
+//
+// Genealogy
+//
+
+/// Logs the birth of every organism and the survivors of the run, organisms carry no lineage otherwise
+pub struct GenealogyRecorder {
+    writer: Option<csv::Writer<File>>
+}
+
+impl GenealogyRecorder {
+    pub fn new() -> Self {
+        GenealogyRecorder {
+            writer: None
+        }
+    }
+}
+
+impl Default for GenealogyRecorder {
+    fn default() -> Self {
+        GenealogyRecorder::new()
+    }
+}
+
+impl<V,P> Observer<V,P> for GenealogyRecorder {
+    /// Gives a lineage to the initial population and logs it
+    fn run_started(&mut self, context: &RunContext<V,P>, solver: &mut dyn UpdatableSolver<V>) {
+        let file_name = context.info.run_file("genealogy", ".csv");
+        let mut writer = csv::Writer::from_path(&file_name).expect("could not create genealogy log");
+        writer.write_record(Lineage::header()).expect("could not write genealogy log");
+
+        for org in solver.organisms_mut() {
+            org.enable_lineage();
+            if let Some(lineage) = org.get_lineage() {
+                writer.write_record(lineage.row("birth")).expect("could not write genealogy log");
+            }
+        }
+
+        self.writer = Some(writer);
+    }
+
+    fn offspring_accepted(&mut self, _context: &RunContext<V,P>, report: &UpdateReport) {
+        if let (Some(writer), Some(lineage)) = (&mut self.writer, &report.offspring_lineage) {
+            writer.write_record(lineage.row("birth")).expect("could not write genealogy log");
+        }
+    }
+
+    /// Logs the organisms alive at the end of the run
+    fn run_finished(&mut self, _context: &RunContext<V,P>, solver: &mut dyn UpdatableSolver<V>) {
+        if let Some(mut writer) = self.writer.take() {
+            for org in solver.organisms_mut() {
+                if let Some(lineage) = org.get_lineage() {
+                    writer.write_record(lineage.row("survivor")).expect("could not write genealogy log");
+                }
+            }
+            writer.flush().expect("could not write genealogy log");
+        }
+    }
+}
+
+//
+// Snapshots
+//
+
+/// Draws the best score, the number of features and the genetic distance to the best organism
+/// of every cell every `gap` iterations, for grids of one or two dimensions
+pub struct SnapshotObserver {
+    pub gap: u64,
+    pub format: ImageFormat,
+    /// Also assemble the snapshots of the run as animated GIFs
+    pub animation: bool,
+    /// Created on the first snapshot of a grid that can be drawn
+    recorder: Option<SnapshotRecorder>
+}
+
+impl SnapshotObserver {
+    pub fn new(gap: u64, format: ImageFormat, animation: bool) -> Self {
+        SnapshotObserver {
+            gap,
+            format,
+            animation,
+            recorder: None
+        }
+    }
+}
+
+impl<V: Metric,P> Observer<V,P> for SnapshotObserver {
+    fn iteration_started(&mut self, context: &RunContext<V,P>, solver: &dyn UpdatableSolver<V>) {
+        if !context.iteration.is_multiple_of(self.gap) {
+            return;
+        }
+        let entries = solver.archive();
+
+        let dimensions = match entries.first().and_then(|e| e.cell.as_ref()) {
+            Some(cell) if cell.len() == 1 || cell.len() == 2 => cell.len(),
+            _ => return
+        };
+        // a line of cells is wrapped into a near-square raster, it would be drawn a pixel high otherwise
+        let row_length = match dimensions {
+            1 => {
+                let length = entries.iter().map(|e| e.cell.as_ref().unwrap()[0]).max().unwrap() + 1;
+                (length as f64).sqrt().ceil() as usize
+            },
+            _ => usize::MAX
+        };
+        let coordinates = |e: &ArchiveEntry<V>| {
+            let cell = e.cell.as_ref().unwrap();
+            if dimensions == 2 { (cell[0], cell[1]) } else { (cell[0] % row_length, cell[0] / row_length) }
+        };
+        let width = entries.iter().map(|e| coordinates(e).0).max().unwrap() + 1;
+        let height = entries.iter().map(|e| coordinates(e).1).max().unwrap() + 1;
+
+        let scores: Vec<f64> = entries.iter().map(|e| context.score_of(e)).collect();
+
+        // best organism of every cell, and of the whole grid
+        let mut best_in_cell: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut features = Heatmap::new(width, height);
+        for (i, e) in entries.iter().enumerate() {
+            let (x, y) = coordinates(e);
+            features.set(x, y, features.values[y * width + x].unwrap_or(0.0) + 1.0);
+
+            let best = best_in_cell.entry((x, y)).or_insert(i);
+            if scores[i] > scores[*best] {
+                *best = i;
+            }
+        }
+        let reference = best_in_cell.values().cloned().max_by(|&a, &b| scores[a].partial_cmp(&scores[b]).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut best_score = Heatmap::new(width, height);
+        let mut distance = Heatmap::new(width, height);
+        for (&(x, y), &i) in &best_in_cell {
+            best_score.set(x, y, scores[i]);
+            if let Some(r) = reference {
+                distance.set(x, y, entries[i].organism.distance_to(entries[r].organism));
+            }
+        }
+
+        if self.recorder.is_none() {
+            self.recorder = Some(SnapshotRecorder::new(context.info.run_file("snapshots", ""), self.format, self.animation)
+                .expect("could not create snapshot folder"));
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(context.iteration, vec![
+                ("best score", best_score),
+                ("features per cell", features),
+                ("distance to best", distance)
+            ]).expect("could not write snapshot");
+        }
+    }
+
+    fn run_finished(&mut self, _context: &RunContext<V,P>, _solver: &mut dyn UpdatableSolver<V>) {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish().expect("could not write snapshot animation");
+        }
+    }
+}
+
+//
+// Final population
+//
+
+/// Writes the organisms kept by the solver at the end of the run as JSON Lines, one organism per line
+pub struct PopulationExport {}
+
+impl<V: Serialize,P> Observer<V,P> for PopulationExport {
+    fn run_finished(&mut self, context: &RunContext<V,P>, solver: &mut dyn UpdatableSolver<V>) {
+        let file_name = context.info.run_file("population", ".jsonl");
+        let lines: Result<Vec<Value>, String> = solver.archive().iter().map(|entry| {
+            let organism = entry.organism;
+            Ok(json!({
+                "genotype": organism.genotype,
+                "score": context.score_of(entry),
+                "features": entry.features.clone().transpose()?,
+                "cell": entry.cell,
+                "hyperparameters": organism.get_hyperparameters(),
+                "lineage id": organism.get_lineage().map(|l| l.id)
+            }))
+        }).collect();
+        let lines = match lines {
+            Ok(lines) => lines,
+            Err(e) => {
+                eprintln!("Could not export the population to {}: features could not be serialised: {}", file_name.display(), e);
+                return;
+            }
+        };
+
+        let mut writer = BufWriter::new(File::create(&file_name).expect("could not create population export"));
+        for line in lines {
+            writeln!(writer, "{}", line).expect("could not write population export");
+        }
+    }
+}
+
+//
+// Early stopping
+//
+
+/// Stops the run once the best score reaches `target_score`,
+/// or when it has not improved for `patience` iterations
+pub struct EarlyStopping {
+    pub target_score: Option<f64>,
+    pub patience: Option<u64>,
+    last_improvement: u64
+}
+
+impl EarlyStopping {
+    pub fn new(target_score: Option<f64>, patience: Option<u64>) -> Self {
+        EarlyStopping {
+            target_score,
+            patience,
+            last_improvement: 0
+        }
+    }
+}
+
+impl<V,P> Observer<V,P> for EarlyStopping {
+    fn new_best(&mut self, context: &RunContext<V,P>, _score: f64) {
+        self.last_improvement = context.iteration;
+    }
+
+    fn iteration_finished(&mut self, context: &RunContext<V,P>, best_so_far: f64) -> RunControl {
+        let reached = self.target_score.is_some_and(|t| best_so_far >= t);
+        let stalled = self.patience.is_some_and(|p| context.iteration - self.last_improvement >= p);
+        if reached || stalled { RunControl::Stop } else { RunControl::Continue }
+    }
+}
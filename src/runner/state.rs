@@ -0,0 +1,250 @@
+use std::vec::Vec;
+use std::time::Instant;
+use std::rc::Rc;
+use crate::algorithm::util::sorted_scores;
+use crate::algorithm::algorithm::UpdatableSolver;
+use statistical::*;
+use serde::Serialize;
+use crate::organism::Diversity;
+use crate::statistics::{HyperparameterStats, OperatorStats, DiversityStats, LogSchedule};
+use crate::quality_diversity::QDArchive;
+use crate::scoring::noise::TrueScoreStats;
+use crate::problems::dynamic::DynamicTracker;
+use crate::scoring::counted::CountedScorer;
+use crate::runner::{MyConfigIt, Iteration};
+use crate::runner::observer::{Observer, RunContext, RunControl, RunInfo};
+
+pub(crate) struct AlgorithmState<V,P,H> {
+    pub(crate) my_config_it: Rc<MyConfigIt<V,P,H>>,
+    pub(crate) updatable_solver: Box<dyn UpdatableSolver<V>>,
+    /// Scorer of the solver, counting its evaluations
+    pub(crate) evaluations: Rc<CountedScorer<V,P>>,
+    pub(crate) run_info: RunInfo,
+    pub(crate) observers: Vec<Box<dyn Observer<V,P>>>,
+    /// An observer asked to end the run
+    pub(crate) stopped: bool,
+    pub(crate) i: u64,
+    /// Offspring produced since the start of the run, several per iteration for batch solvers
+    pub(crate) offspring: u64,
+    /// Hyperparameters of the offspring accepted since the last genome statistics
+    pub(crate) accepted_hyperparameters: Vec<Vec<f64>>,
+    /// Offspring outcomes since the last genome statistics
+    pub(crate) operator_stats: OperatorStats,
+    pub(crate) qd_archive: Option<Box<dyn QDArchive<V>>>,
+    pub(crate) log_schedule: LogSchedule,
+    /// Highest score of the run, over logged and unlogged iterations
+    pub(crate) best_so_far: f64,
+    /// Genome statistics are computed on the first logged iteration after every `genome_stats_gap` iterations
+    pub(crate) genome_stats_due: bool,
+    /// For runs on a dynamic problem
+    pub(crate) dynamic_problem: Option<DynamicTracker>
+}
+
+impl<V: Diversity + Serialize,P,H> Iterator for AlgorithmState<V,P,H> {
+    type Item = Iteration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let my_config_it = self.my_config_it.clone();
+            let context = RunContext {
+                info: &self.run_info,
+                iteration: self.i,
+                scorer: my_config_it.my_config.problem_config.scorer.as_ref(),
+                instance: my_config_it.instance.as_ref()
+            };
+
+            if self.i >= self.run_info.number_of_iterations || self.stopped {
+                for observer in self.observers.iter_mut() {
+                    observer.run_finished(&context, self.updatable_solver.as_mut());
+                }
+                self.my_config_it.progress.borrow_mut().end_run();
+                return None;
+            }
+            else {
+                if self.i == 0 {
+                    self.my_config_it.progress.borrow_mut().start_run(
+                        self.run_info.repetition,
+                        self.run_info.algorithm_index,
+                        &self.run_info.algorithm,
+                        self.run_info.number_of_iterations);
+                    my_config_it.my_config.problem_config.scorer.start_run(my_config_it.instance.as_ref());
+                    // the landscape of a dynamic problem is drawn again, scores from the initialisation are stale
+                    if my_config_it.my_config.problem_config.scorer.is_dynamic() {
+                        self.updatable_solver.invalidate_scores();
+                    }
+                    for observer in self.observers.iter_mut() {
+                        observer.run_started(&context, self.updatable_solver.as_mut());
+                    }
+                }
+
+                if my_config_it.my_config.problem_config.scorer.iteration_started(self.i, my_config_it.instance.as_ref()) {
+                    self.updatable_solver.invalidate_scores();
+                    if let Some(tracker) = &mut self.dynamic_problem {
+                        tracker.change(self.i);
+                    }
+                }
+
+                for observer in self.observers.iter_mut() {
+                    observer.iteration_started(&context, self.updatable_solver.as_ref());
+                }
+
+                let before = Instant::now();
+                let organisms = self.updatable_solver.update();
+                let duration = Instant::now().duration_since(before);
+
+                let number_of_organisms = organisms.len();
+
+                let reports = self.updatable_solver.last_updates();
+                for report in reports {
+                    self.operator_stats.record(report.mutation_changed, report.accepted, report.offspring_score, report.competitor_score);
+                    if report.accepted {
+                        if let Some(h) = &report.offspring_hyperparameters {
+                            self.accepted_hyperparameters.push(h.clone());
+                        }
+                    }
+                    for observer in self.observers.iter_mut() {
+                        observer.offspring_created(&context, report);
+                        if report.accepted {
+                            observer.offspring_accepted(&context, report);
+                            if report.competitor_score.is_none() {
+                                observer.niche_discovered(&context, report);
+                            }
+                        }
+                        else {
+                            observer.offspring_rejected(&context, report);
+                        }
+                    }
+                }
+                let offspring_before = self.offspring;
+                self.offspring += reports.len() as u64;
+
+                // with a noisy scorer, the run is judged on the true score of the organism with the highest
+                // noisy score, which it would return, rather than on the luckiest noise
+                let max_score = {
+                    let scorer = self.my_config_it.my_config.problem_config.scorer.as_ref();
+                    let instance = self.my_config_it.instance.as_ref();
+                    let best = organisms.iter()
+                        .map(|o| (o, o.get_score().unwrap_or_else(|| o.only_score(scorer, instance))))
+                        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+                    match best {
+                        Some((o, score)) => scorer.true_score(&o.genotype, instance).unwrap_or(score),
+                        None => f64::NEG_INFINITY
+                    }
+                };
+                if let Some(tracker) = &mut self.dynamic_problem {
+                    let optimum = self.my_config_it.my_config.problem_config.scorer.optimum(self.my_config_it.instance.as_ref());
+                    tracker.observe(self.i, max_score, optimum);
+                }
+                let improved = max_score > self.best_so_far;
+                self.best_so_far = self.best_so_far.max(max_score);
+                if improved {
+                    for observer in self.observers.iter_mut() {
+                        observer.new_best(&context, max_score);
+                    }
+                }
+                for observer in self.observers.iter_mut() {
+                    if observer.iteration_finished(&context, self.best_so_far) == RunControl::Stop {
+                        self.stopped = true;
+                    }
+                }
+
+                if self.i.is_multiple_of(self.my_config_it.my_config.common_config.genome_stats_gap) {
+                    self.genome_stats_due = true;
+                }
+
+                let generations = offspring_before as f64 / number_of_organisms as f64;
+                let last = self.i + 1 >= self.run_info.number_of_iterations || self.stopped;
+                if !self.log_schedule.should_log(self.i, generations, improved, last) {
+                    self.my_config_it.progress.borrow_mut().iteration(self.i, self.evaluations.evaluations(), self.best_so_far, None);
+                    self.i += 1;
+                    continue;
+                }
+
+                let mut diversity = None;
+                let mut quality_diversity = None;
+                let mut population_hyperparameters = None;
+                let mut offspring_hyperparameters = None;
+                // operator outcomes cover every logging window, unlike the costlier genome statistics
+                let operator_stats = Some(std::mem::take(&mut self.operator_stats));
+                let mut true_scores = None;
+                if self.genome_stats_due {
+                    self.genome_stats_due = false;
+
+                    population_hyperparameters = Some(HyperparameterStats::of(
+                        organisms.iter().filter_map(|o| o.get_hyperparameters())));
+                    offspring_hyperparameters = Some(HyperparameterStats::of(self.accepted_hyperparameters.iter()));
+                    self.accepted_hyperparameters.clear();
+
+                    let genotypes: Vec<&V> = organisms.iter().map(|o| &o.genotype).collect();
+                    diversity = Some(DiversityStats::of(&genotypes,
+                        &self.my_config_it.my_config.common_config.diversity_estimators));
+
+                    {
+                        let scorer = self.my_config_it.my_config.problem_config.scorer.as_ref();
+                        let instance = self.my_config_it.instance.as_ref();
+                        // noisy and true scores, for noisy scorers only
+                        let scores: Option<Vec<(f64, f64)>> = organisms.iter()
+                            .map(|o| scorer.true_score(&o.genotype, instance)
+                                .map(|t| (o.get_score().unwrap_or_else(|| o.only_score(scorer, instance)), t)))
+                            .collect();
+                        true_scores = scores.and_then(|s| TrueScoreStats::of(&s));
+                    }
+
+                    if let Some(archive) = &mut self.qd_archive {
+                        let scorer = self.my_config_it.my_config.problem_config.scorer.as_ref();
+                        let instance = self.my_config_it.instance.as_ref();
+                        let scored: Vec<(&V, f64)> = organisms.iter()
+                            .map(|o| (&o.genotype, o.get_score().unwrap_or_else(|| o.only_score(scorer, instance))))
+                            .collect();
+                        quality_diversity = Some(archive.observe(&scored));
+                    }
+                }
+
+                /*
+                println!("Parameters: {}\nNumber of organism: {}",
+                         self.my_config_it.my_config.algorithms[self.my_config_it.index_algo].replacement_selection.parameters(),
+                        number_of_organisms);
+                */
+
+                let sorted_score = sorted_scores(
+                    organisms,
+                    self.my_config_it.my_config.problem_config.scorer.as_ref(),
+                    self.my_config_it.instance.as_ref());
+
+                //println!("Scores: {:?}", &sorted_score);
+
+                let mean_val = mean(sorted_score.as_slice());
+                self.my_config_it.progress.borrow_mut().iteration(self.i, self.evaluations.evaluations(), self.best_so_far, Some(mean_val));
+                let vari = if sorted_score.len() > 1 {
+                    variance(sorted_score.as_slice(), Some(mean_val)) } else { 0.0 };
+                let iter = Iteration {
+                    iteration: self.i,
+                    repetition: self.my_config_it.repetitions+1,
+                    index_algo: self.my_config_it.index_algo,
+                    duration,
+                    sum_scores: sorted_score.iter().sum(),
+                    min_score: *sorted_score.last().unwrap(),
+                    max_score: *sorted_score.first().unwrap(),
+                    best_so_far: self.best_so_far,
+                    mean_score: mean_val,
+                    median_score: median(sorted_score.as_slice()),
+                    number_of_organisms,
+                    pop_score_variance: vari,
+                    generations,
+                    evaluations: self.evaluations.evaluations(),
+                    diversity,
+                    quality_diversity,
+                    population_hyperparameters,
+                    offspring_hyperparameters,
+                    operator_stats,
+                    fitness_cache: self.my_config_it.my_config.problem_config.scorer.cache_statistics(),
+                    true_scores,
+                    dynamic_problem: self.dynamic_problem.as_ref().map(|t| t.stats())
+                };
+
+                self.i += 1;
+                return Some(iter);
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::scoring::Scorer;
+
+//
+// Fitness memoisation
+//
+// Scores are kept by genotype for the whole run, so that genotypes found again by mutation,
+// such as swaps undoing each other, are not scored again. The genotypes are the keys of a hash
+// map: two genotypes with the same hash cannot share a score.
+//
+
+/// Hits and misses of a fitness cache since the start of the run, the lookups include those of the
+/// run statistics for the organisms the solver did not score
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Scores currently kept
+    pub size: usize
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+    }
+
+    pub fn header() -> Vec<String> {
+        [
+            "fitness cache hits",
+            "fitness cache misses",
+            "fitness cache hit rate",
+            "fitness cache size"
+        ].iter().map(|s| s.to_string()).collect()
+    }
+
+    pub fn row(stats: &Option<CacheStats>) -> Vec<Value> {
+        match stats {
+            Some(s) => vec![
+                s.hits.into(),
+                s.misses.into(),
+                s.hit_rate().into(),
+                s.size.into()
+            ],
+            None => vec![Value::Null; 4]
+        }
+    }
+}
+
+/// Scores of the genotypes used the most recently, at most `capacity` of them
+struct LruScores<V> {
+    capacity: usize,
+    /// Score and time of last use of every genotype
+    scores: HashMap<Rc<V>, (f64, u64)>,
+    /// Genotypes by time of last use
+    order: BTreeMap<u64, Rc<V>>,
+    clock: u64
+}
+
+impl<V: Hash + Eq> LruScores<V> {
+    fn new(capacity: usize) -> Self {
+        LruScores {
+            capacity,
+            scores: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0
+        }
+    }
+
+    fn clear(&mut self) {
+        self.scores.clear();
+        self.order.clear();
+        self.clock = 0;
+    }
+
+    fn get(&mut self, genome: &V) -> Option<f64> {
+        let (key, &(score, last_use)) = self.scores.get_key_value(genome)?;
+        let key = key.clone();
+
+        self.clock += 1;
+        self.order.remove(&last_use);
+        self.order.insert(self.clock, key.clone());
+        self.scores.insert(key, (score, self.clock));
+        Some(score)
+    }
+
+    fn insert(&mut self, genome: V, score: f64) {
+        if self.capacity == 0 || self.scores.contains_key(&genome) {
+            return;
+        }
+        if self.scores.len() >= self.capacity {
+            if let Some((_, least_recent)) = self.order.pop_first() {
+                self.scores.remove(&least_recent);
+            }
+        }
+
+        let key = Rc::new(genome);
+        self.clock += 1;
+        self.order.insert(self.clock, key.clone());
+        self.scores.insert(key, (score, self.clock));
+    }
+}
+
+/// Remembers the scores given by `scorer` during a run, forgetting the least recently used ones beyond `capacity`
+pub struct CachedScorer<V,P> {
+    pub scorer: Rc<dyn Scorer<V,P>>,
+    pub capacity: usize,
+    scores: RefCell<LruScores<V>>,
+    stats: Cell<CacheStats>
+}
+
+impl<V: Hash + Eq, P> CachedScorer<V,P> {
+    pub fn new(scorer: Rc<dyn Scorer<V,P>>, capacity: usize) -> Self {
+        CachedScorer {
+            scorer,
+            capacity,
+            scores: RefCell::new(LruScores::new(capacity)),
+            stats: Cell::new(CacheStats::default())
+        }
+    }
+
+    fn record(&self, hits: u64, misses: u64) {
+        let stats = self.stats.get();
+        self.stats.set(CacheStats {
+            hits: stats.hits + hits,
+            misses: stats.misses + misses,
+            size: self.scores.borrow().scores.len()
+        });
+    }
+}
+
+impl<V,P> Named for CachedScorer<V,P> {
+    fn name(&self) -> String {
+        self.scorer.name()
+    }
+}
+
+impl<V,P> Parametrized for CachedScorer<V,P> {
+    fn parameters(&self) -> Value {
+        let mut hm = match self.scorer.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
+        };
+        hm.insert("fitness cache capacity".to_string(), self.capacity.into());
+        Value::Object(hm)
+    }
+}
+
+impl<V: Hash + Eq + Clone, P> Scorer<V,P> for CachedScorer<V,P> {
+    fn score(&self, genome: &V, problem: &P) -> f64 {
+        let cached = self.scores.borrow_mut().get(genome);
+        let score = match cached {
+            Some(score) => score,
+            None => {
+                let score = self.scorer.score(genome, problem);
+                self.scores.borrow_mut().insert(genome.clone(), score);
+                score
+            }
+        };
+        self.record(cached.is_some() as u64, cached.is_none() as u64);
+        score
+    }
+
+    /// Only the genotypes missing from the cache are sent to the scorer, once each
+    fn score_batch(&self, genomes: &[&V], problem: &P) -> Vec<f64> {
+        let mut scores: Vec<Option<f64>> = {
+            let mut cache = self.scores.borrow_mut();
+            genomes.iter().map(|g| cache.get(g)).collect()
+        };
+
+        let mut missing: Vec<&V> = Vec::new();
+        let mut missing_index: HashMap<&V, usize> = HashMap::new();
+        for (genome, score) in genomes.iter().zip(&scores) {
+            if score.is_none() && !missing_index.contains_key(*genome) {
+                missing_index.insert(genome, missing.len());
+                missing.push(genome);
+            }
+        }
+
+        let computed = if missing.is_empty() { Vec::new() } else { self.scorer.score_batch(&missing, problem) };
+        {
+            let mut cache = self.scores.borrow_mut();
+            for (genome, &score) in missing.iter().zip(&computed) {
+                cache.insert((*genome).clone(), score);
+            }
+        }
+        for (genome, score) in genomes.iter().zip(scores.iter_mut()) {
+            if score.is_none() {
+                *score = Some(computed[missing_index[*genome]]);
+            }
+        }
+
+        self.record((genomes.len() - missing.len()) as u64, missing.len() as u64);
+        scores.into_iter().map(|s| s.unwrap()).collect()
+    }
+
+    fn start_run(&self, problem: &P) {
+        self.scores.borrow_mut().clear();
+        self.stats.set(CacheStats::default());
+        self.scorer.start_run(problem);
+    }
+
+    /// The scores kept are forgotten when the landscape changes
+    fn iteration_started(&self, iteration: u64, problem: &P) -> bool {
+        let changed = self.scorer.iteration_started(iteration, problem);
+        if changed {
+            self.scores.borrow_mut().clear();
+        }
+        changed
+    }
+
+    fn is_dynamic(&self) -> bool {
+        self.scorer.is_dynamic()
+    }
+
+    fn optimum(&self, problem: &P) -> Option<f64> {
+        self.scorer.optimum(problem)
+    }
+
+    fn cache_statistics(&self) -> Option<CacheStats> {
+        Some(self.stats.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let mut scores = LruScores::new(2);
+        scores.insert(1, 1.0);
+        scores.insert(2, 2.0);
+        // using 1 makes 2 the least recently used
+        assert_eq!(scores.get(&1), Some(1.0));
+        scores.insert(3, 3.0);
+        assert_eq!(scores.get(&2), None);
+        assert_eq!(scores.get(&1), Some(1.0));
+        assert_eq!(scores.get(&3), Some(3.0));
+
+        // 1 was used before 3
+        scores.insert(4, 4.0);
+        assert_eq!(scores.get(&1), None);
+        assert_eq!(scores.get(&3), Some(3.0));
+        assert_eq!(scores.get(&4), Some(4.0));
+        assert_eq!(scores.scores.len(), 2);
+        assert_eq!(scores.order.len(), 2);
+    }
+
+    #[test]
+    fn inserting_a_kept_genotype_changes_nothing() {
+        let mut scores = LruScores::new(2);
+        scores.insert(1, 1.0);
+        scores.insert(2, 2.0);
+        scores.insert(1, 10.0);
+        assert_eq!(scores.get(&1), Some(1.0));
+        assert_eq!(scores.get(&2), Some(2.0));
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut scores = LruScores::new(0);
+        scores.insert(1, 1.0);
+        assert_eq!(scores.get(&1), None);
+        assert!(scores.order.is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use serde_json::Value;
+use crate::common::{Named, Parametrized};
+use crate::scoring::Scorer;
+use crate::scoring::cache::CacheStats;
+
+//
+// Evaluation counting
+//
+// The scorer of a solver is wrapped for the length of a run, so that only the scores the solver
+// asks for are counted, and not those of the run statistics.
+//
+
+/// Counts the genomes scored through it, the genomes found in a fitness cache are not evaluated
+pub struct CountedScorer<V,P> {
+    pub scorer: Rc<dyn Scorer<V,P>>,
+    evaluations: Cell<u64>
+}
+
+impl<V,P> CountedScorer<V,P> {
+    pub fn new(scorer: Rc<dyn Scorer<V,P>>) -> Self {
+        CountedScorer {
+            scorer,
+            evaluations: Cell::new(0)
+        }
+    }
+
+    /// Genomes evaluated since the scorer was created
+    pub fn evaluations(&self) -> u64 {
+        self.evaluations.get()
+    }
+
+    fn hits(&self) -> u64 {
+        self.scorer.cache_statistics().map_or(0, |s| s.hits)
+    }
+
+    fn record(&self, genomes: usize, hits_before: u64) {
+        let hits = self.hits() - hits_before;
+        self.evaluations.set(self.evaluations.get() + genomes as u64 - hits);
+    }
+}
+
+impl<V,P> Named for CountedScorer<V,P> {
+    fn name(&self) -> String {
+        self.scorer.name()
+    }
+}
+
+impl<V,P> Parametrized for CountedScorer<V,P> {
+    fn parameters(&self) -> Value {
+        self.scorer.parameters()
+    }
+}
+
+impl<V,P> Scorer<V,P> for CountedScorer<V,P> {
+    fn score(&self, genome: &V, problem: &P) -> f64 {
+        let hits = self.hits();
+        let score = self.scorer.score(genome, problem);
+        self.record(1, hits);
+        score
+    }
+
+    fn score_batch(&self, genomes: &[&V], problem: &P) -> Vec<f64> {
+        let hits = self.hits();
+        let scores = self.scorer.score_batch(genomes, problem);
+        self.record(genomes.len(), hits);
+        scores
+    }
+
+    fn start_run(&self, problem: &P) {
+        self.scorer.start_run(problem);
+    }
+
+    fn iteration_started(&self, iteration: u64, problem: &P) -> bool {
+        self.scorer.iteration_started(iteration, problem)
+    }
+
+    fn is_dynamic(&self) -> bool {
+        self.scorer.is_dynamic()
+    }
+
+    fn optimum(&self, problem: &P) -> Option<f64> {
+        self.scorer.optimum(problem)
+    }
+
+    fn cache_statistics(&self) -> Option<CacheStats> {
+        self.scorer.cache_statistics()
+    }
+
+    fn true_score(&self, genome: &V, problem: &P) -> Option<f64> {
+        self.scorer.true_score(genome, problem)
+    }
+}
@@ -0,0 +1,294 @@
+use std::cell::{Cell, RefCell};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::scoring::Scorer;
+
+//
+// Fitness computed by an external program
+//
+// The program is started once and kept running. Every request is one line holding the JSON array
+// of the genomes to score, the program answers with one line holding the JSON array of their scores.
+// Scores should be non-negative, higher is better.
+//
+
+/// Failures of each kind reported on the standard error before they are only counted
+const REPORTED_FAILURES: u64 = 10;
+
+/// Time given to the program to exit once its input is closed
+const EXIT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+struct EvaluatorProcess {
+    child: Child,
+    /// Closed when the process is dropped
+    stdin: Option<ChildStdin>,
+    /// Lines written by the program, read on a separate thread so that waiting for them can time out
+    lines: Receiver<std::io::Result<String>>
+}
+
+enum Failure {
+    /// The program exited or its pipes broke, it can be restarted
+    Crashed(String),
+    TimedOut
+}
+
+impl EvaluatorProcess {
+    fn start(command: &[String]) -> Result<Self, String> {
+        let (program, args) = command.split_first().ok_or("the evaluator command is empty")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("could not start \"{}\": {}", command.join(" "), e))?;
+
+        let stdin = child.stdin.take().ok_or("the evaluator has no standard input")?;
+        let stdout = child.stdout.take().ok_or("the evaluator has no standard output")?;
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(EvaluatorProcess {
+            child,
+            stdin: Some(stdin),
+            lines
+        })
+    }
+
+    /// Sends a request and waits for the answer line
+    fn exchange(&mut self, request: &str, timeout: Duration) -> Result<String, Failure> {
+        let stdin = self.stdin.as_mut().ok_or_else(|| Failure::Crashed("the evaluator input is closed".to_string()))?;
+        writeln!(stdin, "{}", request)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| Failure::Crashed(format!("could not write to the evaluator: {}", e)))?;
+
+        match self.lines.recv_timeout(timeout) {
+            Ok(Ok(line)) => Ok(line),
+            Ok(Err(e)) => Err(Failure::Crashed(format!("could not read from the evaluator: {}", e))),
+            Err(RecvTimeoutError::Disconnected) => {
+                let status = self.child.wait().map(|s| s.to_string()).unwrap_or_else(|e| e.to_string());
+                Err(Failure::Crashed(format!("the evaluator exited ({})", status)))
+            },
+            Err(RecvTimeoutError::Timeout) => Err(Failure::TimedOut)
+        }
+    }
+}
+
+impl Drop for EvaluatorProcess {
+    fn drop(&mut self) {
+        // closing the input lets the program exit by itself, it is killed if it does not
+        drop(self.stdin.take());
+        let start = Instant::now();
+        while start.elapsed() < EXIT_GRACE_PERIOD {
+            match self.child.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(10))
+            }
+        }
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Scores genomes with an external program, see the protocol above
+pub struct ExternalScorer {
+    /// Program and arguments
+    pub command: Vec<String>,
+    /// Longest wait for an answer, the program is restarted when it does not answer in time
+    pub timeout: Duration,
+    /// Score of the genomes the program gave no valid score for
+    pub penalty: f64,
+    /// Most genomes sent in one request
+    pub batch_size: usize,
+    process: RefCell<Option<EvaluatorProcess>>,
+    invalid_answers: Cell<u64>,
+    restarts: Cell<u64>
+}
+
+impl ExternalScorer {
+    /// Starts the program, so that a command that cannot be run is reported before any run
+    pub fn new(command: Vec<String>, timeout: Duration, penalty: f64, batch_size: usize) -> Result<Self, String> {
+        let process = EvaluatorProcess::start(&command)?;
+        Ok(ExternalScorer {
+            command,
+            timeout,
+            penalty,
+            batch_size: batch_size.max(1),
+            process: RefCell::new(Some(process)),
+            invalid_answers: Cell::new(0),
+            restarts: Cell::new(0)
+        })
+    }
+
+    fn report(count: &Cell<u64>, message: &str) {
+        count.set(count.get() + 1);
+        if count.get() <= REPORTED_FAILURES {
+            eprintln!("Evaluator: {}", message);
+        }
+        if count.get() == REPORTED_FAILURES {
+            eprintln!("Evaluator: further failures of this kind are not reported");
+        }
+    }
+
+    fn report_invalid(&self, message: &str) {
+        ExternalScorer::report(&self.invalid_answers, &format!("{}, penalty score given", message));
+    }
+
+    /// Scores of an answer line, the penalty replaces the scores that are missing or not numbers
+    fn parse_answer(&self, line: &str, expected: usize) -> Vec<f64> {
+        let scores = match serde_json::from_str::<Value>(line) {
+            Ok(Value::Array(scores)) if scores.len() == expected => scores,
+            Ok(Value::Number(score)) if expected == 1 => vec![Value::Number(score)],
+            _ => {
+                self.report_invalid(&format!("expected an array of {} scores, got \"{}\"", expected, line));
+                return vec![self.penalty; expected];
+            }
+        };
+        scores.iter().map(|s| match s.as_f64() {
+            Some(score) if score.is_finite() => score,
+            _ => {
+                self.report_invalid(&format!("invalid score {}", s));
+                self.penalty
+            }
+        }).collect()
+    }
+
+    /// Scores of one request, the program is restarted once if it crashed
+    fn evaluate<V: Serialize>(&self, genomes: &[&V]) -> Vec<f64> {
+        let request = serde_json::to_string(genomes).expect("could not serialize genomes for the evaluator");
+        let mut process = self.process.borrow_mut();
+
+        for _attempt in 0..2 {
+            if process.is_none() {
+                match EvaluatorProcess::start(&self.command) {
+                    Ok(restarted) => *process = Some(restarted),
+                    Err(e) => {
+                        self.report_invalid(&format!("could not restart the evaluator: {}", e));
+                        return vec![self.penalty; genomes.len()];
+                    }
+                }
+            }
+            match process.as_mut().unwrap().exchange(&request, self.timeout) {
+                Ok(line) => return self.parse_answer(&line, genomes.len()),
+                Err(Failure::Crashed(message)) => {
+                    ExternalScorer::report(&self.restarts, &format!("{}, restarting it", message));
+                    *process = None;
+                },
+                Err(Failure::TimedOut) => {
+                    self.report_invalid(&format!("no answer within {:?}, restarting the evaluator", self.timeout));
+                    if let Some(mut hung) = process.take() {
+                        hung.child.kill().ok();
+                    }
+                    return vec![self.penalty; genomes.len()];
+                }
+            }
+        }
+        self.report_invalid("the evaluator crashed twice on the same request");
+        vec![self.penalty; genomes.len()]
+    }
+}
+
+impl Named for ExternalScorer {
+    fn name(&self) -> String {
+        "External evaluator".to_string()
+    }
+}
+
+impl Parametrized for ExternalScorer {
+    fn parameters(&self) -> Value {
+        let mut hm = Map::new();
+        hm.insert("command".to_string(), self.command.clone().into());
+        hm.insert("timeout (s)".to_string(), self.timeout.as_secs_f64().into());
+        hm.insert("penalty".to_string(), self.penalty.into());
+        hm.insert("batch size".to_string(), self.batch_size.into());
+        Value::Object(hm)
+    }
+}
+
+impl<V: Serialize, P> Scorer<V,P> for ExternalScorer {
+    fn score(&self, genome: &V, _problem: &P) -> f64 {
+        self.evaluate(&[genome])[0]
+    }
+
+    fn score_batch(&self, genomes: &[&V], _problem: &P) -> Vec<f64> {
+        genomes.chunks(self.batch_size).flat_map(|batch| self.evaluate(batch)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Scorer running the example evaluator with the given misbehaviour options
+    fn sphere_evaluator(options: &[&str], timeout: Duration) -> ExternalScorer {
+        let script = format!("{}/scripts/sphere_evaluator.py", env!("CARGO_MANIFEST_DIR"));
+        let command = ["python3", script.as_str()].iter().chain(options).map(|s| s.to_string()).collect();
+        ExternalScorer::new(command, timeout, -1.0, 64).unwrap()
+    }
+
+    fn score(scorer: &ExternalScorer, genome: &Value) -> f64 {
+        Scorer::<Value, ()>::score(scorer, genome, &())
+    }
+
+    #[test]
+    fn scores_of_the_evaluator() {
+        let scorer = sphere_evaluator(&[], Duration::from_secs(10));
+        let (a, b) = (json!({"value": [1.0, 2.0]}), json!({"value": [0.0]}));
+        assert_eq!(Scorer::<Value, ()>::score_batch(&scorer, &[&a, &b], &()), vec![1.0 / 6.0, 1.0]);
+        assert_eq!(scorer.invalid_answers.get(), 0);
+    }
+
+    #[test]
+    fn crashed_evaluator_is_restarted() {
+        let scorer = sphere_evaluator(&["--crash-every", "2"], Duration::from_secs(10));
+        let genome = json!({"value": [1.0]});
+        // every evaluator crashes on its second request, which is sent again to a restarted one
+        for _ in 0..3 {
+            assert_eq!(score(&scorer, &genome), 0.5);
+        }
+        assert_eq!(scorer.restarts.get(), 2);
+        assert_eq!(scorer.invalid_answers.get(), 0);
+    }
+
+    #[test]
+    fn invalid_answers_get_the_penalty() {
+        let scorer = sphere_evaluator(&["--garbage-every", "2"], Duration::from_secs(10));
+        let genome = json!({"value": [1.0]});
+        let scores: Vec<f64> = (0..4).map(|_| score(&scorer, &genome)).collect();
+        assert_eq!(scores, vec![0.5, -1.0, 0.5, -1.0]);
+        assert_eq!(scorer.invalid_answers.get(), 2);
+        assert_eq!(scorer.restarts.get(), 0);
+    }
+
+    #[test]
+    fn hung_evaluator_times_out() {
+        let scorer = sphere_evaluator(&["--hang-every", "2"], Duration::from_secs(3));
+        let genome = json!({"value": [1.0]});
+        assert_eq!(score(&scorer, &genome), 0.5);
+        let start = Instant::now();
+        assert_eq!(score(&scorer, &genome), -1.0);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        // the hung evaluator was killed, a new one answers the next request
+        assert_eq!(score(&scorer, &genome), 0.5);
+        assert_eq!(scorer.invalid_answers.get(), 1);
+    }
+
+    #[test]
+    fn missing_evaluator_is_an_error() {
+        let command = vec!["/nonexistent/evaluator".to_string()];
+        assert!(ExternalScorer::new(command, Duration::from_secs(1), 0.0, 1).is_err());
+        assert!(ExternalScorer::new(Vec::new(), Duration::from_secs(1), 0.0, 1).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+pub mod cache;
+pub mod counted;
+pub mod external;
+pub mod noise;
+pub mod parallel;
+
+use crate::common::{Named, Parametrized};
+use crate::scoring::cache::CacheStats;
+
+pub trait Scorer<V,P>: Named + Parametrized {
+    fn score(&self, genome: &V, problem: &P) -> f64;
+
+    /// Scores of several genomes, in order, for scorers that evaluate them faster together
+    fn score_batch(&self, genomes: &[&V], problem: &P) -> Vec<f64> {
+        genomes.iter().map(|g| self.score(g, problem)).collect()
+    }
+
+    /// Called before every run, scorers keeping state between genomes start the run afresh
+    fn start_run(&self, _problem: &P) {}
+
+    /// Called before every iteration, returns whether the landscape of a dynamic problem changed,
+    /// the scores computed before are then stale
+    fn iteration_started(&self, _iteration: u64, _problem: &P) -> bool {
+        false
+    }
+
+    /// Whether the landscape can change during a run
+    fn is_dynamic(&self) -> bool {
+        false
+    }
+
+    /// Highest score of the current landscape, when known
+    fn optimum(&self, _problem: &P) -> Option<f64> {
+        None
+    }
+
+    /// Hits and misses since the start of the run, for scorers caching fitness
+    fn cache_statistics(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// Score without noise, for scorers adding noise to another one
+    fn true_score(&self, _genome: &V, _problem: &P) -> Option<f64> {
+        None
+    }
+}
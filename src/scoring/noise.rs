@@ -0,0 +1,175 @@
+use std::rc::Rc;
+use rand::{thread_rng, Rng};
+use rand_distr::{Cauchy, StandardNormal};
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::scoring::Scorer;
+
+//
+// Noisy fitness
+//
+
+/// Random perturbation of the scores.
+///
+/// Noisy scores are clamped at zero, since the Metropolis-Hastings elitism compares ratios of
+/// non-negative scores. The noise is thus biased upwards for scores within a few `sigma` of zero:
+/// with additive Gaussian noise, the mean noisy score of a zero score is `sigma / sqrt(2 pi)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Noise {
+    /// Gaussian noise of standard deviation `sigma` added to the score
+    Additive { sigma: f64 },
+    /// Score multiplied by 1 plus a Gaussian noise of standard deviation `sigma`
+    Multiplicative { sigma: f64 },
+    /// Cauchy noise of scale `sigma` added to the score, with outliers far more often than Gaussian noise
+    HeavyTailed { sigma: f64 }
+}
+
+impl Noise {
+    pub fn sigma(&self) -> f64 {
+        match self {
+            Noise::Additive { sigma } | Noise::Multiplicative { sigma } | Noise::HeavyTailed { sigma } => *sigma
+        }
+    }
+
+    /// Noisy score, clamped at zero
+    fn apply(&self, score: f64) -> f64 {
+        let mut rng = thread_rng();
+        let noisy = match self {
+            Noise::Additive { sigma } => score + sigma * rng.sample::<f64,_>(StandardNormal),
+            Noise::Multiplicative { sigma } => score * (1.0 + sigma * rng.sample::<f64,_>(StandardNormal)),
+            Noise::HeavyTailed { sigma } => match Cauchy::new(0.0, *sigma) {
+                Ok(cauchy) => score + rng.sample(cauchy),
+                Err(_) => score
+            }
+        };
+        noisy.max(0.0)
+    }
+}
+
+impl Named for Noise {
+    fn name(&self) -> String {
+        match self {
+            Noise::Additive { .. } => "additive Gaussian",
+            Noise::Multiplicative { .. } => "multiplicative Gaussian",
+            Noise::HeavyTailed { .. } => "additive Cauchy"
+        }.to_string()
+    }
+}
+
+/// Adds noise to the scores of `scorer`, every evaluation of a genome draws new noise
+pub struct NoisyScorer<V,P> {
+    pub scorer: Rc<dyn Scorer<V,P>>,
+    pub noise: Noise
+}
+
+impl<V,P> Named for NoisyScorer<V,P> {
+    fn name(&self) -> String {
+        self.scorer.name()
+    }
+}
+
+impl<V,P> Parametrized for NoisyScorer<V,P> {
+    fn parameters(&self) -> Value {
+        let mut hm = match self.scorer.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
+        };
+        hm.insert("noise".to_string(), self.noise.name().into());
+        hm.insert("noise sigma".to_string(), self.noise.sigma().into());
+        Value::Object(hm)
+    }
+}
+
+impl<V,P> Scorer<V,P> for NoisyScorer<V,P> {
+    fn score(&self, genome: &V, problem: &P) -> f64 {
+        self.noise.apply(self.scorer.score(genome, problem))
+    }
+
+    fn score_batch(&self, genomes: &[&V], problem: &P) -> Vec<f64> {
+        self.scorer.score_batch(genomes, problem).into_iter().map(|s| self.noise.apply(s)).collect()
+    }
+
+    fn start_run(&self, problem: &P) {
+        self.scorer.start_run(problem);
+    }
+
+    fn iteration_started(&self, iteration: u64, problem: &P) -> bool {
+        self.scorer.iteration_started(iteration, problem)
+    }
+
+    fn is_dynamic(&self) -> bool {
+        self.scorer.is_dynamic()
+    }
+
+    fn optimum(&self, problem: &P) -> Option<f64> {
+        self.scorer.optimum(problem)
+    }
+
+    fn true_score(&self, genome: &V, problem: &P) -> Option<f64> {
+        Some(self.scorer.true_score(genome, problem).unwrap_or_else(|| self.scorer.score(genome, problem)))
+    }
+}
+
+/// Noise-free scores of a population, for runs with a noisy scorer
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrueScoreStats {
+    pub max: f64,
+    pub mean: f64,
+    /// True score of the organism with the highest noisy score
+    pub of_best: f64
+}
+
+impl TrueScoreStats {
+    /// Statistics of the true scores given with the noisy scores of the organisms
+    pub fn of(scores: &[(f64, f64)]) -> Option<Self> {
+        let best = scores.iter().max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))?;
+        Some(TrueScoreStats {
+            max: scores.iter().map(|s| s.1).fold(f64::NEG_INFINITY, f64::max),
+            mean: scores.iter().map(|s| s.1).sum::<f64>() / scores.len() as f64,
+            of_best: best.1
+        })
+    }
+
+    pub fn header() -> Vec<String> {
+        [
+            "true max score",
+            "true mean score",
+            "true score of best"
+        ].iter().map(|s| s.to_string()).collect()
+    }
+
+    pub fn row(stats: &Option<TrueScoreStats>) -> Vec<Value> {
+        match stats {
+            Some(s) => vec![
+                s.max.into(),
+                s.mean.into(),
+                s.of_best.into()
+            ],
+            None => vec![Value::Null; 3]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean_noisy_score(noise: Noise, score: f64) -> f64 {
+        let samples = 200_000;
+        (0..samples).map(|_| noise.apply(score)).sum::<f64>() / samples as f64
+    }
+
+    #[test]
+    fn noise_is_unbiased_far_from_zero() {
+        assert!((mean_noisy_score(Noise::Additive { sigma: 1.0 }, 100.0) - 100.0).abs() < 0.02);
+        assert!((mean_noisy_score(Noise::Multiplicative { sigma: 0.1 }, 100.0) - 100.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn clamping_biases_the_noise_near_zero() {
+        // mean of max(0, Z) for a standard Gaussian Z
+        let bias = 1.0 / (2.0 * std::f64::consts::PI).sqrt();
+        assert!((mean_noisy_score(Noise::Additive { sigma: 1.0 }, 0.0) - bias).abs() < 0.01);
+        assert!((0..1000).all(|_| Noise::HeavyTailed { sigma: 1.0 }.apply(0.0) >= 0.0));
+    }
+}
@@ -0,0 +1,209 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use serde_json::{Map, Value};
+use crate::common::{Named, Parametrized};
+use crate::scoring::Scorer;
+use crate::scoring::cache::CacheStats;
+
+//
+// Fitness computed on several threads
+//
+// A batch is cut in chunks that a pool of worker threads takes in turn, so that workers
+// finishing early take the chunks left instead of waiting for the slower ones.
+// Single genomes are scored on the calling thread. The workers live as long as the scorer.
+//
+
+/// Genomes a worker takes at once
+const CHUNK_SIZE: usize = 4;
+
+/// Work lent to the workers for one batch, it borrows the batch for no longer than `WorkerPool::run`
+type Job = &'static (dyn Fn() + Sync);
+
+/// Threads waiting for jobs, started once
+struct WorkerPool {
+    jobs: Vec<Sender<Job>>,
+    /// One message per finished job, whether it finished without panicking
+    done: Receiver<bool>,
+    handles: Vec<JoinHandle<()>>
+}
+
+impl WorkerPool {
+    fn new(threads: usize) -> Self {
+        let (done_sender, done) = mpsc::channel();
+        let (jobs, handles) = (0..threads).map(|i| {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let done_sender = done_sender.clone();
+            let handle = thread::Builder::new()
+                .name(format!("scorer {}", i))
+                .spawn(move || {
+                    for job in receiver {
+                        let finished = panic::catch_unwind(AssertUnwindSafe(job)).is_ok();
+                        if done_sender.send(finished).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("could not start a scoring thread");
+            (sender, handle)
+        }).unzip();
+
+        WorkerPool { jobs, done, handles }
+    }
+
+    /// Runs `job` on the first `workers` threads and waits until all of them are done with it
+    fn run(&self, workers: usize, job: &(dyn Fn() + Sync)) {
+        // the job is only used until the workers say they are done, which is awaited below
+        let job: Job = unsafe { std::mem::transmute::<&(dyn Fn() + Sync), Job>(job) };
+        let sent = self.jobs.iter().take(workers).filter(|sender| sender.send(job).is_ok()).count();
+
+        let mut panicked = false;
+        for _ in 0..sent {
+            // the workers only exit when their job channel is closed, they cannot drop the job unfinished
+            panicked |= !self.done.recv().expect("the scoring threads exited");
+        }
+        if panicked {
+            panic!("a scoring thread panicked");
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.jobs.clear();
+        for handle in self.handles.drain(..) {
+            handle.join().ok();
+        }
+    }
+}
+
+/// Scores batches of genomes with `threads` worker threads
+pub struct ParallelScorer<S> {
+    pub scorer: S,
+    pub threads: usize,
+    pool: WorkerPool
+}
+
+impl<S> ParallelScorer<S> {
+    pub fn new(scorer: S, threads: usize) -> Self {
+        let threads = threads.max(1);
+        ParallelScorer {
+            scorer,
+            threads,
+            pool: WorkerPool::new(threads)
+        }
+    }
+}
+
+impl<S: Named> Named for ParallelScorer<S> {
+    fn name(&self) -> String {
+        self.scorer.name()
+    }
+}
+
+impl<S: Parametrized> Parametrized for ParallelScorer<S> {
+    fn parameters(&self) -> Value {
+        let mut hm = match self.scorer.parameters() {
+            Value::Object(map) => map,
+            _ => Map::new()
+        };
+        hm.insert("threads".to_string(), self.threads.into());
+        Value::Object(hm)
+    }
+}
+
+impl<V: Sync, P: Sync, S: Scorer<V,P> + Sync> Scorer<V,P> for ParallelScorer<S> {
+    fn score(&self, genome: &V, problem: &P) -> f64 {
+        self.scorer.score(genome, problem)
+    }
+
+    fn score_batch(&self, genomes: &[&V], problem: &P) -> Vec<f64> {
+        let workers = self.threads.min(genomes.len().div_ceil(CHUNK_SIZE));
+        if workers <= 1 {
+            return self.scorer.score_batch(genomes, problem);
+        }
+
+        let scorer = &self.scorer;
+        let next_chunk = AtomicUsize::new(0);
+        let scores = Mutex::new(vec![0.0; genomes.len()]);
+        self.pool.run(workers, &|| loop {
+            let start = next_chunk.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+            if start >= genomes.len() {
+                break;
+            }
+            let end = (start + CHUNK_SIZE).min(genomes.len());
+            let chunk = scorer.score_batch(&genomes[start..end], problem);
+            scores.lock().expect("a scoring thread panicked")[start..end].copy_from_slice(&chunk);
+        });
+        scores.into_inner().expect("a scoring thread panicked")
+    }
+
+    fn start_run(&self, problem: &P) {
+        self.scorer.start_run(problem);
+    }
+
+    fn iteration_started(&self, iteration: u64, problem: &P) -> bool {
+        self.scorer.iteration_started(iteration, problem)
+    }
+
+    fn is_dynamic(&self) -> bool {
+        self.scorer.is_dynamic()
+    }
+
+    fn optimum(&self, problem: &P) -> Option<f64> {
+        self.scorer.optimum(problem)
+    }
+
+    fn cache_statistics(&self) -> Option<CacheStats> {
+        self.scorer.cache_statistics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Squares its genome, panics on negative ones
+    struct Square {}
+
+    impl Named for Square {
+        fn name(&self) -> String {
+            "square".to_string()
+        }
+    }
+
+    impl Parametrized for Square {}
+
+    impl Scorer<f64, ()> for Square {
+        fn score(&self, genome: &f64, _problem: &()) -> f64 {
+            assert!(*genome >= 0.0, "negative genome");
+            genome * genome
+        }
+    }
+
+    #[test]
+    fn batches_keep_their_order() {
+        let scorer = ParallelScorer::new(Square {}, 3);
+        for size in &[1, 5, 100] {
+            let genomes: Vec<f64> = (0..*size).map(|i| i as f64).collect();
+            let references: Vec<&f64> = genomes.iter().collect();
+            let expected: Vec<f64> = genomes.iter().map(|x| x * x).collect();
+            assert_eq!(scorer.score_batch(&references, &()), expected);
+        }
+    }
+
+    #[test]
+    fn panics_reach_the_caller_and_workers_survive() {
+        let scorer = ParallelScorer::new(Square {}, 2);
+        let genomes: Vec<f64> = (0..40).map(|i| if i == 30 { -1.0 } else { i as f64 }).collect();
+        let references: Vec<&f64> = genomes.iter().collect();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| scorer.score_batch(&references, &())));
+        assert!(result.is_err());
+
+        let genomes: Vec<f64> = (0..40).map(|i| i as f64).collect();
+        let references: Vec<&f64> = genomes.iter().collect();
+        assert_eq!(scorer.score_batch(&references, &())[39], 39.0 * 39.0);
+    }
+}
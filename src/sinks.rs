@@ -0,0 +1,380 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use rusqlite::{Connection, params};
+use rusqlite::types::Value as SqlValue;
+use serde_json::{Value, Map};
+
+//
+// Destinations of the per-iteration statistics of an experiment
+//
+
+/// Receives the rows of one results file, every row has a value for each of the columns given to `start`.
+///
+/// Values are numbers, integers or not, strings or `null` when missing, non-finite numbers are missing.
+pub trait IterationSink {
+    fn start(&mut self, problem: &str, columns: &[String], metadata: &Value) -> Result<(), String>;
+    fn write_row(&mut self, row: &[Value]) -> Result<(), String>;
+    /// Called after the last row of every run
+    fn end_run(&mut self) -> Result<(), String>;
+    fn finish(&mut self, metadata: &Value) -> Result<(), String>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SinkKind {
+    Csv,
+    JsonLines,
+    Sqlite
+}
+
+impl SinkKind {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "csv" => Ok(SinkKind::Csv),
+            "jsonl" => Ok(SinkKind::JsonLines),
+            "sqlite" => Ok(SinkKind::Sqlite),
+            other => Err(format!("unknown output \"{}\", expected csv, jsonl or sqlite", other))
+        }
+    }
+
+    /// Comma-separated list of sinks, as given on the command line
+    pub fn parse_list(text: &str) -> Result<Vec<Self>, String> {
+        text.split(',').map(|s| SinkKind::parse(s.trim())).collect()
+    }
+}
+
+/// Text of a value in a CSV file, missing values are empty
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        // floating point numbers are written as Rust formats them, `1` rather than `1.0`
+        Value::Number(n) if n.is_f64() => n.as_f64().unwrap().to_string(),
+        other => other.to_string()
+    }
+}
+
+pub struct CsvSink {
+    pub path: PathBuf,
+    writer: Option<csv::Writer<File>>
+}
+
+impl CsvSink {
+    pub fn new(path: PathBuf) -> Self {
+        CsvSink { path, writer: None }
+    }
+}
+
+impl IterationSink for CsvSink {
+    fn start(&mut self, _problem: &str, columns: &[String], _metadata: &Value) -> Result<(), String> {
+        let mut writer = csv::Writer::from_path(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        writer.write_record(columns).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[Value]) -> Result<(), String> {
+        let writer = self.writer.as_mut().ok_or("CSV sink not started")?;
+        writer.write_record(row.iter().map(csv_cell)).map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    fn end_run(&mut self) -> Result<(), String> {
+        let writer = self.writer.as_mut().ok_or("CSV sink not started")?;
+        writer.flush().map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    fn finish(&mut self, _metadata: &Value) -> Result<(), String> {
+        self.end_run()
+    }
+}
+
+/// One JSON object per iteration, keyed by column name, missing values are `null`
+pub struct JsonLinesSink {
+    pub path: PathBuf,
+    columns: Vec<String>,
+    writer: Option<BufWriter<File>>
+}
+
+impl JsonLinesSink {
+    pub fn new(path: PathBuf) -> Self {
+        JsonLinesSink { path, columns: Vec::new(), writer: None }
+    }
+}
+
+impl IterationSink for JsonLinesSink {
+    fn start(&mut self, _problem: &str, columns: &[String], _metadata: &Value) -> Result<(), String> {
+        let file = File::create(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        self.writer = Some(BufWriter::new(file));
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[Value]) -> Result<(), String> {
+        let mut hm = Map::new();
+        for (column, value) in self.columns.iter().zip(row.iter()) {
+            hm.insert(column.clone(), value.clone());
+        }
+
+        let mut line = Value::Object(hm).to_string();
+        line.push('\n');
+
+        let path = &self.path;
+        let writer = self.writer.as_mut().ok_or("JSON Lines sink not started")?;
+        writer.write_all(line.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn end_run(&mut self) -> Result<(), String> {
+        let writer = self.writer.as_mut().ok_or("JSON Lines sink not started")?;
+        writer.flush().map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    fn finish(&mut self, _metadata: &Value) -> Result<(), String> {
+        self.end_run()
+    }
+}
+
+/// Experiments, algorithm configurations, runs and iterations as related tables of a database
+/// that can be shared by many experiments.
+///
+/// Every iteration column becomes a column of the `iterations` table, added to an existing
+/// database if it is missing. Added columns are typed after the first row, columns missing
+/// from it are left without a type.
+pub struct SqliteSink {
+    pub path: PathBuf,
+    connection: Option<Connection>,
+    columns: Vec<String>,
+    /// The columns of the table were checked against the first row
+    columns_added: bool,
+    experiment_id: i64,
+    /// Repetition, algorithm index and id of the run being written
+    current_run: Option<(Value, Value, i64)>
+}
+
+const SQLITE_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS experiments (
+        id INTEGER PRIMARY KEY,
+        problem TEXT NOT NULL,
+        start_time TEXT,
+        end_time TEXT,
+        completed_runs INTEGER,
+        configuration TEXT NOT NULL,
+        environment TEXT
+    );
+    CREATE TABLE IF NOT EXISTS configurations (
+        experiment_id INTEGER NOT NULL REFERENCES experiments(id),
+        algorithm_index INTEGER NOT NULL,
+        algorithm_name TEXT,
+        elitism TEXT,
+        parameters TEXT,
+        PRIMARY KEY (experiment_id, algorithm_index)
+    );
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        experiment_id INTEGER NOT NULL REFERENCES experiments(id),
+        repetition INTEGER NOT NULL,
+        algorithm_index INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS iterations (
+        run_id INTEGER NOT NULL REFERENCES runs(id)
+    );
+    CREATE INDEX IF NOT EXISTS iterations_run ON iterations(run_id);
+";
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Declared type of a column whose first value is `value`
+fn column_type(value: &Value) -> &'static str {
+    match value {
+        Value::Number(n) if n.is_f64() => " REAL",
+        Value::Number(_) | Value::Bool(_) => " INTEGER",
+        Value::String(_) => " TEXT",
+        _ => ""
+    }
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or(f64::NAN))
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string())
+    }
+}
+
+impl SqliteSink {
+    pub fn new(path: PathBuf) -> Self {
+        SqliteSink {
+            path,
+            connection: None,
+            columns: Vec::new(),
+            columns_added: false,
+            experiment_id: 0,
+            current_run: None
+        }
+    }
+
+    fn error(&self, e: rusqlite::Error) -> String {
+        format!("{}: {}", self.path.display(), e)
+    }
+
+    fn connection(&self) -> Result<&Connection, String> {
+        self.connection.as_ref().ok_or_else(|| "SQLite sink not started".to_string())
+    }
+
+    /// Adds the iteration columns missing from the table, typed after the values of `row`
+    fn add_columns(&self, row: &[Value]) -> Result<(), String> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare("SELECT name FROM pragma_table_info('iterations')").map_err(|e| self.error(e))?;
+        let existing = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|e| self.error(e))?
+            .collect::<Result<Vec<String>, _>>().map_err(|e| self.error(e))?;
+
+        for (column, value) in self.columns.iter().zip(row).filter(|(c, _)| !existing.contains(c)) {
+            connection.execute(&format!("ALTER TABLE iterations ADD COLUMN {}{}", quote_identifier(column), column_type(value)), [])
+                .map_err(|e| self.error(e))?;
+        }
+        Ok(())
+    }
+
+    fn insert_configurations(&self, connection: &Connection, metadata: &Value) -> Result<(), String> {
+        let algorithms = metadata["configuration"]["algorithms"].as_array().cloned().unwrap_or_default();
+        for (i, algorithm) in algorithms.iter().enumerate() {
+            connection.execute(
+                "INSERT INTO configurations (experiment_id, algorithm_index, algorithm_name, elitism, parameters) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    self.experiment_id,
+                    algorithm["algorithm index"].as_i64().unwrap_or(i as i64),
+                    algorithm["algorithm name"].as_str(),
+                    algorithm["elitism"].as_str(),
+                    algorithm["algorithm config"].to_string()
+                ]
+            ).map_err(|e| self.error(e))?;
+        }
+        Ok(())
+    }
+
+    /// Id of the run a row belongs to, inserting the run when it is the first of its rows
+    fn run_id(&mut self, row: &[Value]) -> Result<i64, String> {
+        let position = |name: &str| self.columns.iter().position(|c| c == name);
+        let repetition = position("repetition").and_then(|i| row.get(i)).cloned().unwrap_or_default();
+        let algorithm = position("algorithm index").and_then(|i| row.get(i)).cloned().unwrap_or_default();
+
+        if let Some((r, a, id)) = &self.current_run {
+            if *r == repetition && *a == algorithm {
+                return Ok(*id);
+            }
+        }
+
+        let connection = self.connection()?;
+        connection.execute(
+            "INSERT INTO runs (experiment_id, repetition, algorithm_index) VALUES (?1, ?2, ?3)",
+            params![self.experiment_id, repetition.as_i64().unwrap_or(-1), algorithm.as_i64().unwrap_or(-1)]
+        ).map_err(|e| self.error(e))?;
+        let id = connection.last_insert_rowid();
+        self.current_run = Some((repetition, algorithm, id));
+        Ok(id)
+    }
+}
+
+impl IterationSink for SqliteSink {
+    fn start(&mut self, problem: &str, columns: &[String], metadata: &Value) -> Result<(), String> {
+        let connection = Connection::open(&self.path).map_err(|e| self.error(e))?;
+        connection.execute_batch(SQLITE_SCHEMA).map_err(|e| self.error(e))?;
+
+        connection.execute(
+            "INSERT INTO experiments (problem, start_time, configuration, environment) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                problem,
+                metadata["timing"]["start"].as_str(),
+                metadata["configuration"].to_string(),
+                metadata["environment"].to_string()
+            ]
+        ).map_err(|e| self.error(e))?;
+        self.experiment_id = connection.last_insert_rowid();
+        self.insert_configurations(&connection, metadata)?;
+
+        connection.execute_batch("BEGIN").map_err(|e| self.error(e))?;
+        self.columns = columns.to_vec();
+        self.columns_added = false;
+        self.current_run = None;
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[Value]) -> Result<(), String> {
+        if !self.columns_added {
+            self.add_columns(row)?;
+            self.columns_added = true;
+        }
+        let run_id = self.run_id(row)?;
+
+        let names: Vec<String> = self.columns.iter().map(|c| quote_identifier(c)).collect();
+        let placeholders: Vec<String> = (1..=self.columns.len() + 1).map(|i| format!("?{}", i)).collect();
+        let sql = format!("INSERT INTO iterations (run_id, {}) VALUES ({})", names.join(", "), placeholders.join(", "));
+
+        let values: Vec<SqlValue> = std::iter::once(SqlValue::Integer(run_id))
+            .chain(row.iter().map(sql_value))
+            .collect();
+
+        let connection = self.connection()?;
+        let mut statement = connection.prepare_cached(&sql).map_err(|e| self.error(e))?;
+        statement.execute(rusqlite::params_from_iter(values)).map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    fn end_run(&mut self) -> Result<(), String> {
+        self.connection()?.execute_batch("COMMIT; BEGIN").map_err(|e| self.error(e))
+    }
+
+    fn finish(&mut self, metadata: &Value) -> Result<(), String> {
+        let connection = self.connection()?;
+        connection.execute_batch("COMMIT").map_err(|e| self.error(e))?;
+        connection.execute(
+            "UPDATE experiments SET end_time = ?1, completed_runs = ?2 WHERE id = ?3",
+            params![metadata["timing"]["end"].as_str(), metadata["timing"]["completed runs"].as_i64(), self.experiment_id]
+        ).map_err(|e| self.error(e))?;
+        Ok(())
+    }
+}
+
+/// Sinks of the results of one problem, the file-based sinks are named after `results_csv`
+pub fn create_sinks(kinds: &[SinkKind], results_csv: &Path, database: &Path) -> Vec<Box<dyn IterationSink>> {
+    kinds.iter().map(|kind| -> Box<dyn IterationSink> {
+        match kind {
+            SinkKind::Csv => Box::new(CsvSink::new(results_csv.to_path_buf())),
+            SinkKind::JsonLines => Box::new(JsonLinesSink::new(results_csv.with_extension("jsonl"))),
+            SinkKind::Sqlite => Box::new(SqliteSink::new(database.to_path_buf()))
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn csv_cells_keep_their_formatting() {
+        assert_eq!(csv_cell(&json!(12)), "12");
+        assert_eq!(csv_cell(&json!(1.0)), "1");
+        assert_eq!(csv_cell(&json!(0.25)), "0.25");
+        assert_eq!(csv_cell(&json!("1;2;3")), "1;2;3");
+        assert_eq!(csv_cell(&Value::from(f64::NAN)), "");
+    }
+
+    #[test]
+    fn sql_values_keep_their_type() {
+        assert_eq!(sql_value(&json!(12)), SqlValue::Integer(12));
+        assert_eq!(sql_value(&json!(1.0)), SqlValue::Real(1.0));
+        assert_eq!(sql_value(&json!("a")), SqlValue::Text("a".to_string()));
+        assert_eq!(sql_value(&Value::Null), SqlValue::Null);
+        assert_eq!(column_type(&json!(12)), " INTEGER");
+        assert_eq!(column_type(&json!(1.0)), " REAL");
+        assert_eq!(column_type(&Value::Null), "");
+    }
+}
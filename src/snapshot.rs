@@ -0,0 +1,347 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+//
+// Images of the spatial grid, written without any plotting dependency
+//
+
+/// Cells drawn this many pixels wide at most, grids are scaled to about this size
+const IMAGE_SIZE: usize = 400;
+/// Colour index of empty cells, the colour map uses the others
+const EMPTY: u8 = 255;
+const GIF_FRAME_DELAY: u16 = 20;
+
+/// Anchors of a viridis-like colour map, from low to high values
+const COLOUR_MAP: [(u8, u8, u8); 5] = [(68, 1, 84), (59, 82, 139), (33, 145, 140), (94, 201, 98), (253, 231, 37)];
+
+fn palette() -> Vec<(u8, u8, u8)> {
+    let mut palette: Vec<(u8, u8, u8)> = (0..EMPTY as usize).map(|i| {
+        let t = i as f64 / (EMPTY - 1) as f64 * (COLOUR_MAP.len() - 1) as f64;
+        let k = (t.floor() as usize).min(COLOUR_MAP.len() - 2);
+        let f = t - k as f64;
+        let (a, b) = (COLOUR_MAP[k], COLOUR_MAP[k + 1]);
+        let mix = |x: u8, y: u8| (x as f64 + (y as f64 - x as f64) * f).round() as u8;
+        (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+    }).collect();
+    palette.push((40, 40, 40));
+    palette
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Svg
+}
+
+impl ImageFormat {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "ppm" => Ok(ImageFormat::Ppm),
+            "svg" => Ok(ImageFormat::Svg),
+            other => Err(format!("unknown image format \"{}\", expected ppm or svg", other))
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Svg => "svg"
+        }
+    }
+}
+
+/// Values of the cells of a one or two dimensional grid, `None` for empty cells
+#[derive(Clone, Debug)]
+pub struct Heatmap {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<Option<f64>>
+}
+
+impl Heatmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Heatmap {
+            width,
+            height,
+            values: vec![None; width * height]
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f64) {
+        self.values[y * self.width + x] = Some(value);
+    }
+
+    /// Colour index of every cell, values are scaled between the lowest and highest value of the map
+    fn indices(&self) -> Vec<u8> {
+        let present = self.values.iter().filter_map(|v| *v).filter(|v| v.is_finite());
+        let (min, max) = present.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        let range = if max > min { max - min } else { 1.0 };
+
+        self.values.iter().map(|v| match v {
+            Some(v) if v.is_finite() => (((v - min) / range) * (EMPTY - 1) as f64).round() as u8,
+            _ => EMPTY
+        }).collect()
+    }
+
+    fn cell_size(&self) -> usize {
+        (IMAGE_SIZE / self.width.max(self.height).max(1)).max(1)
+    }
+
+    /// Colour indices of the pixels of the upscaled image, row by row
+    fn pixels(&self) -> (usize, usize, Vec<u8>) {
+        let size = self.cell_size();
+        let indices = self.indices();
+        let (w, h) = (self.width * size, self.height * size);
+
+        let mut pixels = Vec::with_capacity(w * h);
+        for py in 0..h {
+            for px in 0..w {
+                pixels.push(indices[(py / size) * self.width + px / size]);
+            }
+        }
+        (w, h, pixels)
+    }
+
+    pub fn write(&self, path: &Path, format: ImageFormat) -> std::io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(path),
+            ImageFormat::Svg => self.write_svg(path)
+        }
+    }
+
+    fn write_ppm(&self, path: &Path) -> std::io::Result<()> {
+        let palette = palette();
+        let (w, h, pixels) = self.pixels();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "P6\n{} {}\n255\n", w, h)?;
+        for p in pixels {
+            let (r, g, b) = palette[p as usize];
+            writer.write_all(&[r, g, b])?;
+        }
+        writer.flush()
+    }
+
+    fn write_svg(&self, path: &Path) -> std::io::Result<()> {
+        let palette = palette();
+        let size = self.cell_size();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">",
+                 self.width * size, self.height * size)?;
+        for (i, (&index, value)) in self.indices().iter().zip(self.values.iter()).enumerate() {
+            let (r, g, b) = palette[index as usize];
+            let title = value.map_or("empty".to_string(), |v| v.to_string());
+            writeln!(writer, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"rgb({},{},{})\"><title>{}</title></rect>",
+                     (i % self.width) * size, (i / self.width) * size, size, size, r, g, b, title)?;
+        }
+        writeln!(writer, "</svg>")?;
+        writer.flush()
+    }
+}
+
+/// Writes heatmaps as an animated GIF, one frame per heatmap
+pub fn write_gif(path: &Path, frames: &[Heatmap]) -> std::io::Result<()> {
+    let frames: Vec<(usize, usize, Vec<u8>)> = frames.iter().map(|f| f.pixels()).collect();
+    let (w, h) = match frames.first() {
+        Some(&(w, h, _)) => (w, h),
+        None => return Ok(())
+    };
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"GIF89a")?;
+    writer.write_all(&(w as u16).to_le_bytes())?;
+    writer.write_all(&(h as u16).to_le_bytes())?;
+    // global colour table of 256 entries
+    writer.write_all(&[0xF7, 0, 0])?;
+    for (r, g, b) in palette() {
+        writer.write_all(&[r, g, b])?;
+    }
+    // loop forever
+    writer.write_all(&[0x21, 0xFF, 0x0B])?;
+    writer.write_all(b"NETSCAPE2.0")?;
+    writer.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+    for (fw, fh, pixels) in frames {
+        writer.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        writer.write_all(&GIF_FRAME_DELAY.to_le_bytes())?;
+        writer.write_all(&[0x00, 0x00])?;
+
+        writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        writer.write_all(&(fw as u16).to_le_bytes())?;
+        writer.write_all(&(fh as u16).to_le_bytes())?;
+        writer.write_all(&[0x00, 0x08])?;
+
+        for block in lzw_literals(&pixels).chunks(255) {
+            writer.write_all(&[block.len() as u8])?;
+            writer.write_all(block)?;
+        }
+        writer.write_all(&[0x00])?;
+    }
+
+    writer.write_all(&[0x3B])?;
+    writer.flush()
+}
+
+/// LZW stream of 8 bit pixels that never uses the dictionary: pixels are written as 9 bit literal codes,
+/// with a clear code often enough that the decoder never grows its code size
+fn lzw_literals(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u32 = 256;
+    const END: u32 = 257;
+    const LITERALS_PER_CLEAR: usize = 250;
+
+    let mut bytes = Vec::with_capacity(pixels.len() * 9 / 8 + 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    let codes = pixels.chunks(LITERALS_PER_CLEAR)
+        .flat_map(|chunk| std::iter::once(CLEAR).chain(chunk.iter().map(|&p| p as u32)))
+        .chain(std::iter::once(END));
+    for code in codes {
+        buffer |= code << bits;
+        bits += 9;
+        while bits >= 8 {
+            bytes.push((buffer & 0xFF) as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    }
+    if bits > 0 {
+        bytes.push(buffer as u8);
+    }
+
+    bytes
+}
+
+/// Snapshots of the grid of one run, in a folder of their own
+pub struct SnapshotRecorder {
+    pub folder: PathBuf,
+    pub format: ImageFormat,
+    /// Frames kept in memory for the animation, one list per metric
+    pub animations: Option<Vec<(String, Vec<Heatmap>)>>
+}
+
+impl SnapshotRecorder {
+    pub fn new(folder: PathBuf, format: ImageFormat, animate: bool) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&folder)?;
+        Ok(SnapshotRecorder {
+            folder,
+            format,
+            animations: if animate { Some(Vec::new()) } else { None }
+        })
+    }
+
+    /// Writes the heatmap of every metric at the given iteration
+    pub fn record(&mut self, iteration: u64, heatmaps: Vec<(&str, Heatmap)>) -> std::io::Result<()> {
+        for (metric, heatmap) in heatmaps {
+            let file_name = format!("{}_{:08}.{}", metric.replace(' ', "_"), iteration, self.format.extension());
+            heatmap.write(&self.folder.join(file_name), self.format)?;
+
+            if let Some(animations) = &mut self.animations {
+                match animations.iter_mut().find(|(m, _)| m == metric) {
+                    Some((_, frames)) => frames.push(heatmap),
+                    None => animations.push((metric.to_string(), vec![heatmap]))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles the recorded frames of every metric as animated GIFs
+    pub fn finish(&self) -> std::io::Result<()> {
+        if let Some(animations) = &self.animations {
+            for (metric, frames) in animations {
+                write_gif(&self.folder.join(format!("{}.gif", metric.replace(' ', "_"))), frames)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Codes of a stream of 9 bit codes, least significant bits first
+    fn codes(bytes: &[u8]) -> Vec<u32> {
+        let mut codes = Vec::new();
+        let (mut buffer, mut bits) = (0u32, 0);
+        for &byte in bytes {
+            buffer |= (byte as u32) << bits;
+            bits += 8;
+            if bits >= 9 {
+                codes.push(buffer & 0x1FF);
+                buffer >>= 9;
+                bits -= 9;
+            }
+        }
+        codes
+    }
+
+    /// Pixels of a stream of literal codes, checking the clear codes keep the decoder at 9 bit codes
+    fn decode(bytes: &[u8]) -> Vec<u8> {
+        let mut pixels = Vec::new();
+        let mut since_clear = None;
+        for code in codes(bytes) {
+            match code {
+                256 => since_clear = Some(0),
+                257 => return pixels,
+                literal => {
+                    let n = since_clear.expect("a literal before the first clear code");
+                    // the decoder adds an entry per code after the first, 9 bit codes stop at 511
+                    assert!(258 + n < 512);
+                    since_clear = Some(n + 1);
+                    pixels.push(literal as u8);
+                }
+            }
+        }
+        panic!("no end code");
+    }
+
+    #[test]
+    fn lzw_literals_round_trip() {
+        assert_eq!(decode(&lzw_literals(&[])), Vec::<u8>::new());
+
+        let pixels: Vec<u8> = (0..1000).map(|i| (i * 7 % 256) as u8).collect();
+        assert_eq!(decode(&lzw_literals(&pixels)), pixels);
+    }
+
+    #[test]
+    fn write_gif_frames() {
+        let mut first = Heatmap::new(3, 2);
+        first.set(0, 0, 1.0);
+        first.set(2, 1, 3.0);
+        let mut second = first.clone();
+        second.set(1, 0, 2.0);
+
+        let path = std::env::temp_dir().join(format!("genetic_testbed_snapshot_{}.gif", std::process::id()));
+        write_gif(&path, &[first.clone(), second.clone()]).unwrap();
+        let gif = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (w, h, _) = first.pixels();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([gif[6], gif[7]]) as usize, w);
+        assert_eq!(u16::from_le_bytes([gif[8], gif[9]]) as usize, h);
+        assert_eq!(*gif.last().unwrap(), 0x3B);
+
+        // header, global colour table and looping extension, then one frame per heatmap
+        let mut at = 13 + 3 * 256 + 19;
+        for heatmap in &[first, second] {
+            assert_eq!(gif[at], 0x21);
+            at += 8;
+            assert_eq!(gif[at], 0x2C);
+            at += 11;
+            let mut data = Vec::new();
+            while gif[at] != 0 {
+                let len = gif[at] as usize;
+                data.extend_from_slice(&gif[at + 1..at + 1 + len]);
+                at += 1 + len;
+            }
+            at += 1;
+            assert_eq!(decode(&data), heatmap.pixels().2);
+        }
+        assert_eq!(at, gif.len() - 1);
+    }
+}
@@ -0,0 +1,564 @@
+use statistical::{mean, variance};
+use rand::{thread_rng, Rng};
+use serde_json::Value;
+use crate::common::Parametrized;
+use crate::organism::Diversity;
+
+/// Fixed bins used to build comparable histograms across iterations and runs
+#[derive(Copy, Clone, Debug)]
+pub enum Binning {
+    Linear { min: f64, max: f64, bins: usize },
+    /// Bins evenly spaced in log10 space, values must be positive
+    Log10 { min: f64, max: f64, bins: usize }
+}
+
+impl Binning {
+    fn bins(&self) -> usize {
+        match *self {
+            Binning::Linear { bins, .. } | Binning::Log10 { bins, .. } => bins
+        }
+    }
+
+    /// Bin of a value, values outside of the range fall in the first or last bin
+    fn bin(&self, value: f64) -> usize {
+        let (x, min, max, bins) = match *self {
+            Binning::Linear { min, max, bins } => (value, min, max, bins),
+            Binning::Log10 { min, max, bins } => (value.max(f64::MIN_POSITIVE).log10(), min.log10(), max.log10(), bins)
+        };
+        let relative = (x - min) / (max - min);
+        ((relative * bins as f64).floor().max(0.0) as usize).min(bins - 1)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    pub mean: f64,
+    pub variance: f64,
+    pub histogram: Vec<usize>
+}
+
+impl Distribution {
+    pub fn of(values: &[f64], binning: Binning) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let m = mean(values);
+        let v = if values.len() > 1 { variance(values, Some(m)) } else { 0.0 };
+
+        let mut histogram = vec![0; binning.bins()];
+        for &x in values {
+            histogram[binning.bin(x)] += 1;
+        }
+
+        Some(Distribution {
+            mean: m,
+            variance: v,
+            histogram
+        })
+    }
+
+    /// Histogram counts separated by semicolons, to fit in a single CSV cell
+    pub fn histogram_string(&self) -> String {
+        self.histogram.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(";")
+    }
+}
+
+pub const MUTATION_CHANCE_BINS: Binning = Binning::Linear { min: 0.0, max: 1.0, bins: 10 };
+pub const MUTATION_SIZE_BINS: Binning = Binning::Log10 { min: 1e-4, max: 10.0, bins: 10 };
+
+/// Distribution of the hyperparameters organisms were produced with
+#[derive(Clone, Debug, Default)]
+pub struct HyperparameterStats {
+    pub mutation_chance: Option<Distribution>,
+    pub mutation_size: Option<Distribution>
+}
+
+impl HyperparameterStats {
+    /// Statistics of hyperparameter values, given in the order of `Hyperparameter::to_values`
+    pub fn of<'a, I: Iterator<Item=&'a Vec<f64>>>(values: I) -> Self {
+        let mut chances = Vec::new();
+        let mut sizes = Vec::new();
+
+        for v in values {
+            if let Some(&c) = v.first() {
+                chances.push(c);
+            }
+            if let Some(&s) = v.get(1) {
+                sizes.push(s);
+            }
+        }
+
+        HyperparameterStats {
+            mutation_chance: Distribution::of(&chances, MUTATION_CHANCE_BINS),
+            mutation_size: Distribution::of(&sizes, MUTATION_SIZE_BINS)
+        }
+    }
+
+    pub fn header(prefix: &str) -> Vec<String> {
+        let mut header = Vec::with_capacity(6);
+        for hyperparameter in &["mutation chance", "mutation size"] {
+            header.push(format!("{} {} mean", prefix, hyperparameter));
+            header.push(format!("{} {} variance", prefix, hyperparameter));
+            header.push(format!("{} {} histogram", prefix, hyperparameter));
+        }
+        header
+    }
+
+    pub fn row(stats: &Option<HyperparameterStats>) -> Vec<Value> {
+        let mut row = Vec::with_capacity(6);
+        let empty = HyperparameterStats::default();
+        let stats = stats.as_ref().unwrap_or(&empty);
+
+        for distribution in &[&stats.mutation_chance, &stats.mutation_size] {
+            match distribution {
+                Some(d) => {
+                    row.push(d.mean.into());
+                    row.push(d.variance.into());
+                    row.push(d.histogram_string().into());
+                },
+                None => row.extend(vec![Value::Null; 3])
+            }
+        }
+        row
+    }
+}
+
+/// Outcomes of the offspring produced during a logging window
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct OperatorStats {
+    pub unchanged_mutations: u64,
+    pub accepted: u64,
+    pub rejected: u64,
+    /// Offspring scoring strictly better than the organism they competed with
+    pub improvements: u64,
+    /// Offspring scoring exactly as the organism they competed with
+    pub neutral_moves: u64,
+    /// Accepted offspring scoring worse than the organism they replaced
+    pub accepted_worsening_moves: u64
+}
+
+impl OperatorStats {
+    pub fn record(&mut self, changed: bool, accepted: bool, offspring_score: f64, competitor_score: Option<f64>) {
+        if !changed {
+            self.unchanged_mutations += 1;
+        }
+
+        if accepted {
+            self.accepted += 1;
+        } else {
+            self.rejected += 1;
+        }
+
+        if let Some(competitor) = competitor_score {
+            if offspring_score > competitor {
+                self.improvements += 1;
+            } else if offspring_score == competitor {
+                self.neutral_moves += 1;
+            } else if accepted {
+                self.accepted_worsening_moves += 1;
+            }
+        }
+    }
+
+    pub fn header() -> Vec<String> {
+        [
+            "unchanged mutations",
+            "accepted offspring",
+            "rejected offspring",
+            "improvements",
+            "neutral moves",
+            "accepted worsening moves"
+        ].iter().map(|s| s.to_string()).collect()
+    }
+
+    pub fn row(stats: &Option<OperatorStats>) -> Vec<Value> {
+        match stats {
+            Some(s) => vec![
+                s.unchanged_mutations.into(),
+                s.accepted.into(),
+                s.rejected.into(),
+                s.improvements.into(),
+                s.neutral_moves.into(),
+                s.accepted_worsening_moves.into()
+            ],
+            None => vec![Value::Null; 6]
+        }
+    }
+}
+
+/// Ways to measure the genetic diversity of a population, from exact and quadratic to cheap and genome specific
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DiversityEstimator {
+    /// Exact mean distance over all pairs of organisms, quadratic in the population size
+    AllPairs,
+    /// Mean distance over random pairs of organisms, with a 95% confidence interval
+    SampledPairs { samples: usize },
+    CentroidDispersion,
+    LocusEntropy,
+    EdgeEntropy,
+    DistinctGenotypes
+}
+
+impl DiversityEstimator {
+    /// Parses `all-pairs`, `sampled-pairs:<samples>`, `centroid`, `locus-entropy`, `edge-entropy` or `distinct`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+        match parts[0] {
+            "all-pairs" => Ok(DiversityEstimator::AllPairs),
+            "sampled-pairs" => {
+                let samples = parts.get(1)
+                    .ok_or_else(|| format!("diversity estimator \"{}\" is missing the number of samples", text))?
+                    .parse::<usize>().map_err(|e| format!("diversity estimator \"{}\": {}", text, e))?;
+                if samples < 2 {
+                    return Err(format!("diversity estimator \"{}\" needs at least 2 samples", text));
+                }
+                Ok(DiversityEstimator::SampledPairs { samples })
+            },
+            "centroid" => Ok(DiversityEstimator::CentroidDispersion),
+            "locus-entropy" => Ok(DiversityEstimator::LocusEntropy),
+            "edge-entropy" => Ok(DiversityEstimator::EdgeEntropy),
+            "distinct" => Ok(DiversityEstimator::DistinctGenotypes),
+            other => Err(format!("unknown diversity estimator \"{}\"", other))
+        }
+    }
+
+    /// Parses a comma separated list of estimators
+    pub fn parse_list(text: &str) -> Result<Vec<Self>, String> {
+        text.split(',').map(|s| Self::parse(s.trim())).collect()
+    }
+}
+
+impl Parametrized for DiversityEstimator {
+    fn parameters(&self) -> Value {
+        match *self {
+            DiversityEstimator::AllPairs => "all pairs".into(),
+            DiversityEstimator::SampledPairs { samples } => format!("{} sampled pairs", samples).into(),
+            DiversityEstimator::CentroidDispersion => "centroid dispersion".into(),
+            DiversityEstimator::LocusEntropy => "locus entropy".into(),
+            DiversityEstimator::EdgeEntropy => "edge entropy".into(),
+            DiversityEstimator::DistinctGenotypes => "distinct genotypes".into()
+        }
+    }
+}
+
+/// Diversity measures of a population, absent when not configured or not defined for the genome type
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiversityStats {
+    pub mean_distance: Option<f64>,
+    /// Half width of the 95% confidence interval of the sampled mean distance
+    pub mean_distance_error: Option<f64>,
+    pub centroid_dispersion: Option<f64>,
+    pub locus_entropy: Option<f64>,
+    pub edge_entropy: Option<f64>,
+    pub distinct_genotypes: Option<usize>
+}
+
+impl DiversityStats {
+    pub fn of<V: Diversity>(population: &[&V], estimators: &[DiversityEstimator]) -> Self {
+        let mut stats = DiversityStats::default();
+        let n = population.len();
+
+        for estimator in estimators {
+            match *estimator {
+                DiversityEstimator::AllPairs => {
+                    if n > 1 {
+                        let mut distances: Vec<f64> = Vec::with_capacity(n * (n - 1) / 2);
+                        for i in 0..n {
+                            for j in (i + 1)..n {
+                                distances.push(population[i].distance_to(population[j]));
+                            }
+                        }
+                        stats.mean_distance = Some(mean(&distances)).filter(|m| m.is_finite());
+                    }
+                },
+                DiversityEstimator::SampledPairs { samples } => {
+                    if n > 1 {
+                        let mut rng = thread_rng();
+                        let distances: Vec<f64> = (0..samples).map(|_| {
+                            let i = rng.gen_range(0, n);
+                            // second organism drawn among the n-1 others
+                            let j = (i + rng.gen_range(1, n)) % n;
+                            population[i].distance_to(population[j])
+                        }).collect();
+
+                        let m = mean(&distances);
+                        if m.is_finite() {
+                            let v = variance(&distances, Some(m));
+                            stats.mean_distance = Some(m);
+                            stats.mean_distance_error = Some(1.96 * (v / samples as f64).sqrt());
+                        }
+                    }
+                },
+                DiversityEstimator::CentroidDispersion => stats.centroid_dispersion = V::centroid_dispersion(population),
+                DiversityEstimator::LocusEntropy => stats.locus_entropy = V::locus_entropy(population),
+                DiversityEstimator::EdgeEntropy => stats.edge_entropy = V::edge_entropy(population),
+                DiversityEstimator::DistinctGenotypes => stats.distinct_genotypes = Some(V::distinct_genotypes(population))
+            }
+        }
+
+        stats
+    }
+
+    pub fn header() -> Vec<String> {
+        [
+            "mean genetic distance",
+            "mean genetic distance 95% CI half-width",
+            "centroid dispersion",
+            "locus entropy",
+            "edge entropy",
+            "distinct genotypes"
+        ].iter().map(|s| s.to_string()).collect()
+    }
+
+    pub fn row(stats: &Option<DiversityStats>) -> Vec<Value> {
+        let empty = DiversityStats::default();
+        let stats = stats.as_ref().unwrap_or(&empty);
+        vec![
+            stats.mean_distance.into(),
+            stats.mean_distance_error.into(),
+            stats.centroid_dispersion.into(),
+            stats.locus_entropy.into(),
+            stats.edge_entropy.into(),
+            stats.distinct_genotypes.into()
+        ]
+    }
+}
+
+/// Which iterations of a run are written to the results
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoggingPolicy {
+    EveryIterations(u64),
+    /// Checkpoints evenly spaced on a logarithmic scale
+    Logarithmic { points_per_decade: u32 },
+    /// Only when the best score so far improves
+    Improvements,
+    /// Every time the number of generations crosses a multiple of the given value
+    EveryGenerations(f64)
+}
+
+impl LoggingPolicy {
+    /// Parses `every:<iterations>`, `log:<points per decade>`, `improvements` or `generations:<generations>`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+        let argument = || parts.get(1)
+            .ok_or_else(|| format!("logging policy \"{}\" is missing its parameter", text));
+        match parts[0] {
+            "every" => match argument()?.parse::<u64>() {
+                Ok(n) if n > 0 => Ok(LoggingPolicy::EveryIterations(n)),
+                _ => Err(format!("logging policy \"{}\" needs a positive number of iterations", text))
+            },
+            "log" => match argument()?.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(LoggingPolicy::Logarithmic { points_per_decade: n }),
+                _ => Err(format!("logging policy \"{}\" needs a positive number of points per decade", text))
+            },
+            "improvements" => Ok(LoggingPolicy::Improvements),
+            "generations" => match argument()?.parse::<f64>() {
+                Ok(g) if g > 0.0 => Ok(LoggingPolicy::EveryGenerations(g)),
+                _ => Err(format!("logging policy \"{}\" needs a positive number of generations", text))
+            },
+            other => Err(format!("unknown logging policy \"{}\"", other))
+        }
+    }
+
+    pub fn schedule(&self) -> LogSchedule {
+        LogSchedule {
+            policy: *self,
+            next_checkpoint: 0,
+            last_generation_step: None
+        }
+    }
+}
+
+impl Parametrized for LoggingPolicy {
+    fn parameters(&self) -> Value {
+        match *self {
+            LoggingPolicy::EveryIterations(n) => format!("every {} iterations", n).into(),
+            LoggingPolicy::Logarithmic { points_per_decade } => format!("{} logarithmic checkpoints per decade", points_per_decade).into(),
+            LoggingPolicy::Improvements => "best score improvements".into(),
+            LoggingPolicy::EveryGenerations(g) => format!("every {} generations", g).into()
+        }
+    }
+}
+
+/// State of a logging policy during a run
+pub struct LogSchedule {
+    policy: LoggingPolicy,
+    /// Index of the next logarithmic checkpoint, computed from its index so that decades stay exact
+    next_checkpoint: u32,
+    last_generation_step: Option<i64>
+}
+
+impl LogSchedule {
+    /// Whether an iteration is logged, the first and last iterations of a run always are
+    pub fn should_log(&mut self, iteration: u64, generations: f64, improved: bool, last: bool) -> bool {
+        let logged = match self.policy {
+            LoggingPolicy::EveryIterations(n) => iteration.is_multiple_of(n),
+            LoggingPolicy::Logarithmic { points_per_decade } => {
+                let checkpoint = |k: u32| 10f64.powf(k as f64 / points_per_decade as f64);
+                if iteration as f64 >= checkpoint(self.next_checkpoint) {
+                    // checkpoints closer than an iteration apart are logged once
+                    while checkpoint(self.next_checkpoint) <= iteration as f64 {
+                        self.next_checkpoint += 1;
+                    }
+                    true
+                } else {
+                    false
+                }
+            },
+            LoggingPolicy::Improvements => improved,
+            LoggingPolicy::EveryGenerations(g) => {
+                let step = (generations / g).floor() as i64;
+                let crossed = self.last_generation_step.is_none_or(|last| step > last);
+                self.last_generation_step = Some(step);
+                crossed
+            }
+        };
+        logged || iteration == 0 || last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::rastrigin::RastriginValue;
+
+    /// Iterations logged among the first `n` of a run that improves at every iteration
+    fn logged(policy: LoggingPolicy, n: u64) -> Vec<u64> {
+        let mut schedule = policy.schedule();
+        (0..n).filter(|&i| schedule.should_log(i, 0.0, true, false)).collect()
+    }
+
+    #[test]
+    fn should_log_every_iterations() {
+        assert_eq!(logged(LoggingPolicy::EveryIterations(3), 10), vec![0, 3, 6, 9]);
+        assert_eq!(logged(LoggingPolicy::EveryIterations(1), 3), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn should_log_logarithmic_checkpoints() {
+        assert_eq!(logged(LoggingPolicy::Logarithmic { points_per_decade: 1 }, 1000), vec![0, 1, 10, 100]);
+        // decades are logged exactly, 10^(k/2) is rounded up to the next iteration
+        assert_eq!(logged(LoggingPolicy::Logarithmic { points_per_decade: 2 }, 101), vec![0, 1, 4, 10, 32, 100]);
+    }
+
+    #[test]
+    fn should_log_improvements_first_and_last() {
+        let mut schedule = LoggingPolicy::Improvements.schedule();
+        let improved = [false, true, false, false, true, false];
+        let logged: Vec<usize> = (0..improved.len())
+            .filter(|&i| schedule.should_log(i as u64, 0.0, improved[i], i == improved.len() - 1))
+            .collect();
+        assert_eq!(logged, vec![0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn should_log_generation_crossings() {
+        let mut schedule = LoggingPolicy::EveryGenerations(1.0).schedule();
+        let generations = [0.0, 0.5, 1.0, 1.5, 2.2, 4.1, 4.2];
+        let logged: Vec<usize> = (0..generations.len())
+            .filter(|&i| schedule.should_log(i as u64, generations[i], false, false))
+            .collect();
+        assert_eq!(logged, vec![0, 2, 4, 5]);
+    }
+
+    #[test]
+    fn out_of_range_values_fall_in_the_outer_bins() {
+        let linear = Binning::Linear { min: 0.0, max: 1.0, bins: 10 };
+        assert_eq!(linear.bin(0.05), 0);
+        assert_eq!(linear.bin(0.55), 5);
+        assert_eq!(linear.bin(1.0), 9);
+        assert_eq!(linear.bin(-3.0), 0);
+        assert_eq!(linear.bin(7.0), 9);
+
+        let log = Binning::Log10 { min: 1e-4, max: 10.0, bins: 5 };
+        assert_eq!(log.bin(2e-4), 0);
+        assert_eq!(log.bin(0.5), 3);
+        assert_eq!(log.bin(0.0), 0);
+        assert_eq!(log.bin(-1.0), 0);
+        assert_eq!(log.bin(1e6), 4);
+    }
+
+    #[test]
+    fn hyperparameter_distributions() {
+        let continuous = [vec![0.1, 0.01], vec![0.3, 1.0]];
+        let stats = HyperparameterStats::of(continuous.iter());
+        let chance = stats.mutation_chance.unwrap();
+        assert!((chance.mean - 0.2).abs() < 1e-12);
+        assert_eq!(chance.histogram, vec![0, 1, 0, 1, 0, 0, 0, 0, 0, 0]);
+        let size = stats.mutation_size.unwrap();
+        assert!((size.mean - 0.505).abs() < 1e-12);
+        assert_eq!(size.histogram.iter().sum::<usize>(), 2);
+
+        let discrete = [vec![0.1], vec![0.3]];
+        let stats = HyperparameterStats::of(discrete.iter());
+        assert!(stats.mutation_chance.is_some());
+        assert_eq!(stats.mutation_size, None);
+        assert_eq!(HyperparameterStats::row(&Some(stats))[3..], [Value::Null, Value::Null, Value::Null]);
+
+        assert_eq!(HyperparameterStats::of(Vec::<Vec<f64>>::new().iter()).mutation_chance, None);
+    }
+
+    #[test]
+    fn record_operator_outcomes() {
+        let mut stats = OperatorStats::default();
+        // unchanged mutation, rejected as it is no better
+        stats.record(false, false, 1.0, Some(1.0));
+        // strict improvement
+        stats.record(true, true, 2.0, Some(1.0));
+        // neutral move, accepted
+        stats.record(true, true, 1.0, Some(1.0));
+        // accepted worsening move, then a rejected one that is not counted as worsening
+        stats.record(true, true, 0.5, Some(1.0));
+        stats.record(true, false, 0.5, Some(1.0));
+        // MAP-Elites discovery of an empty niche, with no competitor
+        stats.record(true, true, 0.1, None);
+
+        assert_eq!(stats, OperatorStats {
+            unchanged_mutations: 1,
+            accepted: 4,
+            rejected: 2,
+            improvements: 1,
+            neutral_moves: 2,
+            accepted_worsening_moves: 1
+        });
+    }
+
+    fn points(values: &[f64]) -> Vec<RastriginValue> {
+        values.iter().map(|&x| RastriginValue { value: vec![x] }).collect()
+    }
+
+    #[test]
+    fn mean_distance_over_all_pairs() {
+        // distances 1, 3 and 2
+        let points = points(&[0.0, 1.0, 3.0]);
+        let population: Vec<&RastriginValue> = points.iter().collect();
+        let stats = DiversityStats::of(&population, &[DiversityEstimator::AllPairs, DiversityEstimator::DistinctGenotypes]);
+        assert_eq!(stats.mean_distance, Some(2.0));
+        assert_eq!(stats.mean_distance_error, None);
+        assert_eq!(stats.distinct_genotypes, Some(3));
+        assert_eq!(stats.locus_entropy, None);
+
+        assert_eq!(DiversityStats::of(&population[..1], &[DiversityEstimator::AllPairs]).mean_distance, None);
+    }
+
+    #[test]
+    fn mean_distance_over_sampled_pairs() {
+        // a single pair is always the one sampled
+        let pair = points(&[0.0, 1.5]);
+        let population: Vec<&RastriginValue> = pair.iter().collect();
+        let stats = DiversityStats::of(&population, &[DiversityEstimator::SampledPairs { samples: 10 }]);
+        assert_eq!(stats.mean_distance, Some(1.5));
+        assert_eq!(stats.mean_distance_error, Some(0.0));
+
+        // distances 1, 2 and 3 equally likely: mean 2 and variance 2/3
+        let points = points(&[0.0, 1.0, 3.0]);
+        let population: Vec<&RastriginValue> = points.iter().collect();
+        let samples = 3000;
+        let stats = DiversityStats::of(&population, &[DiversityEstimator::SampledPairs { samples }]);
+        let error = stats.mean_distance_error.unwrap();
+        let expected_error = 1.96 * (2.0 / 3.0 / samples as f64).sqrt();
+        assert!((error - expected_error).abs() < 0.1 * expected_error);
+        assert!((stats.mean_distance.unwrap() - 2.0).abs() < 3.0 * error);
+    }
+}